@@ -0,0 +1,3 @@
+
+drop index jobs_pending;
+drop table jobs;
@@ -0,0 +1,24 @@
+
+create table jobs (
+  id bigint not null primary key default nextval('object_id_seq'),
+  job_type varchar(255) not null,
+  payload jsonb not null,
+  status varchar(16) not null default 'pending',
+  create_date timestamptz not null default current_timestamp,
+  run_at timestamptz not null default current_timestamp,
+  attempts integer not null default 0,
+  max_attempts integer not null,
+  last_error text default null,
+  last_attempt_date timestamptz default null
+);
+comment on table jobs is 'Background jobs queue';
+comment on column jobs.job_type is 'Type of job, selects the handler';
+comment on column jobs.payload is 'Job data passed to the handler';
+comment on column jobs.status is 'Either pending or dead';
+comment on column jobs.run_at is 'Job is not processed earlier than this date';
+comment on column jobs.attempts is 'How many times job was tried';
+comment on column jobs.max_attempts is 'After this number of failed attempts job is moved to dead letters';
+comment on column jobs.last_error is 'Error of the last failed attempt';
+comment on column jobs.last_attempt_date is 'Date of the last failed attempt';
+
+create index jobs_pending on jobs(run_at) where status = 'pending';
@@ -6,6 +6,13 @@
 pub const MIGRATIONS: diesel_migrations::EmbeddedMigrations =
     diesel_migrations::embed_migrations!();
 
+/// Handlers of background jobs stored in this DB. Plugins register their handlers here on initialization
+pub static JOBS: database_pg::jobs::Registry = database_pg::jobs::Registry::new();
+
+/// Soft-deleted tables purged after retention. Plugins register their tables here on initialization
+pub static SOFT_DELETE: database_pg::soft_delete::Registry =
+    database_pg::soft_delete::Registry::new();
+
 #[derive(Clone)]
 pub struct DB {
     pub pool: Arc<database_pg::Pool>,
@@ -13,23 +20,29 @@ pub struct DB {
 }
 
 impl DB {
+    /// Opens DB, runs pending migrations and starts background jobs
     pub async fn new(metadata: &crate::Metadata) -> Result<Self>
     where
         Self: Sized,
     {
+        let r = Self::open(metadata)?;
+        r.run_pending_migrations().await?;
+        database_pg::soft_delete::start_purge(&r.pool, &SOFT_DELETE, &JOBS)?;
+        database_pg::jobs::spawn_workers(r.pool.clone(), &JOBS);
+
+        Ok(r)
+    }
+
+    /// Opens connection pools of DB without running migrations and jobs
+    pub fn open(metadata: &crate::Metadata) -> Result<Self> {
         use webapp_core::plugin::PluginMetadata;
         let pool = database_pg::Pool::new(metadata.plugin_name(), &metadata.configs_path)?;
         let sync_pool =
             database_pg::sync::Pool::new(metadata.plugin_name(), &metadata.configs_path)?;
-
-        let r = Self {
+        Ok(Self {
             pool: Arc::new(pool),
             sync_pool: Arc::new(sync_pool),
-        };
-
-        r.run_pending_migrations().await?;
-
-        Ok(r)
+        })
     }
 
     async fn run_pending_migrations(&self) -> Result<()> {
@@ -46,12 +59,24 @@ async fn run_pending_migrations(&self) -> Result<()> {
     }
 }
 
+impl AsRef<database_pg::Pool> for DB {
+    fn as_ref(&self) -> &database_pg::Pool {
+        &self.pool
+    }
+}
+
 impl webapp_core::plugin::Plugin for DB {
     fn webapp_initializer(
         &self,
         service_config: &mut actix_web::web::ServiceConfig,
     ) -> utoipa::openapi::OpenApi {
         let _ = service_config.app_data(Data::new(self.clone()));
+        if let Ok(keyring) = self.pool.keyring() {
+            let key = keyring.derive_secret("react-admin cursor");
+            let _ = service_config.app_data(Data::new(react_admin::cursor::CursorKey::new(
+                key.unsecure(),
+            )));
+        }
 
         #[derive(OpenApi)]
         #[openapi()]
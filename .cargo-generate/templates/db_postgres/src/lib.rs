@@ -41,4 +41,9 @@ async fn init_plugin(&self) -> Result<Box<dyn webapp_core::plugin::Plugin>>
         let plugin = crate::db::DB::new(self).await?;
         Ok(Box::new(plugin))
     }
+
+    async fn init_documentation(&self) -> Result<Box<dyn webapp_core::plugin::Plugin>> {
+        let plugin = crate::db::DB::open(self)?;
+        Ok(Box::new(plugin))
+    }
 }
@@ -0,0 +1,6 @@
+
+delete from user_session;
+alter table user_session drop column token_hash;
+alter table user_session add column token char(64) not null unique;
+comment on column user_session.token is 'Session token';
+create index user_session_token on user_session(token);
@@ -0,0 +1,7 @@
+
+-- Plaintext tokens cannot be converted to keyed hashes without the key, all existing sessions are closed
+delete from user_session;
+drop index user_session_token;
+alter table user_session drop column token;
+alter table user_session add column token_hash bytea not null unique;
+comment on column user_session.token_hash is 'Keyed hash of session token';
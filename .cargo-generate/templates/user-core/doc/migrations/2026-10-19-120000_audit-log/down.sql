@@ -0,0 +1,5 @@
+
+drop index audit_log_entity;
+drop index audit_log_actor_id;
+drop index audit_log_create_date;
+drop table audit_log;
@@ -0,0 +1,25 @@
+
+create table audit_log (
+  id bigint not null primary key default nextval('object_id_seq'),
+  create_date timestamptz not null default current_timestamp,
+  actor_id bigint default null references "user"(id) on delete set null,
+  action varchar(255) not null,
+  entity varchar(255) not null,
+  entity_id bigint default null,
+  changes jsonb not null,
+  client_address inet default null,
+  request_id varchar(64) default null
+);
+comment on table audit_log is 'Log of changes made by users';
+comment on column audit_log.create_date is 'Date when change was made';
+comment on column audit_log.actor_id is 'User who made the change';
+comment on column audit_log.action is 'Action name';
+comment on column audit_log.entity is 'Type of changed object';
+comment on column audit_log.entity_id is 'ID of changed object';
+comment on column audit_log.changes is 'Changed fields with values before and after the change';
+comment on column audit_log.client_address is 'Address of the user who made the change';
+comment on column audit_log.request_id is 'ID of HTTP request, as in x-request-id response header';
+
+create index audit_log_create_date on audit_log(create_date);
+create index audit_log_actor_id on audit_log(actor_id);
+create index audit_log_entity on audit_log(entity, entity_id);
@@ -0,0 +1,9 @@
+drop index user_session_deleted_at;
+drop index user_deleted_at;
+
+delete from "user" where deleted_at is not null;
+drop index user_username_key;
+alter table "user" add constraint user_username_key unique (username);
+
+alter table user_session drop column deleted_at;
+alter table "user" drop column deleted_at;
@@ -0,0 +1,11 @@
+alter table "user" add column deleted_at timestamptz default null;
+comment on column "user".deleted_at is 'Date when user was deleted, deleted users can be restored until purged';
+alter table user_session add column deleted_at timestamptz default null;
+comment on column user_session.deleted_at is 'Date when session was deleted together with its user';
+
+-- Username of deleted user can be taken by a new user, the deleted one cannot be restored then
+alter table "user" drop constraint user_username_key;
+create unique index user_username_key on "user"(username) where deleted_at is null;
+
+create index user_deleted_at on "user"(deleted_at) where deleted_at is not null;
+create index user_session_deleted_at on user_session(deleted_at) where deleted_at is not null;
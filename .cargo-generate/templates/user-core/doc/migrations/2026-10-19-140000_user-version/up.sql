@@ -0,0 +1,2 @@
+alter table "user" add column version bigint not null default 1;
+comment on column "user".version is 'Incremented on every change of user by API, sent as ETag to detect concurrent changes';
@@ -0,0 +1,3 @@
+drop index user_session_expires_at;
+alter table user_session drop column expires_at;
+alter table user_session drop column remember;
@@ -0,0 +1,8 @@
+alter table user_session add column remember boolean not null default false;
+comment on column user_session.remember is 'Session is created with "remember me" and has longer lifetime';
+alter table user_session add column expires_at timestamptz;
+comment on column user_session.expires_at is 'Session is not valid after this date';
+update user_session set expires_at = last_seen_date + interval '1 day';
+alter table user_session alter column expires_at set not null;
+
+create index user_session_expires_at on user_session(expires_at);
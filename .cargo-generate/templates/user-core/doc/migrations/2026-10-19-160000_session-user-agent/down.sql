@@ -0,0 +1 @@
+alter table user_session drop column user_agent;
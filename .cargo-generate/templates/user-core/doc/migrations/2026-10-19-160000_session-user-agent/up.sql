@@ -0,0 +1,2 @@
+alter table user_session add column user_agent varchar(512) default null;
+comment on column user_session.user_agent is 'User-Agent header of the last request';
@@ -0,0 +1,3 @@
+drop table user_role;
+drop table role_permission;
+drop table role;
@@ -0,0 +1,30 @@
+create table role (
+  id bigint not null primary key default nextval('object_id_seq'),
+  create_date timestamptz not null default current_timestamp,
+  name varchar(255) not null unique,
+  description varchar(255) not null default ''
+);
+comment on table role is 'Roles which grant permissions to users';
+comment on column role.create_date is 'Date when role was created';
+comment on column role.name is 'Role name';
+comment on column role.description is 'What the role is for';
+
+create table role_permission (
+  role_id bigint not null references role(id) on delete cascade,
+  permission varchar(255) not null,
+  primary key (role_id, permission)
+);
+comment on table role_permission is 'Permissions granted by roles';
+comment on column role_permission.permission is 'Permission name declared by a plugin, "*" grants all permissions';
+
+create table user_role (
+  user_id bigint not null references "user"(id) on delete cascade,
+  role_id bigint not null references role(id) on delete cascade,
+  primary key (user_id, role_id)
+);
+comment on table user_role is 'Roles of users';
+
+create index user_role_role_id on user_role(role_id);
+
+insert into role (name, description) values ('admin', 'Administrator, has all permissions');
+insert into role_permission (role_id, permission) select id, '*' from role where name = 'admin';
@@ -0,0 +1,2 @@
+drop table api_key_scope;
+drop table api_key;
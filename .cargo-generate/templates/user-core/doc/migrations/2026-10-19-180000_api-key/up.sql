@@ -0,0 +1,28 @@
+create table api_key (
+  id bigint not null primary key default nextval('object_id_seq'),
+  user_id bigint not null references "user"(id) on delete cascade,
+  create_date timestamptz not null default current_timestamp,
+  name varchar(255) not null,
+  prefix varchar(16) not null,
+  token_hash bytea not null unique,
+  expires_at timestamptz default null,
+  last_used_date timestamptz default null,
+  deleted_at timestamptz default null
+);
+comment on table api_key is 'API keys of users for machine clients';
+comment on column api_key.name is 'Name given by user';
+comment on column api_key.prefix is 'Beginning of the key, to tell keys apart in listings';
+comment on column api_key.token_hash is 'Keyed hash of the key';
+comment on column api_key.expires_at is 'Date when key expires, never if not set';
+comment on column api_key.last_used_date is 'Date when key was used last time';
+comment on column api_key.deleted_at is 'Date when key was deleted together with its user';
+
+create index api_key_user_id on api_key(user_id);
+create index api_key_deleted_at on api_key(deleted_at) where deleted_at is not null;
+
+create table api_key_scope (
+  api_key_id bigint not null references api_key(id) on delete cascade,
+  permission varchar(255) not null,
+  primary key (api_key_id, permission)
+);
+comment on table api_key_scope is 'Permissions API keys are limited to, keys without scopes have all permissions of user';
@@ -1,18 +1,37 @@
+use crate::permission::{
+    Actor, AuditLogRead, Permissions, ReadWrite, RequirePermission, RolesManage, UsersRead,
+    UsersWrite, PERMISSIONS,
+};
 use actix_http::StatusCode;
-use actix_web::{cookie::Cookie, get, post, web::Data, HttpResponseBuilder};
+use actix_web::{
+    delete, get,
+    http::header,
+    post, put,
+    web::{self, Data},
+    CustomizeResponder, HttpRequest, Responder,
+};
+use diesel::prelude::*;
 use react_admin::{
+    crud::{Change, Crud, Forbidden, ImportedObject, Invalid},
+    cursor::{Page, Pagination, TotalCount},
+    export::{Export, ExportRequest, Exportable, Format},
+    filter::{DeletedAt, Filter, FilterColumn, Filterable, Search},
     request_list::{PaginatedRequest, ProcessedPaginatedRequest},
-    APIList, APIObject,
+    sort::{Order, Sort, SortColumn, SortRequest, Sortable},
+    APIList, APIObject, Resource,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::{RefOr, Schema};
 use utoipa::ToSchema;
-use webapp_core::SESSION_COOKIE_NAME;
 
-/// Deletes current session of user
+/// Deletes current session of user and removes session cookie
 #[utoipa::path(
     responses(
-        (status = OK, description = "Session removed successfully", body = react_admin::OKResponse, content_type = "application/json"),
-        (status = FORBIDDEN, description = "User not logged in")
+        (status = OK, description = "Session removed successfully", body = react_admin::OKResponse, content_type = "application/json",
+         headers(
+             ("Set-Cookie" = String, description = "Removal of session cookie"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in")
     ),
     security(("session_cookie" = []), ("authorization_header" = [])),
     tag = "User",
@@ -21,25 +40,41 @@
 pub async fn logout(
     user: crate::user::User,
     db: Data<{{db_plugin}}::db::DB>,
-) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    config: Data<crate::config::Config>,
+    req: HttpRequest,
+) -> actix_web::error::Result<CustomizeResponder<APIObject<react_admin::OKResponse>>> {
+    let session_id = user.require_session()?.id;
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
     db.pool
-        .with_transaction(move |conn| user.logout(conn))
+        .with_transaction(move |conn| {
+            user.logout(conn)?;
+            audit.record(
+                conn,
+                "logout",
+                "user_session",
+                Some(session_id.get()),
+                serde_json::json!({}),
+            )
+        })
         .await
         .map_err(|err| {
             actix_web::error::InternalError::new(err.to_string(), StatusCode::FORBIDDEN)
         })?;
 
-    let mut resp = HttpResponseBuilder::new(StatusCode::OK).body("Logged out");
-    resp.add_removal_cookie(&Cookie::build(SESSION_COOKIE_NAME, "").finish())?;
-
-    Ok(APIObject::new(react_admin::OKResponse))
+    // cookie may be set even if tokens are not delivered in cookies now
+    Ok(APIObject::new(react_admin::OKResponse)
+        .customize()
+        .append_header((
+            header::SET_COOKIE,
+            config.session.removal_cookie().encoded().to_string(),
+        )))
 }
 
 /// Element of user list
 #[derive(Serialize, ToSchema)]
 pub struct UserListResponse {
     /// Internal user ID
-    pub id: i64,
+    pub id: crate::db::user::UserId,
     /// When user was created
     pub create_date: chrono::DateTime<chrono::Utc>,
     /// When authenticated user used API last time
@@ -50,63 +85,304 @@ pub struct UserListResponse {
     pub username: String,
     /// User person
     pub person: String,
+    /// When user was deleted, deleted users can be restored
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Incremented on every change, sent as `ETag`
+    pub version: i64,
 }
 
-/// Returns list of users
-#[utoipa::path(
-    responses(
-        (status = OK, description = "User list", body = [UserListResponse], content_type = "application/json",
-         headers(
-             ("X-Total-Count" = usize, description = "Total count of users"),
-         ))),
-    params(PaginatedRequest),
-    security(("session_cookie" = []), ("authorization_header" = [])),
-    tag = "User",
-)]
-#[get("/api/v1/user")]
-async fn user_list(
-    db: Data<{{db_plugin}}::db::DB>,
-    _user: crate::user::User,
-    pagination: ProcessedPaginatedRequest,
-) -> actix_web::error::Result<APIList<UserListResponse>> {
-    let (list, count) = db
-        .pool
-        .with_transaction(move |conn| {
-            use diesel::prelude::*;
-            use {{db_plugin}}::schema::user;
-            use react_admin::db::*;
+impl Exportable for UserListResponse {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "create_date",
+        "last_seen_date",
+        "login_count",
+        "username",
+        "person",
+        "deleted_at",
+        "version",
+    ];
+}
 
-            let r = user::table
-                .select(user::all_columns)
-                .paginate(pagination.offset, pagination.limit)
-                .load_and_count_pages::<crate::db::user::User>(conn)?;
-            Ok(r)
-        })
-        .await
-        .map_err(|err| {
-            actix_web::error::InternalError::new(err.to_string(), StatusCode::FORBIDDEN)
-        })?;
+impl Resource for UserListResponse {
+    type QuerySource = {{db_plugin}}::schema::user::table;
+}
 
-    let list = list
-        .into_iter()
-        .map(|v| UserListResponse {
+impl Sortable for UserListResponse {
+    fn sort_columns() -> Vec<SortColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::user;
+
+        vec![
+            SortColumn::keyset::<crate::db::user::UserId, _>("id", user::id),
+            SortColumn::keyset::<chrono::DateTime<chrono::Utc>, _>(
+                "create_date",
+                user::create_date,
+            ),
+            SortColumn::new("last_seen_date", user::last_seen_date),
+            SortColumn::keyset::<i64, _>("login_count", user::login_count),
+            SortColumn::keyset::<String, _>("username", user::username),
+            SortColumn::keyset::<String, _>("person", user::person),
+        ]
+    }
+}
+
+impl Filterable for UserListResponse {
+    fn filter_columns() -> Vec<FilterColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::user;
+
+        vec![
+            FilterColumn::new::<i64, _>("id", user::id),
+            FilterColumn::ordered::<chrono::DateTime<chrono::Utc>, _>(
+                "create_date",
+                user::create_date,
+            ),
+            FilterColumn::ordered::<chrono::DateTime<chrono::Utc>, _>(
+                "last_seen_date",
+                user::last_seen_date.assume_not_null(),
+            ),
+            FilterColumn::ordered::<i64, _>("login_count", user::login_count),
+            FilterColumn::text("username", user::username),
+            FilterColumn::text("person", user::person),
+        ]
+    }
+
+    fn search() -> Option<Search<Self::QuerySource>> {
+        use {{db_plugin}}::schema::user;
+
+        Some(Search::new().ilike(user::username).ilike(user::person))
+    }
+
+    fn deleted_at() -> Option<DeletedAt<Self::QuerySource>> {
+        use {{db_plugin}}::schema::user;
+
+        Some(DeletedAt::new(user::deleted_at))
+    }
+}
+
+impl From<crate::db::user::User> for UserListResponse {
+    fn from(v: crate::db::user::User) -> Self {
+        Self {
             id: v.id,
             create_date: v.create_date,
             last_seen_date: v.last_seen_date,
             login_count: v.login_count,
             username: v.username,
             person: v.person,
-        })
-        .collect();
+            deleted_at: v.deleted_at,
+            version: v.version,
+        }
+    }
+}
 
-    APIList::ok(list, count)
+/// New user
+#[derive(Deserialize, ToSchema)]
+pub struct UserCreate {
+    /// Username, must be unique
+    pub username: String,
+    /// User person
+    pub person: String,
+}
+
+/// Changed fields of user, missing fields are not changed
+#[derive(AsChangeset, Deserialize, ToSchema, Clone)]
+#[diesel(table_name = {{db_plugin}}::schema::user)]
+pub struct UserUpdate {
+    /// Username, must be unique
+    pub username: Option<String>,
+    /// User person
+    pub person: Option<String>,
+}
+
+impl UserListResponse {
+    /// New version after soft delete or restore, so `If-Match` with the version before it fails
+    fn bump_version(
+        conn: &mut diesel::PgConnection,
+        id: &crate::db::user::UserId,
+    ) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::user;
+
+        let r = diesel::update(user::table.find(*id))
+            .set(user::version.eq(user::version + 1))
+            .get_result::<crate::db::user::User>(conn)
+            .optional()?;
+        Ok(r.map(Self::from))
+    }
+}
+
+/// Users at `/api/v1/user`. List is paginated with `_start`/`_end` by default, or with cursor if `_cursor` or `_limit`
+/// is set
+impl Crud for UserListResponse {
+    type Id = crate::db::user::UserId;
+    type Create = UserCreate;
+    type Update = UserUpdate;
+    type Policy = ReadWrite<UsersRead, UsersWrite>;
+    type Db = {{db_plugin}}::db::DB;
+    const ENTITY: &'static str = "user";
+    const VERSIONED: bool = true;
+
+    fn id_schema() -> RefOr<Schema> {
+        crate::db::user::UserId::schema().1
+    }
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(self.version.to_string())
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    fn list(
+        conn: &mut diesel::PgConnection,
+        pagination: &Pagination<Self>,
+        filter: &Filter<Self>,
+    ) -> anyhow::Result<Page<Self>> {
+        use {{db_plugin}}::schema::user;
+
+        // react-admin requires exact count, counting the whole table for cursor pagination is too slow. The estimation
+        // includes deleted users, so it is used only when they are listed too
+        let total_count = match pagination {
+            Pagination::Cursor(_) if filter.is_empty() && filter.include_deleted => {
+                TotalCount::Estimated("user")
+            }
+            _ => TotalCount::Exact,
+        };
+        let page = pagination.load::<crate::db::user::User, _>(
+            conn,
+            || filter.apply(user::table.select(user::all_columns).into_boxed()),
+            total_count,
+        )?;
+        Ok(page.map(Self::from))
+    }
+
+    fn get(conn: &mut diesel::PgConnection, id: &Self::Id) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::user;
+
+        let r = user::table
+            .find(*id)
+            .get_result::<crate::db::user::User>(conn)
+            .optional()?;
+        Ok(r.map(Self::from))
+    }
+
+    fn create(conn: &mut diesel::PgConnection, data: Self::Create) -> anyhow::Result<Self> {
+        let r = crate::db::user::User::new(conn, data.username, data.person)?;
+        Ok(r.into())
+    }
+
+    fn update(
+        conn: &mut diesel::PgConnection,
+        id: &Self::Id,
+        data: Self::Update,
+    ) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::user;
+
+        if data.username.is_none() && data.person.is_none() {
+            return Self::get(conn, id);
+        }
+        let r = diesel::update(user::table.find(*id))
+            .set((data, user::version.eq(user::version + 1)))
+            .get_result::<crate::db::user::User>(conn)
+            .optional()?;
+        Ok(r.map(Self::from))
+    }
+
+    /// Users are soft-deleted, they are removed when retention of deleted rows expires
+    fn delete(conn: &mut diesel::PgConnection, id: &Self::Id) -> anyhow::Result<Option<Self>> {
+        if !crate::db::user::soft_delete().delete(conn, *id)? {
+            return Ok(None);
+        }
+        Self::bump_version(conn, id)
+    }
+
+    fn restore(conn: &mut diesel::PgConnection, id: &Self::Id) -> anyhow::Result<Option<Self>> {
+        if !crate::db::user::soft_delete().restore(conn, *id)? {
+            return Ok(None);
+        }
+        Self::bump_version(conn, id)
+    }
+
+    /// Users are matched by username, person of existing users is updated
+    fn import(
+        conn: &mut diesel::PgConnection,
+        data: Self::Create,
+    ) -> anyhow::Result<ImportedObject<Self>> {
+        use {{db_plugin}}::schema::user;
+
+        let existing = user::table
+            .filter(user::username.eq(&data.username))
+            .filter(user::deleted_at.is_null())
+            .for_update()
+            .get_result::<crate::db::user::User>(conn)
+            .optional()?;
+        match existing {
+            Some(before) => {
+                let after = diesel::update(user::table.find(before.id))
+                    .set((
+                        user::person.eq(&data.person),
+                        user::version.eq(user::version + 1),
+                    ))
+                    .get_result::<crate::db::user::User>(conn)?;
+                Ok(ImportedObject::Updated {
+                    before: before.into(),
+                    after: after.into(),
+                })
+            }
+            None => Self::create(conn, data).map(ImportedObject::Created),
+        }
+    }
+}
+
+/// Exports user list to CSV, JSON Lines or XLSX file. Sorted and filtered the same way as the list
+#[utoipa::path(
+    responses(
+        (status = OK, description = "User list file", content(
+            ("text/csv" = String),
+            ("application/jsonl" = String),
+            ("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" = String),
+        ), headers(
+            ("Content-Disposition" = String, description = "Attachment with file name"),
+        )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    params(ExportRequest, SortRequest, Filter<UserListResponse>),
+    security(("session_cookie" = ["users.read"]), ("authorization_header" = ["users.read"])),
+    tag = "User",
+)]
+#[get("/api/v1/user/export")]
+async fn user_export(
+    db: Data<{{db_plugin}}::db::DB>,
+    _user: RequirePermission<UsersRead>,
+    format: Format,
+    sort: Sort<UserListResponse>,
+    filter: Filter<UserListResponse>,
+) -> actix_web::error::Result<Export> {
+    use {{db_plugin}}::schema::user;
+
+    Export::new(
+        db.pool.clone(),
+        format,
+        "users",
+        move || sort.apply(filter.apply(user::table.select(user::all_columns).into_boxed())),
+        |v: crate::db::user::User| UserListResponse::from(v),
+    )
+    .await
+    .map_err(|err| {
+        actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+            .into()
+    })
 }
 
 /// Current user information
 #[derive(Serialize, ToSchema)]
 pub struct CurrentUserInfoResponse {
     /// Internal user ID
-    pub id: i64,
+    pub id: crate::db::user::UserId,
     /// When user was created
     pub create_date: chrono::DateTime<chrono::Utc>,
     /// When authenticated user used API last time
@@ -115,6 +391,8 @@ pub struct CurrentUserInfoResponse {
     pub username: String,
     /// User person
     pub person: String,
+    /// Permissions granted by roles of user, `*` grants all permissions
+    pub permissions: Vec<String>,
 }
 
 /// Returns current user information
@@ -126,12 +404,1112 @@ pub struct CurrentUserInfoResponse {
     tag = "User",
 )]
 #[get("/api/v1/user_session/info")]
-async fn current_user_info(user: crate::user::User) -> APIObject<CurrentUserInfoResponse> {
+async fn current_user_info(permissions: Permissions) -> APIObject<CurrentUserInfoResponse> {
+    let granted = permissions.granted().map(|v| v.to_owned()).collect();
+    let user = permissions.user.user;
     APIObject::new(CurrentUserInfoResponse {
-        id: user.user.id,
-        create_date: user.user.create_date,
-        last_seen_date: user.user.last_seen_date,
-        username: user.user.username,
-        person: user.user.person,
+        id: user.id,
+        create_date: user.create_date,
+        last_seen_date: user.last_seen_date,
+        username: user.username,
+        person: user.person,
+        permissions: granted,
+    })
+}
+
+/// Session of user
+#[derive(Serialize, ToSchema)]
+pub struct UserSessionResponse {
+    /// Session ID
+    pub id: crate::db::user_session::UserSessionId,
+    /// When user logged in
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    /// When session was used last time
+    pub last_seen_date: chrono::DateTime<chrono::Utc>,
+    /// When session expires, unless it is renewed
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Number of requests made with the session
+    pub requests_count: i64,
+    /// Address of the last request
+    pub last_address: String,
+    /// User-Agent of the last request
+    pub user_agent: Option<String>,
+    /// Session is created with "remember me"
+    pub remember: bool,
+    /// Session of this request
+    pub current: bool,
+}
+
+impl UserSessionResponse {
+    fn new(
+        v: crate::db::user_session::UserSession,
+        current: Option<crate::db::user_session::UserSessionId>,
+    ) -> Self {
+        Self {
+            id: v.id,
+            create_date: v.create_date,
+            last_seen_date: v.last_seen_date,
+            expires_at: v.expires_at,
+            requests_count: v.requests_count,
+            last_address: v.last_address.addr().to_string(),
+            user_agent: v.user_agent,
+            remember: v.remember,
+            current: Some(v.id) == current,
+        }
+    }
+}
+
+/// Number of revoked sessions
+#[derive(Serialize, ToSchema)]
+pub struct RevokedSessionsResponse {
+    /// Number of revoked sessions
+    pub revoked: usize,
+}
+
+async fn session_list(
+    db: &{{db_plugin}}::db::DB,
+    config: Data<crate::config::Config>,
+    user_id: crate::db::user::UserId,
+    current: Option<crate::db::user_session::UserSessionId>,
+) -> actix_web::error::Result<APIList<UserSessionResponse>> {
+    let list = db
+        .pool
+        .with_connection(move |conn| {
+            crate::db::user_session::UserSession::list_active(conn, &config.session, user_id)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let count = list.len() as i64;
+    let list = list
+        .into_iter()
+        .map(|v| UserSessionResponse::new(v, current))
+        .collect();
+    APIList::ok(list, count)
+}
+
+async fn session_revoke(
+    db: &{{db_plugin}}::db::DB,
+    audit: crate::audit::AuditContext,
+    user_id: crate::db::user::UserId,
+    id: crate::db::user_session::UserSessionId,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let revoked = db
+        .pool
+        .with_transaction(move |conn| {
+            let r = crate::db::user_session::UserSession::revoke(conn, user_id, id)?;
+            if r.is_some() {
+                audit.record(
+                    conn,
+                    "revoke_session",
+                    "user_session",
+                    Some(id.get()),
+                    serde_json::json!({}),
+                )?;
+            }
+            Ok(r)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    match revoked {
+        Some(_) => APIObject::ok(react_admin::OKResponse),
+        None => Err(actix_web::error::ErrorNotFound("Session not found")),
+    }
+}
+
+async fn session_revoke_all(
+    db: &{{db_plugin}}::db::DB,
+    audit: crate::audit::AuditContext,
+    user_id: crate::db::user::UserId,
+    except: Option<crate::db::user_session::UserSessionId>,
+) -> actix_web::error::Result<APIObject<RevokedSessionsResponse>> {
+    let revoked = db
+        .pool
+        .with_transaction(move |conn| {
+            let ids = crate::db::user_session::UserSession::revoke_all(conn, user_id, except)?;
+            if !ids.is_empty() {
+                audit.record(
+                    conn,
+                    "revoke_sessions",
+                    "user",
+                    Some(user_id.get()),
+                    serde_json::json!({ "sessions": ids.iter().map(|v| v.get()).collect::<Vec<_>>() }),
+                )?;
+            }
+            Ok(ids.len())
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    APIObject::ok(RevokedSessionsResponse { revoked })
+}
+
+/// Returns active sessions of current user, most recently used first
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Sessions of current user", body = [UserSessionResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of sessions"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "User",
+)]
+#[get("/api/v1/user_session")]
+async fn my_session_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    config: Data<crate::config::Config>,
+    user: crate::user::User,
+) -> actix_web::error::Result<APIList<UserSessionResponse>> {
+    let session_id = user.require_session()?.id;
+    session_list(&db, config, user.user.id, Some(session_id)).await
+}
+
+/// Revokes all sessions of current user except the current one: logs out everywhere else
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Sessions revoked", body = RevokedSessionsResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "User",
+)]
+#[post("/api/v1/user_session/logout_others")]
+async fn my_session_revoke_others(
+    db: Data<{{db_plugin}}::db::DB>,
+    user: crate::user::User,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<RevokedSessionsResponse>> {
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    let session_id = user.require_session()?.id;
+    session_revoke_all(&db, audit, user.user.id, Some(session_id)).await
+}
+
+/// Revokes session of current user
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user_session::UserSessionId, Path, description = "Session ID"),
+    ),
+    responses(
+        (status = OK, description = "Session revoked", body = react_admin::OKResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key"),
+        (status = NOT_FOUND, description = "User has no such session")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "User",
+)]
+#[delete("/api/v1/user_session/{id}")]
+async fn my_session_revoke(
+    db: Data<{{db_plugin}}::db::DB>,
+    user: crate::user::User,
+    id: web::Path<crate::db::user_session::UserSessionId>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let _ = user.require_session()?;
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    session_revoke(&db, audit, user.user.id, id.into_inner()).await
+}
+
+/// Returns active sessions of user, most recently used first
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+    ),
+    responses(
+        (status = OK, description = "Sessions of user", body = [UserSessionResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of sessions"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["users.read"]), ("authorization_header" = ["users.read"])),
+    tag = "User",
+)]
+#[get("/api/v1/user/{id}/session")]
+async fn user_session_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    config: Data<crate::config::Config>,
+    RequirePermission { user, .. }: RequirePermission<UsersRead>,
+    id: web::Path<crate::db::user::UserId>,
+) -> actix_web::error::Result<APIList<UserSessionResponse>> {
+    session_list(&db, config, id.into_inner(), user.session().map(|v| v.id)).await
+}
+
+/// Revokes all sessions of user
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+    ),
+    responses(
+        (status = OK, description = "Sessions revoked", body = RevokedSessionsResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["users.write"]), ("authorization_header" = ["users.write"])),
+    tag = "User",
+)]
+#[delete("/api/v1/user/{id}/session")]
+async fn user_session_revoke_all(
+    db: Data<{{db_plugin}}::db::DB>,
+    RequirePermission { user, .. }: RequirePermission<UsersWrite>,
+    id: web::Path<crate::db::user::UserId>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<RevokedSessionsResponse>> {
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    session_revoke_all(&db, audit, id.into_inner(), None).await
+}
+
+/// Revokes session of user
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+        ("session_id" = crate::db::user_session::UserSessionId, Path, description = "Session ID"),
+    ),
+    responses(
+        (status = OK, description = "Session revoked", body = react_admin::OKResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed"),
+        (status = NOT_FOUND, description = "User has no such session")
+    ),
+    security(("session_cookie" = ["users.write"]), ("authorization_header" = ["users.write"])),
+    tag = "User",
+)]
+#[delete("/api/v1/user/{id}/session/{session_id}")]
+async fn user_session_revoke(
+    db: Data<{{db_plugin}}::db::DB>,
+    RequirePermission { user, .. }: RequirePermission<UsersWrite>,
+    path: web::Path<(
+        crate::db::user::UserId,
+        crate::db::user_session::UserSessionId,
+    )>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let (user_id, id) = path.into_inner();
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    session_revoke(&db, audit, user_id, id).await
+}
+
+/// API key of user
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    /// API key ID
+    pub id: crate::db::api_key::ApiKeyId,
+    /// Name to tell keys apart
+    pub name: String,
+    /// Beginning of the key
+    pub prefix: String,
+    /// Permissions the key is limited to, all permissions of user if empty
+    pub scopes: Vec<String>,
+    /// When key was created
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    /// When key expires, never if not set
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When key was used last time
+    pub last_used_date: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ApiKeyResponse {
+    fn new(v: crate::db::api_key::ApiKey, scopes: Vec<String>) -> Self {
+        Self {
+            id: v.id,
+            name: v.name,
+            prefix: v.prefix,
+            scopes,
+            create_date: v.create_date,
+            expires_at: v.expires_at,
+            last_used_date: v.last_used_date,
+        }
+    }
+}
+
+/// New API key
+#[derive(Deserialize, ToSchema)]
+pub struct ApiKeyCreate {
+    /// Name to tell keys apart, e.g. name of the script using it
+    pub name: String,
+    /// When key expires, never if not set
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Permissions the key is limited to, all permissions of user if empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// Created API key
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyCreatedResponse {
+    /// The key, send it in `Authorization: Bearer <key>` header. It cannot be shown again
+    pub key: webapp_core::secstr::SecUtf8,
+    pub api_key: ApiKeyResponse,
+}
+
+async fn api_key_list(
+    db: &{{db_plugin}}::db::DB,
+    user_id: crate::db::user::UserId,
+) -> actix_web::error::Result<APIList<ApiKeyResponse>> {
+    let list = db
+        .pool
+        .with_connection(move |conn| {
+            let list = crate::db::api_key::ApiKey::list(conn, user_id)?;
+            let ids: Vec<_> = list.iter().map(|v| v.id).collect();
+            let mut scopes = crate::db::api_key::ApiKey::scopes(conn, &ids)?;
+            Ok(list
+                .into_iter()
+                .map(|v| {
+                    let scopes = scopes.remove(&v.id).unwrap_or_default();
+                    ApiKeyResponse::new(v, scopes)
+                })
+                .collect::<Vec<_>>())
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let count = list.len() as i64;
+    APIList::ok(list, count)
+}
+
+async fn api_key_revoke(
+    db: &{{db_plugin}}::db::DB,
+    audit: crate::audit::AuditContext,
+    user_id: crate::db::user::UserId,
+    id: crate::db::api_key::ApiKeyId,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let revoked = db
+        .pool
+        .with_transaction(move |conn| {
+            let r = crate::db::api_key::ApiKey::revoke(conn, user_id, id)?;
+            if let Some(v) = &r {
+                audit.record(
+                    conn,
+                    "revoke_api_key",
+                    "api_key",
+                    Some(id.get()),
+                    serde_json::json!({ "name": v.name, "prefix": v.prefix }),
+                )?;
+            }
+            Ok(r)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    match revoked {
+        Some(_) => APIObject::ok(react_admin::OKResponse),
+        None => Err(actix_web::error::ErrorNotFound("API key not found")),
+    }
+}
+
+/// Returns API keys of current user, newest first
+#[utoipa::path(
+    responses(
+        (status = OK, description = "API keys of current user", body = [ApiKeyResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of API keys"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "API key",
+)]
+#[get("/api/v1/api_key")]
+async fn my_api_key_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    user: crate::user::User,
+) -> actix_web::error::Result<APIList<ApiKeyResponse>> {
+    let _ = user.require_session()?;
+    api_key_list(&db, user.user.id).await
+}
+
+/// Creates API key of current user for scripts and other machine clients. Keys are created only in interactive
+/// session, and may be limited only to permissions which user has
+#[utoipa::path(
+    request_body = ApiKeyCreate,
+    responses(
+        (status = OK, description = "Created API key", body = ApiKeyCreatedResponse, content_type = "application/json"),
+        (status = BAD_REQUEST, description = "Invalid scopes or expiration date"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "API key",
+)]
+#[post("/api/v1/api_key")]
+async fn my_api_key_create(
+    db: Data<{{db_plugin}}::db::DB>,
+    permissions: Permissions,
+    data: web::Json<ApiKeyCreate>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<ApiKeyCreatedResponse>> {
+    let _ = permissions.user.require_session()?;
+    let data = data.into_inner();
+    PERMISSIONS
+        .check(&data.scopes)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    if let Some(v) = data.scopes.iter().find(|v| !permissions.has(v)) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Permission {v:?} is not granted to user"
+        )));
+    }
+    if data.expires_at.is_some_and(|v| v <= chrono::Utc::now()) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Expiration date is in the past",
+        ));
+    }
+    let keyring = db.pool.keyring().map_err(|err| {
+        tracing::error!("Cannot create API key: {err}");
+        actix_web::error::ErrorInternalServerError("API keys are not available")
+    })?;
+
+    let audit = crate::audit::AuditContext::new(Some(&permissions.user.user), &req);
+    let user_id = permissions.user.user.id;
+    let (api_key, key) = db
+        .pool
+        .with_transaction(move |conn| {
+            let (api_key, key) = crate::db::api_key::ApiKey::new(
+                conn,
+                &keyring,
+                user_id,
+                data.name,
+                data.expires_at,
+                &data.scopes,
+            )?;
+            let scopes = crate::db::api_key::ApiKey::scopes(conn, &[api_key.id])?
+                .remove(&api_key.id)
+                .unwrap_or_default();
+            audit.record(
+                conn,
+                "create_api_key",
+                "api_key",
+                Some(api_key.id.get()),
+                serde_json::json!({
+                    "name": api_key.name,
+                    "prefix": api_key.prefix,
+                    "scopes": scopes,
+                    "expires_at": api_key.expires_at,
+                }),
+            )?;
+            Ok((ApiKeyResponse::new(api_key, scopes), key))
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    APIObject::ok(ApiKeyCreatedResponse {
+        key: webapp_core::secstr::SecUtf8::from(Into::<secstr::SecUtf8>::into(key)),
+        api_key,
     })
 }
+
+/// Revokes API key of current user
+#[utoipa::path(
+    params(
+        ("id" = crate::db::api_key::ApiKeyId, Path, description = "API key ID"),
+    ),
+    responses(
+        (status = OK, description = "API key revoked", body = react_admin::OKResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Request is authenticated with API key"),
+        (status = NOT_FOUND, description = "User has no such API key")
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "API key",
+)]
+#[delete("/api/v1/api_key/{id}")]
+async fn my_api_key_revoke(
+    db: Data<{{db_plugin}}::db::DB>,
+    user: crate::user::User,
+    id: web::Path<crate::db::api_key::ApiKeyId>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let _ = user.require_session()?;
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    api_key_revoke(&db, audit, user.user.id, id.into_inner()).await
+}
+
+/// Returns API keys of user, newest first
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+    ),
+    responses(
+        (status = OK, description = "API keys of user", body = [ApiKeyResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of API keys"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["users.read"]), ("authorization_header" = ["users.read"])),
+    tag = "API key",
+)]
+#[get("/api/v1/user/{id}/api_key")]
+async fn user_api_key_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    _user: RequirePermission<UsersRead>,
+    id: web::Path<crate::db::user::UserId>,
+) -> actix_web::error::Result<APIList<ApiKeyResponse>> {
+    api_key_list(&db, id.into_inner()).await
+}
+
+/// Revokes API key of user
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+        ("key_id" = crate::db::api_key::ApiKeyId, Path, description = "API key ID"),
+    ),
+    responses(
+        (status = OK, description = "API key revoked", body = react_admin::OKResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed"),
+        (status = NOT_FOUND, description = "User has no such API key")
+    ),
+    security(("session_cookie" = ["users.write"]), ("authorization_header" = ["users.write"])),
+    tag = "API key",
+)]
+#[delete("/api/v1/user/{id}/api_key/{key_id}")]
+async fn user_api_key_revoke(
+    db: Data<{{db_plugin}}::db::DB>,
+    RequirePermission { user, .. }: RequirePermission<UsersWrite>,
+    path: web::Path<(crate::db::user::UserId, crate::db::api_key::ApiKeyId)>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let (user_id, id) = path.into_inner();
+    let audit = crate::audit::AuditContext::new(Some(&user.user), &req);
+    api_key_revoke(&db, audit, user_id, id).await
+}
+
+/// Role, grants permissions to users
+#[derive(Serialize, ToSchema)]
+pub struct RoleResponse {
+    /// Role ID
+    pub id: crate::db::role::RoleId,
+    /// When role was created
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    /// Role name
+    pub name: String,
+    /// What the role is for
+    pub description: String,
+    /// Granted permissions, `*` grants all permissions
+    pub permissions: Vec<String>,
+}
+
+impl Resource for RoleResponse {
+    type QuerySource = {{db_plugin}}::schema::role::table;
+}
+
+impl Sortable for RoleResponse {
+    fn sort_columns() -> Vec<SortColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::role;
+
+        vec![
+            SortColumn::keyset::<crate::db::role::RoleId, _>("id", role::id),
+            SortColumn::keyset::<chrono::DateTime<chrono::Utc>, _>(
+                "create_date",
+                role::create_date,
+            ),
+            SortColumn::keyset::<String, _>("name", role::name),
+        ]
+    }
+}
+
+impl Filterable for RoleResponse {
+    fn filter_columns() -> Vec<FilterColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::role;
+
+        vec![
+            FilterColumn::new::<i64, _>("id", role::id),
+            FilterColumn::text("name", role::name),
+        ]
+    }
+
+    fn search() -> Option<Search<Self::QuerySource>> {
+        use {{db_plugin}}::schema::role;
+
+        Some(Search::new().ilike(role::name).ilike(role::description))
+    }
+}
+
+impl RoleResponse {
+    fn new(v: crate::db::role::Role, permissions: Vec<String>) -> Self {
+        Self {
+            id: v.id,
+            create_date: v.create_date,
+            name: v.name,
+            description: v.description,
+            permissions,
+        }
+    }
+
+    fn load(
+        conn: &mut diesel::PgConnection,
+        list: Vec<crate::db::role::Role>,
+    ) -> anyhow::Result<Vec<Self>> {
+        let ids: Vec<_> = list.iter().map(|v| v.id).collect();
+        let mut permissions = crate::db::role::Role::permissions(conn, &ids)?;
+        Ok(list
+            .into_iter()
+            .map(|v| {
+                let granted = permissions.remove(&v.id).unwrap_or_default();
+                Self::new(v, granted)
+            })
+            .collect())
+    }
+}
+
+/// New role
+#[derive(Deserialize, ToSchema)]
+pub struct RoleCreate {
+    /// Role name, must be unique
+    pub name: String,
+    /// What the role is for
+    #[serde(default)]
+    pub description: String,
+    /// Granted permissions, see `/api/v1/permission`
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+/// Changed fields of role, missing fields are not changed
+#[derive(Deserialize, ToSchema, Clone)]
+pub struct RoleUpdate {
+    /// Role name, must be unique
+    pub name: Option<String>,
+    /// What the role is for
+    pub description: Option<String>,
+    /// Granted permissions, replace the current ones
+    pub permissions: Option<Vec<String>>,
+}
+
+fn check_permissions(permissions: &[String]) -> anyhow::Result<()> {
+    PERMISSIONS
+        .check(permissions)
+        .map_err(|err| Invalid(err.to_string()).into())
+}
+
+/// Roles at `/api/v1/role`, managed by users with `roles.manage` permission
+impl Crud for RoleResponse {
+    type Id = crate::db::role::RoleId;
+    type Create = RoleCreate;
+    type Update = RoleUpdate;
+    type Policy = ReadWrite<RolesManage, RolesManage>;
+    type Db = {{db_plugin}}::db::DB;
+    const ENTITY: &'static str = "role";
+
+    fn id_schema() -> RefOr<Schema> {
+        crate::db::role::RoleId::schema().1
+    }
+
+    fn id(&self) -> Self::Id {
+        self.id
+    }
+
+    fn list(
+        conn: &mut diesel::PgConnection,
+        pagination: &Pagination<Self>,
+        filter: &Filter<Self>,
+    ) -> anyhow::Result<Page<Self>> {
+        use {{db_plugin}}::schema::role;
+
+        let page = pagination.load::<crate::db::role::Role, _>(
+            conn,
+            || filter.apply(role::table.select(role::all_columns).into_boxed()),
+            TotalCount::Exact,
+        )?;
+        let ids: Vec<_> = page.list.iter().map(|v| v.id).collect();
+        let mut permissions = crate::db::role::Role::permissions(conn, &ids)?;
+        Ok(page.map(|v| {
+            let granted = permissions.remove(&v.id).unwrap_or_default();
+            Self::new(v, granted)
+        }))
+    }
+
+    fn get(conn: &mut diesel::PgConnection, id: &Self::Id) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::role;
+
+        let r = role::table
+            .find(*id)
+            .get_result::<crate::db::role::Role>(conn)
+            .optional()?;
+        match r {
+            Some(v) => Ok(RoleResponse::load(conn, vec![v])?.pop()),
+            None => Ok(None),
+        }
+    }
+
+    fn create(conn: &mut diesel::PgConnection, data: Self::Create) -> anyhow::Result<Self> {
+        check_permissions(&data.permissions)?;
+        let r = crate::db::role::Role::new(conn, data.name, data.description)?;
+        crate::db::role::Role::set_permissions(conn, r.id, &data.permissions)?;
+        Self::get(conn, &r.id)?.ok_or_else(|| anyhow::anyhow!("Created role not found"))
+    }
+
+    fn update(
+        conn: &mut diesel::PgConnection,
+        id: &Self::Id,
+        data: Self::Update,
+    ) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::role;
+
+        if Self::get(conn, id)?.is_none() {
+            return Ok(None);
+        }
+        if data.name.is_some() || data.description.is_some() {
+            let _ = diesel::update(role::table.find(*id))
+                .set((
+                    data.name.map(|v| role::name.eq(v)),
+                    data.description.map(|v| role::description.eq(v)),
+                ))
+                .execute(conn)?;
+        }
+        if let Some(permissions) = data.permissions {
+            check_permissions(&permissions)?;
+            crate::db::role::Role::set_permissions(conn, *id, &permissions)?;
+        }
+        Self::get(conn, id)
+    }
+
+    /// Roles with permissions which are not granted to the actor cannot be changed, and such permissions cannot be
+    /// granted
+    fn check_change(
+        _conn: &mut diesel::PgConnection,
+        context: &Actor,
+        change: &Change<'_, Self>,
+    ) -> anyhow::Result<()> {
+        let granted = change
+            .before
+            .into_iter()
+            .chain(change.after)
+            .flat_map(|v| v.permissions.iter().cloned())
+            .collect();
+        if context.includes(&granted) {
+            Ok(())
+        } else {
+            Err(Forbidden("Role has permissions which are not granted to you".to_owned()).into())
+        }
+    }
+
+    /// Roles are removed together with their assignments to users
+    fn delete(conn: &mut diesel::PgConnection, id: &Self::Id) -> anyhow::Result<Option<Self>> {
+        use {{db_plugin}}::schema::role;
+
+        let r = Self::get(conn, id)?;
+        if r.is_some() {
+            let _ = diesel::delete(role::table.find(*id)).execute(conn)?;
+        }
+        Ok(r)
+    }
+}
+
+/// Permission declared by a plugin
+#[derive(Serialize, ToSchema)]
+pub struct PermissionResponse {
+    /// Permission name, as in roles and OpenAPI security scopes
+    pub name: String,
+    /// What the permission allows
+    pub description: String,
+}
+
+/// Returns permissions which may be granted to roles
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Permissions", body = [PermissionResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of permissions"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["roles.manage"]), ("authorization_header" = ["roles.manage"])),
+    tag = "Role",
+)]
+#[get("/api/v1/permission")]
+async fn permission_list(
+    _user: RequirePermission<RolesManage>,
+) -> actix_web::error::Result<APIList<PermissionResponse>> {
+    let list: Vec<_> = PERMISSIONS
+        .list()
+        .into_iter()
+        .map(|(name, description)| PermissionResponse {
+            name: name.to_owned(),
+            description: description.to_owned(),
+        })
+        .collect();
+    let count = list.len() as i64;
+    APIList::ok(list, count)
+}
+
+/// Roles assigned to user
+#[derive(Deserialize, ToSchema)]
+pub struct UserRolesRequest {
+    /// IDs of roles, replace the current roles of user
+    pub roles: Vec<crate::db::role::RoleId>,
+}
+
+/// Returns roles of user, sorted by name
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+    ),
+    responses(
+        (status = OK, description = "Roles of user", body = [RoleResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of roles"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["users.read"]), ("authorization_header" = ["users.read"])),
+    tag = "Role",
+)]
+#[get("/api/v1/user/{id}/role")]
+async fn user_role_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    _user: RequirePermission<UsersRead>,
+    id: web::Path<crate::db::user::UserId>,
+) -> actix_web::error::Result<APIList<RoleResponse>> {
+    let id = id.into_inner();
+    let list = db
+        .pool
+        .with_connection(move |conn| {
+            let list = crate::db::role::Role::of_user(conn, id)?;
+            RoleResponse::load(conn, list)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let count = list.len() as i64;
+    APIList::ok(list, count)
+}
+
+/// Replaces roles of user. Only roles whose permissions are all granted to the current user can be assigned or removed
+#[utoipa::path(
+    params(
+        ("id" = crate::db::user::UserId, Path, description = "User ID"),
+    ),
+    request_body = UserRolesRequest,
+    responses(
+        (status = OK, description = "New roles of user", body = [RoleResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of roles"),
+         )),
+        (status = BAD_REQUEST, description = "Unknown role"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed, or roles have permissions which are not granted to you"),
+        (status = NOT_FOUND, description = "User not found")
+    ),
+    security(("session_cookie" = ["roles.manage"]), ("authorization_header" = ["roles.manage"])),
+    tag = "Role",
+)]
+#[put("/api/v1/user/{id}/role")]
+async fn user_role_set(
+    db: Data<{{db_plugin}}::db::DB>,
+    permissions: Permissions,
+    id: web::Path<crate::db::user::UserId>,
+    data: web::Json<UserRolesRequest>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIList<RoleResponse>> {
+    use {{db_plugin}}::schema::{role, user};
+
+    permissions.require::<RolesManage>()?;
+    let audit = crate::audit::AuditContext::new(Some(&permissions.user.user), &req);
+    let id = id.into_inner();
+    let roles = data.into_inner().roles;
+    let list = db
+        .pool
+        .with_transaction(move |conn| {
+            let exists = user::table
+                .find(id)
+                .filter(user::deleted_at.is_null())
+                .select(user::id)
+                .get_result::<crate::db::user::UserId>(conn)
+                .optional()?
+                .is_some();
+            if !exists {
+                return Ok(None);
+            }
+            let found: i64 = role::table
+                .filter(role::id.eq_any(&roles))
+                .count()
+                .get_result(conn)?;
+            if found as usize != roles.iter().collect::<std::collections::HashSet<_>>().len() {
+                return Err(Invalid("Unknown role".to_owned()).into());
+            }
+
+            let names = |list: &[crate::db::role::Role]| -> Vec<String> {
+                list.iter().map(|v| v.name.clone()).collect()
+            };
+            let before = crate::db::role::Role::of_user(conn, id)?;
+            // assigned and removed roles
+            let changed: Vec<_> = before
+                .iter()
+                .map(|v| v.id)
+                .filter(|v| !roles.contains(v))
+                .chain(roles.iter().copied())
+                .collect();
+            let granted = crate::db::role::Role::permissions(conn, &changed)?
+                .into_values()
+                .flatten()
+                .collect();
+            if !permissions.includes(&granted) {
+                return Err(Forbidden(
+                    "Roles have permissions which are not granted to you".to_owned(),
+                )
+                .into());
+            }
+            crate::db::role::Role::set_user_roles(conn, id, &roles)?;
+            let after = crate::db::role::Role::of_user(conn, id)?;
+            if before != after {
+                audit.record(
+                    conn,
+                    "set_roles",
+                    "user",
+                    Some(id.get()),
+                    serde_json::json!({ "roles": { "before": names(&before), "after": names(&after) } }),
+                )?;
+            }
+            RoleResponse::load(conn, after).map(Some)
+        })
+        .await
+        .map_err(react_admin::crud::db_error)?;
+    match list {
+        Some(list) => {
+            let count = list.len() as i64;
+            APIList::ok(list, count)
+        }
+        None => Err(actix_web::error::ErrorNotFound("User not found")),
+    }
+}
+
+/// Element of audit log
+#[derive(Serialize, ToSchema)]
+pub struct AuditLogResponse {
+    /// Record ID
+    pub id: crate::db::audit_log::AuditLogId,
+    /// When change was made
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    /// User who made the change
+    pub actor_id: Option<crate::db::user::UserId>,
+    /// Action name
+    pub action: String,
+    /// Type of changed object
+    pub entity: String,
+    /// ID of changed object
+    pub entity_id: Option<i64>,
+    /// Changed fields with values before and after the change
+    #[schema(value_type = Object)]
+    pub changes: serde_json::Value,
+    /// Address of the user who made the change
+    pub client_address: Option<String>,
+    /// ID of HTTP request, as in x-request-id response header
+    pub request_id: Option<String>,
+}
+
+impl Resource for AuditLogResponse {
+    type QuerySource = {{db_plugin}}::schema::audit_log::table;
+}
+
+impl Sortable for AuditLogResponse {
+    fn sort_columns() -> Vec<SortColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::audit_log;
+
+        vec![
+            SortColumn::new("id", audit_log::id),
+            SortColumn::new("create_date", audit_log::create_date),
+            SortColumn::new("actor_id", audit_log::actor_id),
+            SortColumn::new("action", audit_log::action),
+            SortColumn::new("entity", audit_log::entity),
+        ]
+    }
+
+    fn default_sort() -> (&'static str, Order) {
+        ("create_date", Order::Desc)
+    }
+}
+
+impl Filterable for AuditLogResponse {
+    fn filter_columns() -> Vec<FilterColumn<Self::QuerySource>> {
+        use {{db_plugin}}::schema::audit_log;
+
+        vec![
+            FilterColumn::new::<i64, _>("id", audit_log::id),
+            FilterColumn::ordered::<chrono::DateTime<chrono::Utc>, _>(
+                "create_date",
+                audit_log::create_date,
+            ),
+            FilterColumn::new::<i64, _>("actor_id", audit_log::actor_id.assume_not_null()),
+            FilterColumn::new::<String, _>("action", audit_log::action),
+            FilterColumn::new::<String, _>("entity", audit_log::entity),
+            FilterColumn::new::<i64, _>("entity_id", audit_log::entity_id.assume_not_null()),
+        ]
+    }
+}
+
+/// Returns audit log, most recent changes first by default
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Audit log", body = [AuditLogResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of records"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    params(PaginatedRequest, SortRequest, Filter<AuditLogResponse>),
+    security(("session_cookie" = ["audit_log.read"]), ("authorization_header" = ["audit_log.read"])),
+    tag = "Audit",
+)]
+#[get("/api/v1/audit_log")]
+async fn audit_log_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    _user: RequirePermission<AuditLogRead>,
+    pagination: ProcessedPaginatedRequest,
+    sort: Sort<AuditLogResponse>,
+    filter: Filter<AuditLogResponse>,
+) -> actix_web::error::Result<APIList<AuditLogResponse>> {
+    let (list, count) = db
+        .pool
+        .with_transaction(move |conn| {
+            use {{db_plugin}}::schema::audit_log;
+            use react_admin::db::*;
+
+            let query = filter.apply(audit_log::table.into_boxed());
+
+            let r = sort
+                .apply(query)
+                .paginate(pagination.offset, pagination.limit)
+                .load_and_count_pages::<crate::db::audit_log::AuditLog>(conn)?;
+            Ok(r)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::FORBIDDEN)
+        })?;
+
+    let list = list
+        .into_iter()
+        .map(|v| AuditLogResponse {
+            id: v.id,
+            create_date: v.create_date,
+            actor_id: v.actor_id,
+            action: v.action,
+            entity: v.entity,
+            entity_id: v.entity_id,
+            changes: v.changes,
+            client_address: v.client_address.map(|v| v.addr().to_string()),
+            request_id: v.request_id,
+        })
+        .collect();
+
+    APIList::ok(list, count)
+}
@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+/// Who made the change and where the request came from
+#[derive(Clone, Debug)]
+pub struct AuditContext {
+    pub actor_id: Option<crate::db::user::UserId>,
+    pub client_address: Option<ipnet::IpNet>,
+    pub request_id: Option<String>,
+}
+
+impl AuditContext {
+    pub fn new(actor: Option<&crate::db::user::User>, req: &actix_web::HttpRequest) -> Self {
+        Self {
+            actor_id: actor.map(|v| v.id),
+            client_address: req.peer_addr().map(|v| ipnet::IpNet::from(v.ip())),
+            request_id: webapp_core::request_id(req),
+        }
+    }
+
+    /// Sets the actor when it becomes known only within the transaction, like on login
+    pub fn with_actor(self, actor: &crate::db::user::User) -> Self {
+        Self {
+            actor_id: Some(actor.id),
+            ..self
+        }
+    }
+
+    /// Writes audit log record. Call it in the same transaction as the change itself
+    pub fn record(
+        &self,
+        db: &mut diesel::PgConnection,
+        action: &str,
+        entity: &str,
+        entity_id: Option<i64>,
+        changes: serde_json::Value,
+    ) -> Result<()> {
+        let _ = crate::db::audit_log::AuditLog::new(
+            db,
+            crate::db::audit_log::AuditLogNew {
+                create_date: chrono::Utc::now(),
+                actor_id: self.actor_id,
+                action,
+                entity,
+                entity_id,
+                changes,
+                client_address: self.client_address,
+                request_id: self.request_id.as_deref(),
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// Returns fields which differ in `before` and `after` as `{"field": {"before": ..., "after": ...}}`. Missing object
+/// means it was created or deleted. Never pass objects with secrets here, audit log is readable by administrators
+pub fn diff<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Result<serde_json::Value> {
+    use serde_json::{Map, Value};
+
+    let to_map = |v: Option<&T>| -> Result<Map<String, Value>> {
+        match v.map(serde_json::to_value).transpose()? {
+            None => Ok(Map::new()),
+            Some(Value::Object(v)) => Ok(v),
+            Some(_) => Err(anyhow!("Only objects can be recorded in audit log")),
+        }
+    };
+    let before = to_map(before)?;
+    let after = to_map(after)?;
+
+    let mut r = Map::new();
+    for key in before.keys().chain(after.keys()) {
+        let (before, after) = (before.get(key), after.get(key));
+        if before != after && !r.contains_key(key) {
+            let _ = r.insert(
+                key.clone(),
+                serde_json::json!({ "before": before, "after": after }),
+            );
+        }
+    }
+    Ok(Value::Object(r))
+}
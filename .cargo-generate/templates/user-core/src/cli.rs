@@ -0,0 +1,130 @@
+//! Commands of the plugin, `<app> plugin {{project-name}} <COMMAND>`
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+pub struct CommandLine {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create API key of user, e.g. of a service account without password. The key is printed once, it cannot be
+    /// shown again
+    CreateApiKey {
+        /// Username of key owner
+        username: String,
+        /// Name to tell keys apart, e.g. name of the script using it
+        #[clap(long)]
+        name: String,
+        /// Key expires after this time, e.g. "90days". Never expires if not set
+        #[clap(long, value_parser = humantime_serde::re::humantime::parse_duration)]
+        expires_in: Option<std::time::Duration>,
+        /// Permission the key is limited to, repeat for several. All permissions of user if not set
+        #[clap(long)]
+        scope: Vec<String>,
+    },
+    /// Assign role to user, e.g. `admin` to the first administrator of an existing database
+    GrantRole {
+        /// Username of user
+        username: String,
+        /// Role name
+        role: String,
+    },
+}
+
+impl CommandLine {
+    pub async fn run(self, configs_path: &std::path::Path) -> Result<()> {
+        match self.command {
+            Command::CreateApiKey {
+                username,
+                name,
+                expires_in,
+                scope,
+            } => {
+                let pool = database_pg::Pool::new("{{db-plugin}}", configs_path)?;
+                let keyring = pool.keyring()?;
+                let expires_at = match expires_in {
+                    Some(v) => Some(chrono::Utc::now() + chrono::Duration::from_std(v)?),
+                    None => None,
+                };
+                let (api_key, key) = pool
+                    .with_transaction(move |conn| {
+                        let user = crate::db::user::User::of_username(conn, &username)?;
+                        // other plugins are not initialized, so scopes are checked only against roles of user
+                        let granted = crate::db::role::Role::user_permissions(conn, user.id)?;
+                        if let Some(v) = scope.iter().find(|v| {
+                            !granted.contains(crate::permission::ALL) && !granted.contains(*v)
+                        }) {
+                            return Err(anyhow!("Permission {v:?} is not granted to user"));
+                        }
+                        let (api_key, key) = crate::db::api_key::ApiKey::new(
+                            conn, &keyring, user.id, name, expires_at, &scope,
+                        )?;
+                        let audit = crate::audit::AuditContext {
+                            actor_id: None,
+                            client_address: None,
+                            request_id: None,
+                        };
+                        audit.record(
+                            conn,
+                            "create_api_key",
+                            "api_key",
+                            Some(api_key.id.get()),
+                            serde_json::json!({
+                                "name": api_key.name,
+                                "prefix": api_key.prefix,
+                                "scopes": scope,
+                                "expires_at": api_key.expires_at,
+                            }),
+                        )?;
+                        Ok((api_key, key))
+                    })
+                    .await?;
+                eprintln!("API key {} of user {} created", api_key.id, api_key.user_id);
+                println!("{}", key.unsecure());
+                Ok(())
+            }
+            Command::GrantRole { username, role } => {
+                let pool = database_pg::Pool::new("{{db-plugin}}", configs_path)?;
+                let granted = pool
+                    .with_transaction(move |conn| {
+                        let user = crate::db::user::User::of_username(conn, &username)?;
+                        let role = crate::db::role::Role::of_name(conn, &role)?;
+                        let before = crate::db::role::Role::of_user(conn, user.id)?;
+                        if before.contains(&role) {
+                            return Ok(false);
+                        }
+                        let ids: Vec<_> = before.iter().map(|v| v.id).chain([role.id]).collect();
+                        crate::db::role::Role::set_user_roles(conn, user.id, &ids)?;
+                        let names = |list: &[crate::db::role::Role]| -> Vec<String> {
+                            list.iter().map(|v| v.name.clone()).collect()
+                        };
+                        let after = crate::db::role::Role::of_user(conn, user.id)?;
+                        let audit = crate::audit::AuditContext {
+                            actor_id: None,
+                            client_address: None,
+                            request_id: None,
+                        };
+                        audit.record(
+                            conn,
+                            "set_roles",
+                            "user",
+                            Some(user.id.get()),
+                            serde_json::json!({ "roles": { "before": names(&before), "after": names(&after) } }),
+                        )?;
+                        Ok(true)
+                    })
+                    .await?;
+                if granted {
+                    eprintln!("Role granted");
+                } else {
+                    eprintln!("User already has the role");
+                }
+                Ok(())
+            }
+        }
+    }
+}
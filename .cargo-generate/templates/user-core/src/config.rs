@@ -0,0 +1,157 @@
+use actix_web::cookie::{time, Cookie};
+use serde::{Deserialize, Serialize};
+use structdoc::StructDoc;
+
+#[derive(Serialize, Deserialize, StructDoc, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    /// User sessions
+    pub session: SessionConfig,
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// How long session is valid after login
+    #[serde(with = "humantime_serde")]
+    pub lifetime: std::time::Duration,
+    /// How long session is valid after login with "remember me"
+    #[serde(with = "humantime_serde")]
+    pub remember_lifetime: std::time::Duration,
+    /// Session expires if it is not used for this time. "Remember me" sessions do not expire by idle timeout
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: std::time::Duration,
+    /// Every request extends session for its lifetime, so only sessions which are not used expire
+    pub sliding_renewal: bool,
+    /// How often expired sessions are removed, requires jobs processing in the DB plugin config
+    #[serde(with = "humantime_serde")]
+    pub cleanup_interval: std::time::Duration,
+    /// How session token is passed to the client on login
+    pub token_delivery: TokenDelivery,
+    /// Attributes of session cookie
+    pub cookie: CookieConfig,
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenDelivery {
+    /// In login response body, client sends it in `Authorization: Bearer <token>` header
+    Body,
+    /// In session cookie
+    Cookie,
+    /// Both in response body and cookie
+    #[default]
+    Both,
+}
+
+impl TokenDelivery {
+    pub fn body(self) -> bool {
+        self != Self::Cookie
+    }
+
+    pub fn cookie(self) -> bool {
+        self != Self::Body
+    }
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+#[serde(default)]
+pub struct CookieConfig {
+    /// Cookie is sent only over HTTPS
+    pub secure: bool,
+    /// Cookie is not available to JavaScript
+    pub http_only: bool,
+    /// Whether cookie is sent with cross-site requests: Strict, Lax or None. None requires `secure`
+    pub same_site: SameSite,
+    /// Domain of cookie, if not set cookie is sent only to the host which issued it
+    pub domain: Option<String>,
+    /// Path of cookie
+    pub path: String,
+    /// Max-Age of cookie. If not set, "remember me" cookies live as long as the session, others are removed when
+    /// browser is closed
+    #[serde(with = "humantime_serde")]
+    pub max_age: Option<std::time::Duration>,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            secure: true,
+            http_only: true,
+            same_site: SameSite::Lax,
+            domain: None,
+            path: "/".to_owned(),
+            max_age: None,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: std::time::Duration::from_secs(24 * 3600),
+            remember_lifetime: std::time::Duration::from_secs(30 * 24 * 3600),
+            idle_timeout: std::time::Duration::from_secs(3600),
+            sliding_renewal: false,
+            cleanup_interval: std::time::Duration::from_secs(3600),
+            token_delivery: TokenDelivery::default(),
+            cookie: CookieConfig::default(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// Lifetime of session created with or without "remember me"
+    pub fn lifetime(&self, remember: bool) -> std::time::Duration {
+        if remember {
+            self.remember_lifetime
+        } else {
+            self.lifetime
+        }
+    }
+
+    fn cookie(&self, value: String) -> Cookie<'static> {
+        let mut r = Cookie::build(webapp_core::SESSION_COOKIE_NAME, value)
+            .secure(self.cookie.secure)
+            .http_only(self.cookie.http_only)
+            .same_site(match self.cookie.same_site {
+                SameSite::Strict => actix_web::cookie::SameSite::Strict,
+                SameSite::Lax => actix_web::cookie::SameSite::Lax,
+                SameSite::None => actix_web::cookie::SameSite::None,
+            })
+            .path(self.cookie.path.clone())
+            .finish();
+        if let Some(domain) = &self.cookie.domain {
+            r.set_domain(domain.clone());
+        }
+        r
+    }
+
+    /// Cookie with session token
+    pub fn session_cookie(&self, token: &str, remember: bool) -> Cookie<'static> {
+        let mut r = self.cookie(token.to_owned());
+        let max_age = match self.cookie.max_age {
+            Some(v) => Some(v),
+            None if remember => Some(self.remember_lifetime),
+            None => None,
+        };
+        if let Some(max_age) = max_age {
+            r.set_max_age(time::Duration::try_from(max_age).unwrap_or(time::Duration::MAX));
+        }
+        r
+    }
+
+    /// Cookie removing session cookie in browser
+    pub fn removal_cookie(&self) -> Cookie<'static> {
+        let mut r = self.cookie(String::new());
+        r.make_removal();
+        r
+    }
+}
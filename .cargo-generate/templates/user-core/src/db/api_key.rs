@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::{BTreeSet, HashMap};
+use {{db_plugin}}::schema::{api_key, api_key_scope};
+
+database_pg::make_id!(ApiKeyId);
+
+/// API keys start with it, so they are told apart from session tokens
+pub const PREFIX: &str = "wak_";
+
+/// Length of the beginning of key stored in `prefix` column
+const SHOWN_LENGTH: usize = 12;
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = api_key)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub user_id: crate::db::user::UserId,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub name: String,
+    pub prefix: String,
+    pub token_hash: database_pg::crypto::KeyedHash,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_date: Option<chrono::DateTime<chrono::Utc>>,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = api_key)]
+pub struct ApiKeyNew {
+    pub user_id: crate::db::user::UserId,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub name: String,
+    pub prefix: String,
+    token_hash: database_pg::crypto::KeyedHash,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ApiKey {
+    /// Creates new API key limited to `scopes`, or with all permissions of user if `scopes` are empty. Only keyed hash
+    /// of the key is stored, so the key is returned to be passed to the user
+    pub fn new(
+        db: &mut diesel::PgConnection,
+        keyring: &database_pg::crypto::Keyring,
+        user_id: crate::db::user::UserId,
+        name: String,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        scopes: &[String],
+    ) -> Result<(ApiKey, database_pg::secstr::SecUtf8)> {
+        let secret: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(48)
+            .map(char::from)
+            .collect();
+        let key = format!("{PREFIX}{secret}");
+        let r: ApiKey = diesel::insert_into(api_key::dsl::api_key)
+            .values(ApiKeyNew {
+                user_id,
+                create_date: chrono::Utc::now(),
+                name,
+                prefix: key[..SHOWN_LENGTH].to_owned(),
+                token_hash: keyring.hash(key.as_bytes()),
+                expires_at,
+            })
+            .get_result(db)
+            .map_err(|err| anyhow!("Failed to add API key: {err}"))?;
+        let rows: Vec<_> = scopes
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|v| {
+                (
+                    api_key_scope::api_key_id.eq(r.id),
+                    api_key_scope::permission.eq(v),
+                )
+            })
+            .collect();
+        let _ = diesel::insert_into(api_key_scope::table)
+            .values(rows)
+            .execute(db)
+            .map_err(|err| anyhow!("Failed to add API key scopes: {err}"))?;
+        Ok((r, database_pg::secstr::SecUtf8::from(key)))
+    }
+
+    /// Finds active key and marks it used at `now`
+    pub fn authenticate(
+        db: &mut diesel::PgConnection,
+        keyring: &database_pg::crypto::Keyring,
+        key: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<ApiKey>> {
+        diesel::update(
+            api_key::dsl::api_key
+                .filter(api_key::dsl::token_hash.eq_any(keyring.hash_candidates(key.as_bytes())))
+                .filter(api_key::dsl::deleted_at.is_null())
+                .filter(
+                    api_key::dsl::expires_at
+                        .is_null()
+                        .or(api_key::dsl::expires_at.gt(now)),
+                ),
+        )
+        .set(api_key::dsl::last_used_date.eq(Some(now)))
+        .get_result(db)
+        .optional()
+        .map_err(|err| anyhow!("Failed to check API key: {err}"))
+    }
+
+    /// Scopes of keys, sorted by name. Keys without scopes are missing
+    pub fn scopes(
+        db: &mut diesel::PgConnection,
+        ids: &[ApiKeyId],
+    ) -> Result<HashMap<ApiKeyId, Vec<String>>> {
+        let rows = api_key_scope::table
+            .filter(api_key_scope::api_key_id.eq_any(ids))
+            .order((api_key_scope::api_key_id, api_key_scope::permission))
+            .select((api_key_scope::api_key_id, api_key_scope::permission))
+            .load::<(ApiKeyId, String)>(db)
+            .map_err(|err| anyhow!("Failed to get API key scopes: {err}"))?;
+        let mut r: HashMap<ApiKeyId, Vec<String>> = HashMap::new();
+        for (id, permission) in rows {
+            r.entry(id).or_default().push(permission);
+        }
+        Ok(r)
+    }
+
+    /// Keys of user including expired ones, newest first
+    pub fn list(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+    ) -> Result<Vec<ApiKey>> {
+        api_key::dsl::api_key
+            .filter(api_key::dsl::user_id.eq(user_id))
+            .filter(api_key::dsl::deleted_at.is_null())
+            .order(api_key::dsl::create_date.desc())
+            .load(db)
+            .map_err(|err| anyhow!("Failed to list API keys: {err}"))
+    }
+
+    /// Removes key of user. Returns `None` if user has no such key
+    pub fn revoke(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+        id: ApiKeyId,
+    ) -> Result<Option<ApiKey>> {
+        diesel::delete(
+            api_key::dsl::api_key
+                .find(id)
+                .filter(api_key::dsl::user_id.eq(user_id)),
+        )
+        .get_result(db)
+        .optional()
+        .map_err(|err| anyhow!("Failed to revoke API key {id}: {err}"))
+    }
+}
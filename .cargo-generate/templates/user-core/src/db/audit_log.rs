@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use {{db_plugin}}::schema::audit_log;
+
+database_pg::make_id!(AuditLogId);
+
+#[derive(Identifiable, Queryable, PartialEq, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLog {
+    pub id: AuditLogId,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub actor_id: Option<crate::db::user::UserId>,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: Option<i64>,
+    pub changes: serde_json::Value,
+    pub client_address: Option<ipnet::IpNet>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLogNew<'a> {
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub actor_id: Option<crate::db::user::UserId>,
+    pub action: &'a str,
+    pub entity: &'a str,
+    pub entity_id: Option<i64>,
+    pub changes: serde_json::Value,
+    pub client_address: Option<ipnet::IpNet>,
+    pub request_id: Option<&'a str>,
+}
+
+impl AuditLog {
+    pub fn new(db: &mut diesel::PgConnection, record: AuditLogNew<'_>) -> Result<AuditLog> {
+        let r = diesel::insert_into(audit_log::dsl::audit_log)
+            .values(record)
+            .get_result(db)
+            .map_err(|err| anyhow!("Failed to add audit log record: {err}"))?;
+        Ok(r)
+    }
+}
@@ -1,2 +1,5 @@
+pub mod api_key;
+pub mod audit_log;
+pub mod role;
 pub mod user;
 pub mod user_session;
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Context, Result};
+use diesel::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use {{db_plugin}}::schema::{role, role_permission, user_role};
+
+database_pg::make_id!(RoleId);
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = role)]
+pub struct Role {
+    pub id: RoleId,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = role)]
+pub struct RoleNew {
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub name: String,
+    pub description: String,
+}
+
+impl Role {
+    pub fn new(db: &mut diesel::PgConnection, name: String, description: String) -> Result<Role> {
+        let r = diesel::insert_into(role::dsl::role)
+            .values(RoleNew {
+                create_date: chrono::Utc::now(),
+                name,
+                description,
+            })
+            .get_result(db)
+            .context("Failed to add role")?;
+        Ok(r)
+    }
+
+    pub fn of_name(db: &mut diesel::PgConnection, name: &str) -> Result<Role> {
+        let r = role::dsl::role
+            .filter(role::dsl::name.eq(name))
+            .get_result(db)
+            .map_err(|err| anyhow!("Failed to get role {name:?}: {err}"))?;
+        Ok(r)
+    }
+
+    /// Permissions of roles, sorted by name
+    pub fn permissions(
+        db: &mut diesel::PgConnection,
+        ids: &[RoleId],
+    ) -> Result<HashMap<RoleId, Vec<String>>> {
+        let rows = role_permission::table
+            .filter(role_permission::role_id.eq_any(ids))
+            .order((role_permission::role_id, role_permission::permission))
+            .select((role_permission::role_id, role_permission::permission))
+            .load::<(RoleId, String)>(db)
+            .map_err(|err| anyhow!("Failed to get role permissions: {err}"))?;
+        let mut r: HashMap<RoleId, Vec<String>> = HashMap::new();
+        for (id, permission) in rows {
+            r.entry(id).or_default().push(permission);
+        }
+        Ok(r)
+    }
+
+    /// Replaces permissions of role
+    pub fn set_permissions(
+        db: &mut diesel::PgConnection,
+        id: RoleId,
+        permissions: &[String],
+    ) -> Result<()> {
+        let _ = diesel::delete(role_permission::table.filter(role_permission::role_id.eq(id)))
+            .execute(db)?;
+        let rows: Vec<_> = permissions
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|v| {
+                (
+                    role_permission::role_id.eq(id),
+                    role_permission::permission.eq(v),
+                )
+            })
+            .collect();
+        let _ = diesel::insert_into(role_permission::table)
+            .values(rows)
+            .execute(db)?;
+        Ok(())
+    }
+
+    /// Roles of user, sorted by name
+    pub fn of_user(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+    ) -> Result<Vec<Role>> {
+        let r = role::table
+            .inner_join(user_role::table)
+            .filter(user_role::user_id.eq(user_id))
+            .order(role::name)
+            .select(role::all_columns)
+            .load(db)
+            .map_err(|err| anyhow!("Failed to get roles of user {user_id}: {err}"))?;
+        Ok(r)
+    }
+
+    /// Replaces roles of user
+    pub fn set_user_roles(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+        ids: &[RoleId],
+    ) -> Result<()> {
+        let _ =
+            diesel::delete(user_role::table.filter(user_role::user_id.eq(user_id))).execute(db)?;
+        let rows: Vec<_> = ids
+            .iter()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|v| (user_role::user_id.eq(user_id), user_role::role_id.eq(v)))
+            .collect();
+        let _ = diesel::insert_into(user_role::table)
+            .values(rows)
+            .execute(db)?;
+        Ok(())
+    }
+
+    /// Permissions granted to user by all their roles
+    pub fn user_permissions(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+    ) -> Result<BTreeSet<String>> {
+        let r = role_permission::table
+            .inner_join(user_role::table.on(user_role::role_id.eq(role_permission::role_id)))
+            .filter(user_role::user_id.eq(user_id))
+            .select(role_permission::permission)
+            .load::<String>(db)
+            .map_err(|err| anyhow!("Failed to get permissions of user {user_id}: {err}"))?;
+        Ok(r.into_iter().collect())
+    }
+}
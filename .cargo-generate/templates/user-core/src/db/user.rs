@@ -1,16 +1,20 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use diesel::prelude::*;
 use {{db_plugin}}::schema::user;
 
+database_pg::make_id!(UserId);
+
 #[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = user)]
 pub struct User {
-    pub id: i64,
+    pub id: UserId,
     pub create_date: chrono::DateTime<chrono::Utc>,
     pub last_seen_date: Option<chrono::DateTime<chrono::Utc>>,
     pub login_count: i64,
     pub username: String,
     pub person: String,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub version: i64,
 }
 
 #[derive(Insertable)]
@@ -23,6 +27,13 @@ pub struct UserNew {
     pub person: String,
 }
 
+/// Users are soft-deleted together with their sessions and API keys
+pub fn soft_delete() -> database_pg::soft_delete::Table {
+    database_pg::soft_delete::Table::new("user")
+        .cascade("user_session", "user_id")
+        .cascade("api_key", "user_id")
+}
+
 impl User {
     pub fn new(db: &mut diesel::PgConnection, username: String, person: String) -> Result<User> {
         let r = diesel::insert_into(user::dsl::user)
@@ -34,16 +45,22 @@ pub fn new(db: &mut diesel::PgConnection, username: String, person: String) -> R
                 person,
             })
             .get_result(db)
-            .map_err(|err| anyhow!("Failed to add user: {err}"))?;
+            .context("Failed to add user")?;
         Ok(r)
     }
 
     pub fn of_username(db: &mut diesel::PgConnection, username: &str) -> Result<User> {
-        let r = user::dsl::user
+        Self::find_by_username(db, username)?.ok_or_else(|| anyhow!("User {username:?} not found"))
+    }
+
+    /// Not deleted user with `username`, `None` if there is no such user
+    pub fn find_by_username(db: &mut diesel::PgConnection, username: &str) -> Result<Option<User>> {
+        user::dsl::user
             .filter(user::dsl::username.eq(username))
+            .filter(user::dsl::deleted_at.is_null())
             .get_result(db)
-            .map_err(|err| anyhow!("Failed to get user: {err}"))?;
-        Ok(r)
+            .optional()
+            .map_err(|err| anyhow!("Failed to get user: {err}"))
     }
 
     pub fn logged_in(&self, db: &mut diesel::PgConnection) -> Result<()> {
@@ -1,55 +1,209 @@
 use anyhow::{anyhow, Result};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::sql_types::Bool;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use {{db_plugin}}::schema::user_session;
 
+database_pg::make_id!(UserSessionId);
+
 #[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = user_session)]
 pub struct UserSession {
-    pub id: i64,
-    pub user_id: Option<i64>,
-    pub token: database_pg::secstr::SecUtf8,
+    pub id: UserSessionId,
+    pub user_id: Option<crate::db::user::UserId>,
     pub create_date: chrono::DateTime<chrono::Utc>,
     pub last_seen_date: chrono::DateTime<chrono::Utc>,
     pub requests_count: i64,
     pub last_address: ipnet::IpNet,
+    pub token_hash: database_pg::crypto::KeyedHash,
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub remember: bool,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub user_agent: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = user_session)]
 pub struct UserSessionNew {
-    pub user_id: Option<i64>,
-    token: database_pg::secstr::SecUtf8,
+    pub user_id: Option<crate::db::user::UserId>,
+    token_hash: database_pg::crypto::KeyedHash,
     pub create_date: chrono::DateTime<chrono::Utc>,
     pub last_seen_date: chrono::DateTime<chrono::Utc>,
     pub requests_count: i64,
     pub last_address: ipnet::IpNet,
+    pub remember: bool,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub user_agent: Option<String>,
+}
+
+/// Sessions which are not deleted and not expired at `now`
+pub fn is_active(
+    config: &crate::config::SessionConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Box<dyn BoxableExpression<user_session::table, Pg, SqlType = Bool>>> {
+    let idle_since = now - chrono::Duration::from_std(config.idle_timeout)?;
+    Ok(Box::new(
+        user_session::dsl::deleted_at
+            .is_null()
+            .and(user_session::dsl::expires_at.gt(now))
+            .and(
+                user_session::dsl::remember
+                    .eq(true)
+                    .or(user_session::dsl::last_seen_date.gt(idle_since)),
+            ),
+    ))
 }
 
 impl UserSession {
+    /// Creates new session. Only keyed hash of the token is stored, so the token is returned to be passed to the user.
+    /// Session created with `remember` has longer lifetime and no idle timeout
     pub fn new(
         db: &mut diesel::PgConnection,
+        keyring: &database_pg::crypto::Keyring,
+        config: &crate::config::SessionConfig,
         user: &crate::db::user::User,
         last_address: ipnet::IpNet,
-    ) -> Result<UserSession> {
+        user_agent: Option<String>,
+        remember: bool,
+    ) -> Result<(UserSession, database_pg::secstr::SecUtf8)> {
+        let now = chrono::Utc::now();
         let token: String = thread_rng()
             .sample_iter(&Alphanumeric)
             .take(64)
             .map(char::from)
             .collect();
+        let token_hash = keyring.hash(token.as_bytes());
         let token = database_pg::secstr::SecUtf8::from(token);
         let r = diesel::insert_into(user_session::dsl::user_session)
             .values(UserSessionNew {
                 user_id: Some(user.id),
-                token,
-                create_date: chrono::Utc::now(),
-                last_seen_date: chrono::Utc::now(),
+                token_hash,
+                create_date: now,
+                last_seen_date: now,
                 requests_count: 0,
                 last_address,
+                remember,
+                user_agent,
+                expires_at: now + chrono::Duration::from_std(config.lifetime(remember))?,
             })
             .get_result(db)
             .map_err(|err| anyhow!("Failed to add user session: {err}"))?;
-        Ok(r)
+        Ok((r, token))
+    }
+
+    /// Extends session for its lifetime since `now`
+    pub fn renew(
+        &mut self,
+        db: &mut diesel::PgConnection,
+        config: &crate::config::SessionConfig,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        let expires_at = now + chrono::Duration::from_std(config.lifetime(self.remember))?;
+        if expires_at <= self.expires_at {
+            return Ok(());
+        }
+        diesel::update(user_session::dsl::user_session.find(self.id))
+            .set(user_session::dsl::expires_at.eq(expires_at))
+            .execute(db)
+            .map_err(|err| anyhow!("Failed to renew user session: {err}"))?;
+        self.expires_at = expires_at;
+        Ok(())
+    }
+
+    /// Active sessions of user, most recently used first
+    pub fn list_active(
+        db: &mut diesel::PgConnection,
+        config: &crate::config::SessionConfig,
+        user_id: crate::db::user::UserId,
+    ) -> Result<Vec<UserSession>> {
+        user_session::dsl::user_session
+            .filter(user_session::dsl::user_id.eq(user_id))
+            .filter(is_active(config, chrono::Utc::now())?)
+            .order(user_session::dsl::last_seen_date.desc())
+            .load(db)
+            .map_err(|err| anyhow!("Failed to list user sessions: {err}"))
+    }
+
+    /// Removes session of user. Returns `None` if user has no such session
+    pub fn revoke(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+        id: UserSessionId,
+    ) -> Result<Option<UserSession>> {
+        diesel::delete(
+            user_session::dsl::user_session
+                .find(id)
+                .filter(user_session::dsl::user_id.eq(user_id)),
+        )
+        .get_result(db)
+        .optional()
+        .map_err(|err| anyhow!("Failed to revoke user session {id}: {err}"))
     }
+
+    /// Removes all sessions of user, except `except`. Returns IDs of removed sessions
+    pub fn revoke_all(
+        db: &mut diesel::PgConnection,
+        user_id: crate::db::user::UserId,
+        except: Option<UserSessionId>,
+    ) -> Result<Vec<UserSessionId>> {
+        let mut query = diesel::delete(user_session::dsl::user_session)
+            .filter(user_session::dsl::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(except) = except {
+            query = query.filter(user_session::dsl::id.ne(except));
+        }
+        query
+            .returning(user_session::dsl::id)
+            .get_results(db)
+            .map_err(|err| anyhow!("Failed to revoke user sessions: {err}"))
+    }
+
+    /// Removes sessions expired by lifetime or idle timeout. Returns number of removed sessions
+    pub fn remove_expired(
+        db: &mut diesel::PgConnection,
+        config: &crate::config::SessionConfig,
+    ) -> Result<usize> {
+        let now = chrono::Utc::now();
+        let idle_since = now - chrono::Duration::from_std(config.idle_timeout)?;
+        diesel::delete(
+            user_session::dsl::user_session.filter(
+                user_session::dsl::expires_at
+                    .le(now)
+                    .or(user_session::dsl::remember
+                        .eq(false)
+                        .and(user_session::dsl::last_seen_date.le(idle_since))),
+            ),
+        )
+        .execute(db)
+        .map_err(|err| anyhow!("Failed to remove expired user sessions: {err}"))
+    }
+}
+
+/// Job removing expired sessions
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct SessionCleanup {}
+
+impl database_pg::jobs::Job for SessionCleanup {
+    const JOB_TYPE: &'static str = "user_session_cleanup";
+}
+
+/// Registers periodic job removing expired sessions
+pub fn start_cleanup(
+    jobs: &'static database_pg::jobs::Registry,
+    config: &crate::config::SessionConfig,
+) -> Result<()> {
+    let job_config = config.clone();
+    jobs.register_periodic(
+        SessionCleanup {},
+        config.cleanup_interval,
+        move |conn: &mut diesel::PgConnection, _: SessionCleanup| {
+            let removed = UserSession::remove_expired(conn, &job_config)?;
+            if removed > 0 {
+                tracing::info!("Removed {removed} expired user sessions");
+            }
+            Ok(())
+        },
+    )
 }
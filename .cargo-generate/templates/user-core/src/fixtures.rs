@@ -0,0 +1,31 @@
+use anyhow::Result;
+use serde::Deserialize;
+
+/// User account
+#[derive(Deserialize)]
+pub struct UserFixture {
+    pub username: String,
+    pub person: String,
+    /// Names of roles of user
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl database_pg::seed::Fixture for UserFixture {
+    const FIXTURE_TYPE: &'static str = "user";
+
+    fn load(self, conn: &mut diesel::PgConnection) -> Result<Option<i64>> {
+        let user = crate::db::user::User::new(conn, self.username, self.person)?;
+        let roles = self
+            .roles
+            .iter()
+            .map(|v| crate::db::role::Role::of_name(conn, v).map(|v| v.id))
+            .collect::<Result<Vec<_>>>()?;
+        crate::db::role::Role::set_user_roles(conn, user.id, &roles)?;
+        Ok(Some(user.id.get()))
+    }
+}
+
+pub fn register(registry: &mut database_pg::seed::Registry) -> Result<()> {
+    registry.register::<UserFixture>()
+}
@@ -1,33 +1,46 @@
 pub mod api;
+pub mod audit;
+pub mod cli;
+pub mod config;
 pub mod db;
+pub mod fixtures;
+pub mod permission;
 pub mod user;
 
+use actix_web::web::Data;
 use anyhow::Result;
 use async_trait::async_trait;
+use structdoc::StructDoc;
 use utoipa::OpenApi;
 use webapp_core::plugin::{Plugin, PluginMetadata};
 
-pub struct Metadata {}
+pub struct Metadata {
+    configs_path: std::path::PathBuf,
+}
 
 #[async_trait]
 impl PluginMetadata for Metadata {
     fn plugin_name(&self) -> &'static str {
-        ""
+        "{{project_name}}"
     }
 
     fn config_dump(&self) -> Result<Option<String>> {
-        Ok(None)
+        let config: webapp_yaml_config::yaml::Config<crate::config::Config> =
+            webapp_yaml_config::yaml::Config::new(&self.configs_path, self.plugin_name())?;
+        config.as_yaml().map(Some)
     }
 
     fn config_documentation(&self) -> Option<String> {
-        None
+        Some(crate::config::Config::document().to_string())
     }
 
-    fn new(_configs_path: &std::path::Path) -> Result<Self>
+    fn new(configs_path: &std::path::Path) -> Result<Self>
     where
         Self: Sized,
     {
-        Ok(Self {})
+        Ok(Self {
+            configs_path: configs_path.to_path_buf(),
+        })
     }
 
     async fn init_plugin(&self) -> Result<Box<dyn Plugin>>
@@ -37,16 +50,32 @@ async fn init_plugin(&self) -> Result<Box<dyn Plugin>>
         let plugin = PluginImpl::new(self)?;
         Ok(Box::new(plugin))
     }
+
+    async fn run_command(&self, args: &[String]) -> Result<()> {
+        use clap::Parser;
+        crate::cli::CommandLine::parse_from(args)
+            .run(&self.configs_path)
+            .await
+    }
 }
 
-pub struct PluginImpl {}
+pub struct PluginImpl {
+    config: Data<crate::config::Config>,
+}
 
 impl PluginImpl {
-    pub fn new(_metadata: &Metadata) -> Result<Self>
+    pub fn new(metadata: &Metadata) -> Result<Self>
     where
         Self: Sized,
     {
-        Ok(Self {})
+        let config: webapp_yaml_config::yaml::Config<crate::config::Config> =
+            webapp_yaml_config::yaml::Config::new(&metadata.configs_path, metadata.plugin_name())?;
+        {{db_plugin}}::db::SOFT_DELETE.register(crate::db::user::soft_delete())?;
+        crate::permission::register()?;
+        crate::db::user_session::start_cleanup(&{{db_plugin}}::db::JOBS, &config.session)?;
+        Ok(Self {
+            config: Data::from(config.config.clone()),
+        })
     }
 }
 
@@ -56,24 +85,80 @@ fn webapp_initializer(
         service_config: &mut actix_web::web::ServiceConfig,
     ) -> utoipa::openapi::OpenApi {
         let _ = service_config
+            .app_data(self.config.clone())
             .service(crate::api::logout)
-            .service(crate::api::user_list)
-            .service(crate::api::current_user_info);
+            // before CRUD resource, so `export` is not taken for user ID
+            .service(crate::api::user_export)
+            .service(crate::api::current_user_info)
+            // before `{id}` paths, so `logout_others` is not taken for session ID
+            .service(crate::api::my_session_revoke_others)
+            .service(crate::api::my_session_list)
+            .service(crate::api::my_session_revoke)
+            .service(crate::api::user_session_list)
+            .service(crate::api::user_session_revoke_all)
+            .service(crate::api::user_session_revoke)
+            .service(crate::api::my_api_key_list)
+            .service(crate::api::my_api_key_create)
+            .service(crate::api::my_api_key_revoke)
+            .service(crate::api::user_api_key_list)
+            .service(crate::api::user_api_key_revoke)
+            .service(crate::api::permission_list)
+            .service(crate::api::user_role_list)
+            .service(crate::api::user_role_set)
+            .service(crate::api::audit_log_list);
 
         #[derive(OpenApi)]
         #[openapi(
             paths(
                 crate::api::logout,
-                crate::api::user_list,
-                crate::api::current_user_info
+                crate::api::user_export,
+                crate::api::current_user_info,
+                crate::api::my_session_list,
+                crate::api::my_session_revoke_others,
+                crate::api::my_session_revoke,
+                crate::api::user_session_list,
+                crate::api::user_session_revoke_all,
+                crate::api::user_session_revoke,
+                crate::api::my_api_key_list,
+                crate::api::my_api_key_create,
+                crate::api::my_api_key_revoke,
+                crate::api::user_api_key_list,
+                crate::api::user_api_key_revoke,
+                crate::api::permission_list,
+                crate::api::user_role_list,
+                crate::api::user_role_set,
+                crate::api::audit_log_list
             ),
             components(schemas(
-                crate::api::UserListResponse,
-                crate::api::CurrentUserInfoResponse
+                crate::db::user::UserId,
+                crate::api::CurrentUserInfoResponse,
+                crate::db::user_session::UserSessionId,
+                crate::api::UserSessionResponse,
+                crate::api::RevokedSessionsResponse,
+                crate::db::api_key::ApiKeyId,
+                crate::api::ApiKeyResponse,
+                crate::api::ApiKeyCreate,
+                crate::api::ApiKeyCreatedResponse,
+                crate::db::role::RoleId,
+                crate::api::PermissionResponse,
+                crate::api::UserRolesRequest,
+                crate::db::audit_log::AuditLogId,
+                crate::api::AuditLogResponse
             ))
         )]
         struct ApiDoc;
 
-        ApiDoc::openapi()
+        let mut doc = ApiDoc::openapi();
+        doc.merge(
+            react_admin::crud::CrudResource::<crate::api::UserListResponse>::new("/api/v1/user")
+                .tag("User")
+                .register(service_config),
+        );
+        doc.merge(
+            react_admin::crud::CrudResource::<crate::api::RoleResponse>::new("/api/v1/role")
+                .tag("Role")
+                .register(service_config),
+        );
+        doc
     }
 }
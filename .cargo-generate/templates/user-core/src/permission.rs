@@ -0,0 +1,308 @@
+//! Permissions granted to users through roles.
+//!
+//! Plugins declare their permissions with [`Permission`] and register them in [`PERMISSIONS`] on initialization, so
+//! they can be granted to roles. Handlers require a permission with [`RequirePermission`] extractor, or check
+//! [`Permissions`] of the user, resources of `react_admin` use [`ReadWrite`] policy.
+
+use actix_web::web::Data;
+use anyhow::{anyhow, Result};
+use react_admin::crud::{Action, Change, Crud, Policy};
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+use std::sync::RwLock;
+use utoipa::openapi::security::SecurityRequirement;
+
+/// Permission granted by `admin` role, it includes all other permissions
+pub const ALL: &str = "*";
+
+/// Permission which may be granted to roles. Its name is used as scope in OpenAPI security requirements
+pub trait Permission: 'static {
+    /// Unique name, e.g. `users.read`
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+}
+
+/// List and view users and their sessions
+pub struct UsersRead;
+
+impl Permission for UsersRead {
+    const NAME: &'static str = "users.read";
+    const DESCRIPTION: &'static str = "List and view users and their sessions";
+}
+
+/// Create, update, delete and import users, revoke their sessions
+pub struct UsersWrite;
+
+impl Permission for UsersWrite {
+    const NAME: &'static str = "users.write";
+    const DESCRIPTION: &'static str =
+        "Create, update, delete and import users, revoke their sessions";
+}
+
+/// Manage roles and roles of users
+pub struct RolesManage;
+
+impl Permission for RolesManage {
+    const NAME: &'static str = "roles.manage";
+    const DESCRIPTION: &'static str = "Manage roles and assign them to users";
+}
+
+/// Read audit log
+pub struct AuditLogRead;
+
+impl Permission for AuditLogRead {
+    const NAME: &'static str = "audit_log.read";
+    const DESCRIPTION: &'static str = "Read audit log";
+}
+
+/// Permissions declared by plugins
+#[derive(Default)]
+pub struct Registry {
+    permissions: RwLock<BTreeMap<&'static str, &'static str>>,
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            permissions: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn register<P: Permission>(&self) -> Result<()> {
+        let mut permissions = self.permissions.write().unwrap();
+        if P::NAME == ALL || permissions.contains_key(P::NAME) {
+            return Err(anyhow!("Permission {:?} already registered", P::NAME));
+        }
+        let _ = permissions.insert(P::NAME, P::DESCRIPTION);
+        Ok(())
+    }
+
+    /// Names and descriptions of permissions, sorted by name
+    pub fn list(&self) -> Vec<(&'static str, &'static str)> {
+        self.permissions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect()
+    }
+
+    /// Returns error if some of `names` is neither registered nor [`ALL`]
+    pub fn check(&self, names: &[String]) -> Result<()> {
+        let permissions = self.permissions.read().unwrap();
+        match names
+            .iter()
+            .find(|v| *v != ALL && !permissions.contains_key(v.as_str()))
+        {
+            Some(v) => Err(anyhow!("Unknown permission {v:?}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Permissions which may be granted to roles
+pub static PERMISSIONS: Registry = Registry::new();
+
+/// Registers permissions of this plugin
+pub fn register() -> Result<()> {
+    PERMISSIONS.register::<UsersRead>()?;
+    PERMISSIONS.register::<UsersWrite>()?;
+    PERMISSIONS.register::<RolesManage>()?;
+    PERMISSIONS.register::<AuditLogRead>()
+}
+
+/// Security requirements of endpoints which require `permission`
+pub fn security(permission: &str) -> Vec<SecurityRequirement> {
+    vec![
+        SecurityRequirement::new("session_cookie", [permission]),
+        SecurityRequirement::new("authorization_header", [permission]),
+    ]
+}
+
+/// Logged in user with permissions granted by their roles, limited to scopes of API key if request is authenticated
+/// with it
+pub struct Permissions {
+    pub user: crate::user::User,
+    granted: BTreeSet<String>,
+}
+
+fn has(granted: &BTreeSet<String>, name: &str) -> bool {
+    granted.contains(ALL) || granted.contains(name)
+}
+
+impl Permissions {
+    pub fn has(&self, name: &str) -> bool {
+        has(&self.granted, name)
+    }
+
+    /// Granted permission names, sorted
+    pub fn granted(&self) -> impl Iterator<Item = &str> {
+        self.granted.iter().map(|v| v.as_str())
+    }
+
+    /// Whether every permission of `other` is granted, e.g. of user whose credentials are changed, so acting as them
+    /// does not escalate privileges
+    pub fn includes(&self, other: &BTreeSet<String>) -> bool {
+        other.iter().all(|v| self.has(v))
+    }
+
+    /// Returns 403 unless permission `P` is granted
+    pub fn require<P: Permission>(&self) -> actix_web::Result<()> {
+        if self.has(P::NAME) {
+            Ok(())
+        } else {
+            Err(actix_web::error::ErrorForbidden("Action is not allowed"))
+        }
+    }
+}
+
+impl actix_web::FromRequest for Permissions {
+    type Error = actix_web::Error;
+
+    type Future = std::pin::Pin<Box<dyn futures_util::Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let user = crate::user::User::from_request(req, payload);
+        let db = req.app_data::<Data<{{db_plugin}}::db::DB>>().cloned();
+
+        Box::pin(async move {
+            let user = user.await?;
+            let db = db.ok_or_else(|| {
+                actix_web::error::ErrorInternalServerError("No user DB available")
+            })?;
+            let user_id = user.user.id;
+            let api_key = match &user.credential {
+                crate::user::Credential::ApiKey(v) => Some(v.id),
+                crate::user::Credential::Session(_) => None,
+            };
+            let granted = db
+                .pool
+                .with_connection(move |conn| {
+                    let granted = crate::db::role::Role::user_permissions(conn, user_id)?;
+                    let scopes = match api_key {
+                        Some(id) => crate::db::api_key::ApiKey::scopes(conn, &[id])?.remove(&id),
+                        None => None,
+                    };
+                    // API key has only the permissions of its scopes which user has
+                    Ok(match scopes {
+                        Some(scopes) => scopes
+                            .into_iter()
+                            .filter(|v| granted.contains(ALL) || granted.contains(v))
+                            .collect(),
+                        None => granted,
+                    })
+                })
+                .await
+                .map_err(|err| {
+                    tracing::error!("Cannot check permissions: {err}");
+                    actix_web::error::ErrorInternalServerError("Failed to get permissions")
+                })?;
+            Ok(Self { user, granted })
+        })
+    }
+}
+
+/// Logged in user who has permission `P`, otherwise request is rejected with 403
+pub struct RequirePermission<P: Permission> {
+    pub user: crate::user::User,
+    permission: PhantomData<P>,
+}
+
+impl<P: Permission> actix_web::FromRequest for RequirePermission<P> {
+    type Error = actix_web::Error;
+
+    type Future = std::pin::Pin<Box<dyn futures_util::Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let permissions = Permissions::from_request(req, payload);
+
+        Box::pin(async move {
+            let permissions = permissions.await?;
+            permissions.require::<P>()?;
+            Ok(Self {
+                user: permissions.user,
+                permission: PhantomData,
+            })
+        })
+    }
+}
+
+/// Who makes changes of [`ReadWrite`] resources
+pub struct Actor {
+    pub audit: crate::audit::AuditContext,
+    granted: BTreeSet<String>,
+}
+
+impl Actor {
+    /// Whether every permission of `other` is granted, see [`Permissions::includes`]
+    pub fn includes(&self, other: &BTreeSet<String>) -> bool {
+        other.iter().all(|v| has(&self.granted, v))
+    }
+}
+
+/// Policy of resources which are read with permission `R` and changed with permission `W`
+pub struct ReadWrite<R: Permission, W: Permission> {
+    permissions: PhantomData<(R, W)>,
+}
+
+impl<R: Permission, W: Permission> ReadWrite<R, W> {
+    fn permission(action: Action) -> &'static str {
+        match action {
+            Action::List | Action::Read | Action::ReadDeleted => R::NAME,
+            Action::Create | Action::Update | Action::Delete | Action::Restore => W::NAME,
+        }
+    }
+}
+
+impl<R: Permission, W: Permission> Policy for ReadWrite<R, W> {
+    type Subject = Permissions;
+    type Context = Actor;
+
+    fn allowed(subject: &Self::Subject, action: Action) -> bool {
+        subject.has(Self::permission(action))
+    }
+
+    fn context(subject: &Self::Subject, req: &actix_web::HttpRequest) -> Self::Context {
+        Actor {
+            audit: crate::audit::AuditContext::new(Some(&subject.user.user), req),
+            granted: subject.granted.clone(),
+        }
+    }
+
+    /// Records change in audit log with changed fields
+    fn record<C: Crud>(
+        conn: &mut diesel::PgConnection,
+        context: &Self::Context,
+        change: Change<'_, C>,
+    ) -> Result<()> {
+        let action = match change.action {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+            Action::List | Action::Read | Action::ReadDeleted => return Ok(()),
+        };
+        let changes = crate::audit::diff(change.before, change.after)?;
+        // updates which change nothing, e.g. with empty body, are not recorded
+        if change.action == Action::Update && changes.as_object().is_some_and(|v| v.is_empty()) {
+            return Ok(());
+        }
+        context.audit.record(
+            conn,
+            action,
+            C::ENTITY,
+            Some(change.id.clone().into()),
+            changes,
+        )
+    }
+
+    fn security(action: Action) -> Vec<SecurityRequirement> {
+        security(Self::permission(action))
+    }
+}
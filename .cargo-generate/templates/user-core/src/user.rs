@@ -2,16 +2,55 @@
 use anyhow::anyhow;
 use diesel::prelude::*;
 use {{db_plugin}}::schema::{user, user_session};
+use webapp_core::auth::Unauthorized;
 use webapp_core::SESSION_COOKIE_NAME;
 
+/// How request is authenticated
+pub enum Credential {
+    /// Session token from `Authorization: Bearer` header or session cookie
+    Session(crate::db::user_session::UserSession),
+    /// API key from `Authorization: Bearer` header
+    ApiKey(crate::db::api_key::ApiKey),
+}
+
 pub struct User {
     pub user: crate::db::user::User,
-    pub session: crate::db::user_session::UserSession,
+    pub credential: Credential,
+}
+
+enum Token {
+    Session(String),
+    ApiKey(String),
+}
+
+/// `User-Agent` header of request, as stored in the session
+pub fn user_agent(req: &actix_web::HttpRequest) -> Option<String> {
+    req.headers()
+        .get(actix_web::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(512).collect())
 }
 
 impl User {
+    /// Session of request, `None` if request is authenticated with API key
+    pub fn session(&self) -> Option<&crate::db::user_session::UserSession> {
+        match &self.credential {
+            Credential::Session(v) => Some(v),
+            Credential::ApiKey(_) => None,
+        }
+    }
+
+    /// Session of request, 403 if request is authenticated with API key
+    pub fn require_session(&self) -> actix_web::Result<&crate::db::user_session::UserSession> {
+        self.session()
+            .ok_or_else(|| actix_web::error::ErrorForbidden("Session is required, not API key"))
+    }
+
+    /// Removes session of request
     pub fn logout(&self, db: &mut diesel::PgConnection) -> anyhow::Result<()> {
-        diesel::delete(user_session::dsl::user_session.find(self.session.id)).execute(db)?;
+        if let Some(session) = self.session() {
+            diesel::delete(user_session::dsl::user_session.find(session.id)).execute(db)?;
+        }
         Ok(())
     }
 }
@@ -25,21 +64,14 @@ fn from_request(
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        let token = req
-            .headers()
-            .get(actix_web::http::header::AUTHORIZATION)
-            .and_then(|v| v.to_str().ok().map(|v| v.to_owned()))
-            .or_else(|| {
-                req.cookie(SESSION_COOKIE_NAME)
-                    .map(|v| v.value().to_owned())
-            });
-        let token = match token {
-            Some(v) => v,
-            None => {
-                return Box::pin(
-                    async move { Err(actix_web::error::ErrorForbidden("Not authorized")) },
-                )
-            }
+        let token = match webapp_core::auth::bearer_token(req) {
+            Ok(Some(v)) if v.starts_with(crate::db::api_key::PREFIX) => Token::ApiKey(v.to_owned()),
+            Ok(Some(v)) => Token::Session(v.to_owned()),
+            Ok(None) => match req.cookie(SESSION_COOKIE_NAME) {
+                Some(v) => Token::Session(v.value().to_owned()),
+                None => return Box::pin(async move { Err(Unauthorized::missing().into()) }),
+            },
+            Err(err) => return Box::pin(async move { Err(err.into()) }),
         };
         let db = match req.app_data::<Data<{{db_plugin}}::db::DB>>() {
             Some(v) => v.clone(),
@@ -52,6 +84,18 @@ fn from_request(
             }
         };
 
+        let keyring = match db.pool.keyring() {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!("Cannot check session token: {err}");
+                return Box::pin(async move {
+                    Err(actix_web::error::ErrorInternalServerError(
+                        "No user DB encryption available",
+                    ))
+                });
+            }
+        };
+
         let client_ip = match req.peer_addr() {
             None => {
                 return Box::pin(async move {
@@ -63,34 +107,96 @@ fn from_request(
             Some(v) => ipnet::IpNet::from(v.ip()),
         };
 
+        let config = match req.app_data::<Data<crate::config::Config>>() {
+            Some(v) => v.clone(),
+            None => {
+                return Box::pin(async move {
+                    Err(actix_web::error::ErrorInternalServerError(
+                        "No user config available",
+                    ))
+                })
+            }
+        };
+
+        let user_agent = user_agent(req);
         let now = chrono::Utc::now();
 
         Box::pin(async move {
-            db.pool
+            let user = db
+                .pool
                 .with_transaction(move |conn| {
-                    let session: crate::db::user_session::UserSession = diesel::update(
-                        user_session::dsl::user_session.filter(user_session::dsl::token.eq(token)),
-                    )
-                    .set((
-                        user_session::dsl::last_seen_date.eq(now),
-                        user_session::dsl::last_address.eq(client_ip),
-                        user_session::dsl::requests_count.eq(user_session::dsl::requests_count + 1),
-                    ))
-                    .get_result(conn)?;
+                    let credential = match token {
+                        Token::Session(token) => {
+                            let config = &config.session;
+                            let token_hashes = keyring.hash_candidates(token.as_bytes());
+                            // expired sessions are left for the cleanup job
+                            let session: Option<crate::db::user_session::UserSession> =
+                                diesel::update(
+                                    user_session::dsl::user_session
+                                        .filter(user_session::dsl::token_hash.eq_any(token_hashes))
+                                        .filter(crate::db::user_session::is_active(config, now)?),
+                                )
+                                .set((
+                                    user_session::dsl::last_seen_date.eq(now),
+                                    user_session::dsl::last_address.eq(client_ip),
+                                    user_session::dsl::user_agent.eq(user_agent),
+                                    user_session::dsl::requests_count
+                                        .eq(user_session::dsl::requests_count + 1),
+                                ))
+                                .get_result(conn)
+                                .optional()
+                                .map_err(|err| anyhow!("Failed to check user session: {err}"))?;
+                            let Some(mut session) = session else {
+                                return Ok(None);
+                            };
+                            if config.sliding_renewal {
+                                session.renew(conn, config, now)?;
+                            }
+                            Credential::Session(session)
+                        }
+                        Token::ApiKey(key) => {
+                            match crate::db::api_key::ApiKey::authenticate(
+                                conn, &keyring, &key, now,
+                            )? {
+                                Some(v) => Credential::ApiKey(v),
+                                None => return Ok(None),
+                            }
+                        }
+                    };
 
-                    let user_id = match session.user_id {
-                        None => return Err(anyhow!("Not authorized")),
-                        Some(v) => v,
+                    let user_id = match &credential {
+                        Credential::Session(v) => match v.user_id {
+                            None => return Ok(None),
+                            Some(v) => v,
+                        },
+                        Credential::ApiKey(v) => v.user_id,
                     };
 
-                    let user: crate::db::user::User = diesel::update(user::dsl::user.find(user_id))
-                        .set(user::dsl::last_seen_date.eq(Some(now)))
-                        .get_result::<crate::db::user::User>(conn)?;
+                    let user: Option<crate::db::user::User> = diesel::update(
+                        user::dsl::user
+                            .find(user_id)
+                            .filter(user::dsl::deleted_at.is_null()),
+                    )
+                    .set(user::dsl::last_seen_date.eq(Some(now)))
+                    .get_result(conn)
+                    .optional()
+                    .map_err(|err| anyhow!("Failed to update user: {err}"))?;
 
-                    Ok(User { user, session })
+                    Ok(user.map(|user| User { user, credential }))
                 })
-                .await
-                .map_err(|_| actix_web::error::ErrorForbidden("Not authorized"))
+                .await;
+            match user {
+                Ok(Some(v)) => Ok(v),
+                Ok(None) => {
+                    Err(Unauthorized::invalid_token("Token is unknown, expired or revoked").into())
+                }
+                Err(err) => {
+                    tracing::error!("Cannot check session token: {err}");
+                    Err(actix_web::error::ErrorInternalServerError(
+                        "Failed to check session token",
+                    ))
+                }
+            }
         })
     }
 }
@@ -0,0 +1,2 @@
+drop table login_lockout;
+drop table login_failure;
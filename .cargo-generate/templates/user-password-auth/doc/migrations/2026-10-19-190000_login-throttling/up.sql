@@ -0,0 +1,28 @@
+create table login_failure (
+  id bigint not null primary key default nextval('object_id_seq'),
+  create_date timestamptz not null default current_timestamp,
+  username varchar(255) not null,
+  address inet not null
+);
+comment on table login_failure is 'Failed login attempts, used to throttle login';
+comment on column login_failure.username is 'Username as entered, user may not exist';
+comment on column login_failure.address is 'Client IP address';
+
+create index login_failure_username on login_failure(username, create_date);
+create index login_failure_address on login_failure(address, create_date);
+
+create table login_lockout (
+  id bigint not null primary key default nextval('object_id_seq'),
+  create_date timestamptz not null default current_timestamp,
+  username varchar(255) default null,
+  address inet default null,
+  locked_until timestamptz not null,
+  check ((username is null) <> (address is null))
+);
+comment on table login_lockout is 'Usernames and IP addresses locked out of login after too many failed attempts';
+comment on column login_lockout.username is 'Locked username, if lockout is by username';
+comment on column login_lockout.address is 'Locked client IP address, if lockout is by address';
+comment on column login_lockout.locked_until is 'Date when lockout ends';
+
+create index login_lockout_username on login_lockout(username) where username is not null;
+create index login_lockout_address on login_lockout(address) where address is not null;
@@ -0,0 +1 @@
+alter table user_password drop column must_change;
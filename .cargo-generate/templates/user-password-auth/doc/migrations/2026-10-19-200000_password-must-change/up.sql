@@ -0,0 +1,2 @@
+alter table user_password add column must_change boolean not null default false;
+comment on column user_password.must_change is 'User must change password on the next login, e.g. after it is reset by administrator';
@@ -1,10 +1,14 @@
 use actix_http::StatusCode;
 use actix_web::{
-    post,
+    delete, get,
+    http::header,
+    post, put,
     web::{self, Data},
-    HttpRequest, Responder,
+    CustomizeResponder, HttpRequest, HttpResponse, Responder,
 };
+use react_admin::{crud::Invalid, APIList, APIObject};
 use serde::{Deserialize, Serialize};
+use user_core::permission::{Permissions, RequirePermission, UsersRead, UsersWrite};
 use utoipa::ToSchema;
 
 /// Data required for login
@@ -14,29 +18,167 @@ pub struct LoginRequest {
     username: String,
     /// User password
     password: webapp_core::secstr::SecUtf8,
+    /// Remember me: session has longer lifetime and does not expire when it is not used
+    #[serde(default)]
+    remember: bool,
+    /// New password, if password must be changed, e.g. after it is reset by administrator. Ignored otherwise
+    new_password: Option<webapp_core::secstr::SecUtf8>,
 }
 
 /// Response for login
 #[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
-    /// Session token
-    token: webapp_core::secstr::SecUtf8,
+    /// Session token, unless it is delivered only in cookie
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<webapp_core::secstr::SecUtf8>,
 }
 
-/// Creates new session for user
+/// 429 response with `Retry-After` header
+fn too_many_attempts(
+    retry_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> actix_web::Error {
+    let message = "Too many failed login attempts, try again later";
+    let seconds = ((retry_at - now).num_milliseconds() + 999) / 1000;
+    actix_web::error::InternalError::from_response(
+        message,
+        HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, seconds.max(1).to_string()))
+            .body(message),
+    )
+    .into()
+}
+
+/// Login error when password is correct, but must be changed
+#[derive(Debug)]
+struct PasswordChangeRequired;
+
+impl std::fmt::Display for PasswordChangeRequired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password must be changed, send the new one in new_password")
+    }
+}
+
+impl std::error::Error for PasswordChangeRequired {}
+
+/// Password reset error when target user has permissions which administrator does not have
+#[derive(Debug)]
+struct PrivilegedUser;
+
+impl std::fmt::Display for PrivilegedUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("User has permissions which are not granted to you")
+    }
+}
+
+impl std::error::Error for PrivilegedUser {}
+
+/// Login error when attempts are throttled until the given time
+#[derive(Debug)]
+struct Throttled(chrono::DateTime<chrono::Utc>);
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Login attempts are throttled until {}", self.0)
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// Checks password with `f` within transaction, unless attempts of `username` from `address` are throttled. Attempts
+/// with the same username or address are serialized, so parallel ones cannot bypass throttling. Changes of `f` are
+/// rolled back if password is wrong, but the failed attempt and lockouts it causes are recorded
+fn throttled_attempt<T>(
+    conn: &mut diesel::PgConnection,
+    throttling: &crate::config::ThrottlingConfig,
+    audit: &user_core::audit::AuditContext,
+    username: &str,
+    address: ipnet::IpNet,
+    now: chrono::DateTime<chrono::Utc>,
+    f: impl FnOnce(&mut diesel::PgConnection) -> anyhow::Result<T>,
+) -> anyhow::Result<Result<T, crate::db::user_password::WrongPassword>> {
+    use diesel::Connection;
+
+    if throttling.enabled {
+        crate::db::login_throttle::lock(conn, username, address)?;
+        let retry_at =
+            crate::db::login_throttle::retry_at(conn, throttling, username, address, now)?;
+        if let Some(retry_at) = retry_at {
+            return Err(Throttled(retry_at).into());
+        }
+    }
+    match conn.transaction(f) {
+        Ok(v) => Ok(Ok(v)),
+        Err(err) if err.is::<crate::db::user_password::WrongPassword>() => {
+            if throttling.enabled {
+                let lockouts = crate::db::login_throttle::record_failure(
+                    conn, throttling, username, address, now,
+                )?;
+                for v in lockouts {
+                    tracing::warn!(
+                        "Login of {} is locked out until {}",
+                        v.subject(),
+                        v.locked_until
+                    );
+                    audit.record(
+                        conn,
+                        "lock_out",
+                        "login_lockout",
+                        Some(v.id.get()),
+                        lockout_changes(&v),
+                    )?;
+                }
+            }
+            Ok(Err(crate::db::user_password::WrongPassword))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Error response of failed [`throttled_attempt`]
+fn attempt_error(
+    err: anyhow::Error,
+    username: &str,
+    address: ipnet::IpNet,
+    now: chrono::DateTime<chrono::Utc>,
+) -> actix_web::Error {
+    match err.downcast_ref::<Throttled>() {
+        Some(Throttled(retry_at)) => {
+            tracing::warn!("Login of {username:?} from {address} is throttled until {retry_at}");
+            too_many_attempts(*retry_at, now)
+        }
+        None => react_admin::crud::db_error(err),
+    }
+}
+
+/// Creates new session for user. Session token is returned in response body, session cookie or both, depending on
+/// `token_delivery` of user config. After too many failed attempts with the same username or from the same address
+/// login is delayed or locked out, depending on `throttling` of this plugin config. If password must be changed, login
+/// fails until the new one is sent in `new_password`, other sessions of user are revoked then
 #[utoipa::path(
     responses(
-        (status = OK, description = "Session created", body = LoginResponse),
-        (status = FORBIDDEN, description = "No such user or password is incorrect")
+        (status = OK, description = "Session created", body = LoginResponse,
+         headers(
+             ("Set-Cookie" = String, description = "Session cookie"),
+         )),
+        (status = BAD_REQUEST, description = "New password is not allowed by password policy"),
+        (status = FORBIDDEN, description = "No such user, password is incorrect or it must be changed"),
+        (status = TOO_MANY_REQUESTS, description = "Too many failed login attempts",
+         headers(
+             ("Retry-After" = u64, description = "Seconds until the next attempt is allowed"),
+         ))
     ),
     tag = "User",
     )]
 #[post("/api/v1/user/login/password")]
 pub async fn login(
     db: Data<{{db_plugin}}::db::DB>,
+    config: Data<user_core::config::Config>,
+    password_config: Data<crate::config::Config>,
+    policy: Data<crate::policy::Policy>,
     login_request: web::Json<LoginRequest>,
     req: HttpRequest,
-) -> actix_web::error::Result<impl Responder> {
+) -> actix_web::error::Result<CustomizeResponder<web::Json<LoginResponse>>> {
     let client_ip = match req.peer_addr() {
         None => {
             tracing::error!("No peer address available");
@@ -47,30 +189,466 @@ pub async fn login(
         }
         Some(v) => v,
     };
+    let address = ipnet::IpNet::from(client_ip.ip());
+    let now = chrono::Utc::now();
+    let throttling = password_config.throttling.clone();
+    let keyring = db.pool.keyring().map_err(|err| {
+        tracing::error!("Cannot create session token: {err}");
+        actix_web::error::InternalError::new(
+            "Session tokens are not available",
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+    })?;
     let login_request_transaction = login_request.clone();
-    let user_token = db
+    let username = login_request.username.clone();
+    let remember = login_request.remember;
+    let session_config = config.clone();
+    let throttling_enabled = throttling.enabled;
+    let audit = user_core::audit::AuditContext::new(None, &req);
+    let failure_audit = audit.clone();
+    let policy = policy.into_inner();
+    let user_agent = user_core::user::user_agent(&req);
+    let result = db
         .pool
         .with_transaction(move |conn| {
-            let user =
-                user_core::db::user::User::of_username(conn, &login_request_transaction.username)?;
-            user.logged_in(conn)?;
-            crate::db::user_password::UserPassword::of_user(conn, &user)?.validate_password(
-                &database_pg::secstr::SecUtf8::from(Into::<secstr::SecUtf8>::into(
-                    login_request_transaction.password,
-                )),
-            )?;
-            user_core::db::user_session::UserSession::new(conn, &user, client_ip.ip().into())
+            throttled_attempt(
+                conn,
+                &throttling,
+                &failure_audit,
+                &username,
+                address,
+                now,
+                |conn| {
+                    let password =
+                        Into::<secstr::SecUtf8>::into(login_request_transaction.password);
+                    let (user, user_password) =
+                        crate::db::user_password::UserPassword::authenticate(
+                            conn,
+                            &login_request_transaction.username,
+                            &database_pg::secstr::SecUtf8::from(password.clone()),
+                        )?;
+                    let audit = audit.with_actor(&user);
+                    if user_password.must_change {
+                        let new_password = Into::<secstr::SecUtf8>::into(
+                            login_request_transaction
+                                .new_password
+                                .ok_or(PasswordChangeRequired)?,
+                        );
+                        if new_password == password {
+                            return Err(Invalid(
+                                "New password must differ from the current one".to_owned(),
+                            )
+                            .into());
+                        }
+                        let _ = crate::db::user_password::UserPassword::set(
+                            conn,
+                            &policy,
+                            &user,
+                            &new_password,
+                            false,
+                        )?;
+                        let revoked = user_core::db::user_session::UserSession::revoke_all(
+                            conn, user.id, None,
+                        )?;
+                        audit.record(
+                            conn,
+                            "change_password",
+                            "user",
+                            Some(user.id.get()),
+                            serde_json::json!({ "revoked_sessions": revoked.len() }),
+                        )?;
+                    }
+                    user.logged_in(conn)?;
+                    if throttling_enabled {
+                        crate::db::login_throttle::clear_failures(
+                            conn,
+                            &login_request_transaction.username,
+                        )?;
+                    }
+                    let (session, token) = user_core::db::user_session::UserSession::new(
+                        conn,
+                        &keyring,
+                        &session_config.session,
+                        &user,
+                        client_ip.ip().into(),
+                        user_agent,
+                        login_request_transaction.remember,
+                    )?;
+                    audit.record(
+                        conn,
+                        "login",
+                        "user_session",
+                        Some(session.id.get()),
+                        serde_json::json!({}),
+                    )?;
+                    Ok((session, token))
+                },
+            )
         })
-        .await
-        .map_err(|err| {
+        .await;
+    let (_session, user_token) = match result {
+        Ok(Ok(v)) => v,
+        Ok(Err(err)) => {
             tracing::warn!("Login failed for request {:?}: {err}", login_request);
-            actix_web::error::InternalError::new(
+            return Err(actix_web::error::InternalError::new(
                 "No such user or password is incorrect",
                 StatusCode::FORBIDDEN,
             )
+            .into());
+        }
+        Err(err) if err.is::<PasswordChangeRequired>() => {
+            return Err(actix_web::error::InternalError::new(
+                err.to_string(),
+                StatusCode::FORBIDDEN,
+            )
+            .into());
+        }
+        Err(err) => {
+            if !err.is::<Throttled>() {
+                tracing::error!("Login failed for request {:?}: {err}", login_request);
+            }
+            return Err(attempt_error(err, &login_request.username, address, now));
+        }
+    };
+
+    let delivery = config.session.token_delivery;
+    let cookie = delivery.cookie().then(|| {
+        config
+            .session
+            .session_cookie(user_token.unsecure(), remember)
+    });
+    let token = delivery
+        .body()
+        .then(|| webapp_core::secstr::SecUtf8::from(Into::<secstr::SecUtf8>::into(user_token)));
+    let mut r = web::Json(LoginResponse { token }).customize();
+    if let Some(cookie) = cookie {
+        r = r.append_header((header::SET_COOKIE, cookie.encoded().to_string()));
+    }
+    Ok(r)
+}
+
+/// Current and new password of user
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct PasswordChangeRequest {
+    /// Current password
+    current_password: webapp_core::secstr::SecUtf8,
+    /// New password, it must be allowed by password policy
+    new_password: webapp_core::secstr::SecUtf8,
+}
+
+/// Password set by administrator
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct PasswordResetRequest {
+    /// New password, it must be allowed by password policy
+    new_password: webapp_core::secstr::SecUtf8,
+}
+
+/// Result of password change
+#[derive(Serialize, ToSchema)]
+pub struct PasswordChangedResponse {
+    /// Number of revoked sessions of user
+    revoked_sessions: usize,
+}
+
+/// Changes password of current user and revokes their other sessions. Request must be authenticated with session,
+/// wrong current password counts as failed login attempt
+#[utoipa::path(
+    request_body = PasswordChangeRequest,
+    responses(
+        (status = OK, description = "Password changed", body = PasswordChangedResponse, content_type = "application/json"),
+        (status = BAD_REQUEST, description = "New password is not allowed by password policy"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Current password is incorrect or request is authenticated with API key"),
+        (status = TOO_MANY_REQUESTS, description = "Too many failed login attempts",
+         headers(
+             ("Retry-After" = u64, description = "Seconds until the next attempt is allowed"),
+         ))
+    ),
+    security(("session_cookie" = []), ("authorization_header" = [])),
+    tag = "User",
+)]
+#[post("/api/v1/user/password")]
+pub async fn password_change(
+    db: Data<{{db_plugin}}::db::DB>,
+    password_config: Data<crate::config::Config>,
+    policy: Data<crate::policy::Policy>,
+    user: user_core::user::User,
+    request: web::Json<PasswordChangeRequest>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<PasswordChangedResponse>> {
+    let session_id = user.require_session()?.id;
+    let address = match req.peer_addr() {
+        Some(v) => ipnet::IpNet::from(v.ip()),
+        None => {
+            return Err(actix_web::error::ErrorForbidden(
+                "No peer address available",
+            ))
+        }
+    };
+    let now = chrono::Utc::now();
+    let throttling = password_config.throttling.clone();
+    let audit = user_core::audit::AuditContext::new(Some(&user.user), &req);
+    let failure_audit = audit.clone();
+    let policy = policy.into_inner();
+    let request = request.into_inner();
+    let current_password = Into::<secstr::SecUtf8>::into(request.current_password);
+    let new_password = Into::<secstr::SecUtf8>::into(request.new_password);
+    let account = user.user.clone();
+    let username = user.user.username.clone();
+    let revoked = db
+        .pool
+        .with_transaction(move |conn| {
+            throttled_attempt(
+                conn,
+                &throttling,
+                &failure_audit,
+                &username,
+                address,
+                now,
+                |conn| {
+                    crate::db::user_password::UserPassword::of_user(conn, &account)?
+                        .ok_or(crate::db::user_password::WrongPassword)?
+                        .validate_password(&database_pg::secstr::SecUtf8::from(
+                            current_password.clone(),
+                        ))?;
+                    if new_password == current_password {
+                        return Err(Invalid(
+                            "New password must differ from the current one".to_owned(),
+                        )
+                        .into());
+                    }
+                    let _ = crate::db::user_password::UserPassword::set(
+                        conn,
+                        &policy,
+                        &account,
+                        &new_password,
+                        false,
+                    )?;
+                    let revoked = user_core::db::user_session::UserSession::revoke_all(
+                        conn,
+                        account.id,
+                        Some(session_id),
+                    )?;
+                    audit.record(
+                        conn,
+                        "change_password",
+                        "user",
+                        Some(account.id.get()),
+                        serde_json::json!({ "revoked_sessions": revoked.len() }),
+                    )?;
+                    Ok(revoked.len())
+                },
+            )
+        })
+        .await;
+    match revoked {
+        Ok(Ok(revoked_sessions)) => APIObject::ok(PasswordChangedResponse { revoked_sessions }),
+        Ok(Err(_)) => Err(actix_web::error::ErrorForbidden(
+            "Current password is incorrect",
+        )),
+        Err(err) => Err(attempt_error(err, &user.user.username, address, now)),
+    }
+}
+
+/// Sets password of user and revokes their sessions. User must change it on the next login if `change_after_reset`
+/// is set in this plugin config. Administrator must have every permission of the user, otherwise they could log in as
+/// more privileged user
+#[utoipa::path(
+    params(
+        ("id" = user_core::db::user::UserId, Path, description = "User ID"),
+    ),
+    request_body = PasswordResetRequest,
+    responses(
+        (status = OK, description = "Password set", body = PasswordChangedResponse, content_type = "application/json"),
+        (status = BAD_REQUEST, description = "Password is not allowed by password policy"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed or user has permissions which are not granted to you"),
+        (status = NOT_FOUND, description = "No such user")
+    ),
+    security(("session_cookie" = ["users.write"]), ("authorization_header" = ["users.write"])),
+    tag = "User",
+)]
+#[put("/api/v1/user/{id}/password")]
+pub async fn password_reset(
+    db: Data<{{db_plugin}}::db::DB>,
+    policy: Data<crate::policy::Policy>,
+    permissions: Permissions,
+    id: web::Path<user_core::db::user::UserId>,
+    request: web::Json<PasswordResetRequest>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<PasswordChangedResponse>> {
+    use diesel::prelude::*;
+    use {{db_plugin}}::schema::user;
+
+    permissions.require::<UsersWrite>()?;
+    let id = id.into_inner();
+    let user = &permissions.user;
+    // administrator resetting their own password keeps the current session
+    let keep_session = user.session().filter(|_| user.user.id == id).map(|v| v.id);
+    let audit = user_core::audit::AuditContext::new(Some(&user.user), &req);
+    let policy = policy.into_inner();
+    let new_password = Into::<secstr::SecUtf8>::into(request.into_inner().new_password);
+    let revoked = db
+        .pool
+        .with_transaction(move |conn| {
+            let target: Option<user_core::db::user::User> = user::table
+                .find(id)
+                .filter(user::deleted_at.is_null())
+                .get_result(conn)
+                .optional()?;
+            let target = match target {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+            let target_permissions = user_core::db::role::Role::user_permissions(conn, target.id)?;
+            if !permissions.includes(&target_permissions) {
+                return Err(PrivilegedUser.into());
+            }
+            let must_change = policy.change_after_reset;
+            let _ = crate::db::user_password::UserPassword::set(
+                conn,
+                &policy,
+                &target,
+                &new_password,
+                must_change,
+            )?;
+            let revoked = user_core::db::user_session::UserSession::revoke_all(
+                conn,
+                target.id,
+                keep_session,
+            )?;
+            audit.record(
+                conn,
+                "reset_password",
+                "user",
+                Some(target.id.get()),
+                serde_json::json!({ "must_change": must_change, "revoked_sessions": revoked.len() }),
+            )?;
+            Ok(Some(revoked.len()))
+        })
+        .await
+        .map_err(|err| match err.downcast_ref::<PrivilegedUser>() {
+            Some(v) => actix_web::error::ErrorForbidden(v.to_string()),
+            None => react_admin::crud::db_error(err),
         })?;
+    match revoked {
+        Some(revoked_sessions) => APIObject::ok(PasswordChangedResponse { revoked_sessions }),
+        None => Err(actix_web::error::ErrorNotFound("User not found")),
+    }
+}
 
-    Ok(web::Json(LoginResponse {
-        token: webapp_core::secstr::SecUtf8::from(Into::<secstr::SecUtf8>::into(user_token.token)),
-    }))
+/// Login locked out after too many failed attempts, either by username or by client address
+#[derive(Serialize, ToSchema)]
+pub struct LoginLockoutResponse {
+    /// Lockout ID
+    pub id: crate::db::login_throttle::LoginLockoutId,
+    /// Locked username
+    pub username: Option<String>,
+    /// Locked client address
+    pub address: Option<String>,
+    /// When lockout started
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    /// When lockout ends
+    pub locked_until: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<crate::db::login_throttle::LoginLockout> for LoginLockoutResponse {
+    fn from(v: crate::db::login_throttle::LoginLockout) -> Self {
+        Self {
+            id: v.id,
+            username: v.username,
+            address: v.address.map(|v| v.addr().to_string()),
+            create_date: v.create_date,
+            locked_until: v.locked_until,
+        }
+    }
+}
+
+fn lockout_changes(v: &crate::db::login_throttle::LoginLockout) -> serde_json::Value {
+    serde_json::json!({
+        "username": v.username,
+        "address": v.address.map(|v| v.addr().to_string()),
+        "locked_until": v.locked_until,
+    })
+}
+
+/// Returns lockouts of login which have not ended yet, latest first
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Active lockouts", body = [LoginLockoutResponse], content_type = "application/json",
+         headers(
+             ("X-Total-Count" = usize, description = "Total count of lockouts"),
+         )),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed")
+    ),
+    security(("session_cookie" = ["users.read"]), ("authorization_header" = ["users.read"])),
+    tag = "User",
+)]
+#[get("/api/v1/login_lockout")]
+pub async fn login_lockout_list(
+    db: Data<{{db_plugin}}::db::DB>,
+    _user: RequirePermission<UsersRead>,
+) -> actix_web::error::Result<APIList<LoginLockoutResponse>> {
+    let now = chrono::Utc::now();
+    let list = db
+        .pool
+        .with_connection(move |conn| {
+            crate::db::login_throttle::LoginLockout::list_active(conn, now)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let list: Vec<_> = list.into_iter().map(LoginLockoutResponse::from).collect();
+    let count = list.len() as i64;
+    APIList::ok(list, count)
+}
+
+/// Clears lockout together with failed login attempts of its username or address
+#[utoipa::path(
+    params(
+        ("id" = crate::db::login_throttle::LoginLockoutId, Path, description = "Lockout ID"),
+    ),
+    responses(
+        (status = OK, description = "Lockout cleared", body = react_admin::OKResponse, content_type = "application/json"),
+        (status = UNAUTHORIZED, description = "User not logged in"),
+        (status = FORBIDDEN, description = "Action is not allowed"),
+        (status = NOT_FOUND, description = "No such lockout")
+    ),
+    security(("session_cookie" = ["users.write"]), ("authorization_header" = ["users.write"])),
+    tag = "User",
+)]
+#[delete("/api/v1/login_lockout/{id}")]
+pub async fn login_lockout_clear(
+    db: Data<{{db_plugin}}::db::DB>,
+    RequirePermission { user, .. }: RequirePermission<UsersWrite>,
+    id: web::Path<crate::db::login_throttle::LoginLockoutId>,
+    req: HttpRequest,
+) -> actix_web::error::Result<APIObject<react_admin::OKResponse>> {
+    let id = id.into_inner();
+    let audit = user_core::audit::AuditContext::new(Some(&user.user), &req);
+    let cleared = db
+        .pool
+        .with_transaction(move |conn| {
+            let r = crate::db::login_throttle::LoginLockout::clear(conn, id)?;
+            if let Some(v) = &r {
+                audit.record(
+                    conn,
+                    "clear_login_lockout",
+                    "login_lockout",
+                    Some(id.get()),
+                    lockout_changes(v),
+                )?;
+            }
+            Ok(r)
+        })
+        .await
+        .map_err(|err| {
+            actix_web::error::InternalError::new(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    match cleared {
+        Some(_) => APIObject::ok(react_admin::OKResponse),
+        None => Err(actix_web::error::ErrorNotFound("Lockout not found")),
+    }
 }
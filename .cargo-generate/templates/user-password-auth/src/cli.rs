@@ -0,0 +1,94 @@
+//! Commands of the plugin, `<app> plugin {{project-name}} <COMMAND>`
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use std::io::IsTerminal;
+
+#[derive(Parser)]
+pub struct CommandLine {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Set password of user, read from the first line of stdin, and revoke their sessions. User must change it on the
+    /// next login if `change_after_reset` is set in the plugin config
+    SetPassword {
+        /// Username of password owner
+        username: String,
+    },
+}
+
+/// Reads password from the first line of stdin, prompting for it without echo if stdin is a terminal
+fn read_password() -> Result<secstr::SecUtf8> {
+    let stdin = std::io::stdin();
+    let password = if stdin.is_terminal() {
+        rpassword::prompt_password("New password: ")
+    } else {
+        let mut line = String::new();
+        stdin.read_line(&mut line).map(|_| line)
+    }
+    .map_err(|err| anyhow!("Failed to read password: {err}"))?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err(anyhow!("No password given on stdin"));
+    }
+    Ok(secstr::SecUtf8::from(password))
+}
+
+impl CommandLine {
+    pub async fn run(self, configs_path: &std::path::Path) -> Result<()> {
+        match self.command {
+            Command::SetPassword { username } => {
+                let config: webapp_yaml_config::yaml::Config<crate::config::Config> =
+                    webapp_yaml_config::yaml::Config::new(configs_path, "{{project_name}}")?;
+                let policy = crate::policy::Policy::new(&config)?;
+                let password = read_password()?;
+                let pool = database_pg::Pool::new("{{db-plugin}}", configs_path)?;
+                let (user_password, revoked) = pool
+                    .with_transaction(move |conn| {
+                        let user = user_core::db::user::User::of_username(conn, &username)?;
+                        let must_change = policy.change_after_reset;
+                        let user_password = crate::db::user_password::UserPassword::set(
+                            conn,
+                            &policy,
+                            &user,
+                            &password,
+                            must_change,
+                        )?;
+                        let revoked = user_core::db::user_session::UserSession::revoke_all(
+                            conn, user.id, None,
+                        )?;
+                        let audit = user_core::audit::AuditContext {
+                            actor_id: None,
+                            client_address: None,
+                            request_id: None,
+                        };
+                        audit.record(
+                            conn,
+                            "reset_password",
+                            "user",
+                            Some(user.id.get()),
+                            serde_json::json!({
+                                "must_change": must_change,
+                                "revoked_sessions": revoked.len(),
+                            }),
+                        )?;
+                        Ok((user_password, revoked.len()))
+                    })
+                    .await?;
+                eprintln!(
+                    "Password of user {} set, {revoked} sessions revoked{}",
+                    user_password.user_id,
+                    if user_password.must_change {
+                        ", it must be changed on the next login"
+                    } else {
+                        ""
+                    }
+                );
+                Ok(())
+            }
+        }
+    }
+}
@@ -3,6 +3,187 @@
 
 #[derive(Serialize, Deserialize, StructDoc)]
 pub struct Config {
-    /// Minimum password length
+    /// Minimum password length in characters
+    #[serde(default = "default_min_password_length")]
     pub min_password_length: usize,
+    /// Maximum password length in characters, hashing of very long passwords is slow
+    #[serde(default = "default_max_password_length")]
+    pub max_password_length: usize,
+    /// Character classes password must contain: lowercase, uppercase, digit, symbol
+    #[serde(default)]
+    pub required_character_classes: Vec<CharacterClass>,
+    /// Reject passwords which contain username, case-insensitive
+    #[serde(default = "default_true")]
+    pub reject_username: bool,
+    /// File with breached or common passwords, one per line, which are rejected. It is read on startup
+    #[serde(default)]
+    pub breached_passwords_file: Option<std::path::PathBuf>,
+    /// User must change password set by administrator on the next login
+    #[serde(default = "default_true")]
+    pub change_after_reset: bool,
+    /// Throttling of failed login attempts, enabled by default
+    #[serde(default)]
+    pub throttling: ThrottlingConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_password_length: default_min_password_length(),
+            max_password_length: default_max_password_length(),
+            required_character_classes: Vec::new(),
+            reject_username: true,
+            breached_passwords_file: None,
+            change_after_reset: true,
+            throttling: ThrottlingConfig::default(),
+        }
+    }
+}
+
+fn default_min_password_length() -> usize {
+    8
+}
+
+fn default_max_password_length() -> usize {
+    128
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    /// Any character which is not a letter or digit
+    Symbol,
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+#[serde(default)]
+pub struct ThrottlingConfig {
+    /// Delay and lock out login after failed attempts
+    pub enabled: bool,
+    /// Failed attempts older than this are not counted
+    #[serde(with = "humantime_serde")]
+    pub window: std::time::Duration,
+    /// Limits of failed attempts with the same username, whether such user exists or not
+    pub username: ThrottlingLimits,
+    /// Limits of failed attempts from the same client IP address
+    pub address: ThrottlingLimits,
+    /// How often old failed attempts and ended lockouts are removed, requires jobs processing in the DB plugin config
+    #[serde(with = "humantime_serde")]
+    pub cleanup_interval: std::time::Duration,
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+pub struct ThrottlingLimits {
+    /// Failed attempts which are not delayed
+    pub free_attempts: u32,
+    /// Delay of the next attempt after the first delayed failed attempt, doubled after each next one
+    #[serde(with = "humantime_serde")]
+    pub delay: std::time::Duration,
+    /// Maximum delay of the next attempt
+    #[serde(with = "humantime_serde")]
+    pub max_delay: std::time::Duration,
+    /// Failed attempts within `window` after which login is locked out, 0 to never lock out
+    pub lockout_after: u32,
+    /// How long lockout lasts
+    #[serde(with = "humantime_serde")]
+    pub lockout_duration: std::time::Duration,
+}
+
+impl ThrottlingLimits {
+    /// Delay of the next attempt after `failures` failed attempts
+    pub fn delay(&self, failures: u32) -> std::time::Duration {
+        match failures.checked_sub(self.free_attempts) {
+            None | Some(0) => std::time::Duration::ZERO,
+            Some(v) => self
+                .delay
+                .saturating_mul(2u32.saturating_pow(v - 1))
+                .min(self.max_delay),
+        }
+    }
+
+    /// Whether `failures` failed attempts lock out login
+    pub fn locks_out(&self, failures: u32) -> bool {
+        self.lockout_after > 0 && failures >= self.lockout_after
+    }
+}
+
+impl Default for ThrottlingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window: std::time::Duration::from_secs(15 * 60),
+            username: ThrottlingLimits {
+                free_attempts: 3,
+                delay: std::time::Duration::from_secs(1),
+                max_delay: std::time::Duration::from_secs(30),
+                lockout_after: 10,
+                lockout_duration: std::time::Duration::from_secs(15 * 60),
+            },
+            address: ThrottlingLimits {
+                free_attempts: 10,
+                delay: std::time::Duration::from_secs(1),
+                max_delay: std::time::Duration::from_secs(30),
+                lockout_after: 100,
+                lockout_duration: std::time::Duration::from_secs(15 * 60),
+            },
+            cleanup_interval: std::time::Duration::from_secs(3600),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limits() -> ThrottlingLimits {
+        ThrottlingLimits {
+            free_attempts: 3,
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            lockout_after: 10,
+            lockout_duration: Duration::from_secs(900),
+        }
+    }
+
+    #[test]
+    fn free_attempts_are_not_delayed() {
+        let limits = limits();
+        for failures in 0..=3 {
+            assert_eq!(limits.delay(failures), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn delay_is_doubled_up_to_max() {
+        let limits = limits();
+        let delays: Vec<_> = (4..=10).map(|v| limits.delay(v).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+        assert_eq!(limits.delay(u32::MAX), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn lockout_after_limit() {
+        let limits = limits();
+        assert!(!limits.locks_out(9));
+        assert!(limits.locks_out(10));
+        assert!(limits.locks_out(11));
+    }
+
+    #[test]
+    fn zero_lockout_after_never_locks_out() {
+        let limits = ThrottlingLimits {
+            lockout_after: 0,
+            ..limits()
+        };
+        assert!(!limits.locks_out(0));
+        assert!(!limits.locks_out(u32::MAX));
+    }
 }
@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Result};
+use diesel::dsl::{count_star, max};
+use diesel::prelude::*;
+use {{db_plugin}}::schema::{login_failure, login_lockout};
+
+database_pg::make_id!(LoginLockoutId);
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
+#[diesel(table_name = login_lockout)]
+pub struct LoginLockout {
+    pub id: LoginLockoutId,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub username: Option<String>,
+    pub address: Option<ipnet::IpNet>,
+    pub locked_until: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = login_lockout)]
+struct LoginLockoutNew<'a> {
+    create_date: chrono::DateTime<chrono::Utc>,
+    username: Option<&'a str>,
+    address: Option<ipnet::IpNet>,
+    locked_until: chrono::DateTime<chrono::Utc>,
+}
+
+/// What failed attempts are counted by
+#[derive(Clone, Copy)]
+enum Subject<'a> {
+    Username(&'a str),
+    Address(ipnet::IpNet),
+}
+
+impl<'a> Subject<'a> {
+    fn both(
+        config: &'a crate::config::ThrottlingConfig,
+        username: &'a str,
+        address: ipnet::IpNet,
+    ) -> [(Self, &'a crate::config::ThrottlingLimits); 2] {
+        [
+            (Self::Username(username), &config.username),
+            (Self::Address(address), &config.address),
+        ]
+    }
+
+    /// Number of failed attempts since `since` and date of the last one
+    fn failures(
+        self,
+        db: &mut diesel::PgConnection,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(u32, Option<chrono::DateTime<chrono::Utc>>)> {
+        let query = login_failure::table
+            .filter(login_failure::create_date.gt(since))
+            .into_boxed();
+        let query = match self {
+            Self::Username(v) => query.filter(login_failure::username.eq(v)),
+            Self::Address(v) => query.filter(login_failure::address.eq(v)),
+        };
+        let (count, last) = query
+            .select((count_star(), max(login_failure::create_date)))
+            .get_result::<(i64, Option<chrono::DateTime<chrono::Utc>>)>(db)
+            .map_err(|err| anyhow!("Failed to count failed login attempts: {err}"))?;
+        Ok((u32::try_from(count).unwrap_or(u32::MAX), last))
+    }
+
+    fn lock_out(
+        self,
+        db: &mut diesel::PgConnection,
+        locked_until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<LoginLockout> {
+        let (username, address) = match self {
+            Self::Username(v) => (Some(v), None),
+            Self::Address(v) => (None, Some(v)),
+        };
+        diesel::insert_into(login_lockout::table)
+            .values(LoginLockoutNew {
+                create_date: chrono::Utc::now(),
+                username,
+                address,
+                locked_until,
+            })
+            .get_result(db)
+            .map_err(|err| anyhow!("Failed to lock out login: {err}"))
+    }
+}
+
+fn window_start(
+    config: &crate::config::ThrottlingConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<chrono::DateTime<chrono::Utc>> {
+    Ok(now - chrono::Duration::from_std(config.window)?)
+}
+
+/// Serializes login attempts of `username` and from `address` until the end of transaction, so parallel attempts
+/// are checked against failures recorded by each other
+pub fn lock(db: &mut diesel::PgConnection, username: &str, address: ipnet::IpNet) -> Result<()> {
+    // always in the same order, so transactions do not deadlock
+    for key in [
+        format!("login_throttle username {username}"),
+        format!("login_throttle address {address}"),
+    ] {
+        let _ = diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind::<diesel::sql_types::Text, _>(key)
+            .execute(db)
+            .map_err(|err| anyhow!("Failed to lock login attempts: {err}"))?;
+    }
+    Ok(())
+}
+
+/// When the next login attempt of `username` from `address` is allowed, `None` if it is allowed now
+pub fn retry_at(
+    db: &mut diesel::PgConnection,
+    config: &crate::config::ThrottlingConfig,
+    username: &str,
+    address: ipnet::IpNet,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+    let mut r = login_lockout::table
+        .filter(
+            login_lockout::username
+                .eq(username)
+                .or(login_lockout::address.eq(address)),
+        )
+        .filter(login_lockout::locked_until.gt(now))
+        .select(max(login_lockout::locked_until))
+        .get_result::<Option<chrono::DateTime<chrono::Utc>>>(db)
+        .map_err(|err| anyhow!("Failed to check login lockouts: {err}"))?;
+    let since = window_start(config, now)?;
+    for (subject, limits) in Subject::both(config, username, address) {
+        if let (count, Some(last)) = subject.failures(db, since)? {
+            let allowed = last + chrono::Duration::from_std(limits.delay(count))?;
+            if allowed > now {
+                r = r.max(Some(allowed));
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// Records failed login attempt and locks out username or address which have too many of them. Returns new lockouts
+pub fn record_failure(
+    db: &mut diesel::PgConnection,
+    config: &crate::config::ThrottlingConfig,
+    username: &str,
+    address: ipnet::IpNet,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<LoginLockout>> {
+    let _ = diesel::insert_into(login_failure::table)
+        .values((
+            login_failure::create_date.eq(now),
+            login_failure::username.eq(username),
+            login_failure::address.eq(address),
+        ))
+        .execute(db)
+        .map_err(|err| anyhow!("Failed to record failed login attempt: {err}"))?;
+    let since = window_start(config, now)?;
+    let mut r = Vec::new();
+    for (subject, limits) in Subject::both(config, username, address) {
+        let (count, _) = subject.failures(db, since)?;
+        if limits.locks_out(count) {
+            let locked_until = now + chrono::Duration::from_std(limits.lockout_duration)?;
+            r.push(subject.lock_out(db, locked_until)?);
+        }
+    }
+    Ok(r)
+}
+
+/// Forgets failed attempts of username after successful login
+pub fn clear_failures(db: &mut diesel::PgConnection, username: &str) -> Result<()> {
+    let _ = diesel::delete(login_failure::table.filter(login_failure::username.eq(username)))
+        .execute(db)
+        .map_err(|err| anyhow!("Failed to clear failed login attempts: {err}"))?;
+    Ok(())
+}
+
+impl LoginLockout {
+    /// Locked username or address, for logs
+    pub fn subject(&self) -> String {
+        match (&self.username, self.address) {
+            (Some(v), _) => format!("username {v:?}"),
+            (None, Some(v)) => format!("address {}", v.addr()),
+            (None, None) => "nothing".to_owned(),
+        }
+    }
+
+    /// Lockouts which have not ended yet, latest first
+    pub fn list_active(
+        db: &mut diesel::PgConnection,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<LoginLockout>> {
+        login_lockout::table
+            .filter(login_lockout::locked_until.gt(now))
+            .order(login_lockout::create_date.desc())
+            .load(db)
+            .map_err(|err| anyhow!("Failed to list login lockouts: {err}"))
+    }
+
+    /// Removes lockout together with failed attempts of its username or address, so login is not delayed either.
+    /// Returns `None` if there is no such lockout
+    pub fn clear(
+        db: &mut diesel::PgConnection,
+        id: LoginLockoutId,
+    ) -> Result<Option<LoginLockout>> {
+        let r: Option<LoginLockout> = diesel::delete(login_lockout::table.find(id))
+            .get_result(db)
+            .optional()
+            .map_err(|err| anyhow!("Failed to clear login lockout {id}: {err}"))?;
+        if let Some(v) = &r {
+            let failures = diesel::delete(login_failure::table).into_boxed();
+            let failures = match (&v.username, v.address) {
+                (Some(username), _) => failures.filter(login_failure::username.eq(username)),
+                (None, Some(address)) => failures.filter(login_failure::address.eq(address)),
+                (None, None) => return Ok(r),
+            };
+            let _ = failures
+                .execute(db)
+                .map_err(|err| anyhow!("Failed to clear failed login attempts: {err}"))?;
+        }
+        Ok(r)
+    }
+}
+
+/// Removes failed attempts which are not counted anymore and ended lockouts. Returns number of removed rows
+pub fn remove_expired(
+    db: &mut diesel::PgConnection,
+    config: &crate::config::ThrottlingConfig,
+) -> Result<usize> {
+    let now = chrono::Utc::now();
+    let failures = diesel::delete(
+        login_failure::table.filter(login_failure::create_date.le(window_start(config, now)?)),
+    )
+    .execute(db)
+    .map_err(|err| anyhow!("Failed to remove old failed login attempts: {err}"))?;
+    let lockouts = diesel::delete(login_lockout::table.filter(login_lockout::locked_until.le(now)))
+        .execute(db)
+        .map_err(|err| anyhow!("Failed to remove ended login lockouts: {err}"))?;
+    Ok(failures + lockouts)
+}
+
+/// Job removing old failed login attempts and ended lockouts
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct LoginThrottlingCleanup {}
+
+impl database_pg::jobs::Job for LoginThrottlingCleanup {
+    const JOB_TYPE: &'static str = "login_throttling_cleanup";
+}
+
+/// Registers periodic job removing old failed login attempts and ended lockouts
+pub fn start_cleanup(
+    jobs: &'static database_pg::jobs::Registry,
+    config: &crate::config::ThrottlingConfig,
+) -> Result<()> {
+    let job_config = config.clone();
+    jobs.register_periodic(
+        LoginThrottlingCleanup {},
+        config.cleanup_interval,
+        move |conn: &mut diesel::PgConnection, _: LoginThrottlingCleanup| {
+            let removed = remove_expired(conn, &job_config)?;
+            if removed > 0 {
+                tracing::info!("Removed {removed} old failed login attempts and lockouts");
+            }
+            Ok(())
+        },
+    )
+}
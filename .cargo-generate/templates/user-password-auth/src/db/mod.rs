@@ -1 +1,2 @@
+pub mod login_throttle;
 pub mod user_password;
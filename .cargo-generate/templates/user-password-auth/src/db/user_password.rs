@@ -3,6 +3,7 @@
 use database_pg::secstr::SecUtf8;
 use diesel::prelude::*;
 use rand_core::OsRng;
+use std::sync::OnceLock;
 use {{db_plugin}}::schema::user_password;
 
 fn hash_password(password: &secstr::SecUtf8) -> Result<String> {
@@ -14,85 +15,202 @@ fn hash_password(password: &secstr::SecUtf8) -> Result<String> {
     Ok(hash.to_string())
 }
 
+/// Error of password verification when user is unknown, has no password or password is incorrect
+#[derive(Debug)]
+pub struct WrongPassword;
+
+impl std::fmt::Display for WrongPassword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("No such user or password is incorrect")
+    }
+}
+
+impl std::error::Error for WrongPassword {}
+
 fn validate_password(hash: &secstr::SecUtf8, password: &secstr::SecUtf8) -> Result<()> {
     let argon2 = Argon2::default();
     let hash = PasswordHash::new(hash.unsecure())
         .map_err(|err| anyhow!("Failed to parse password hash: {err}"))?;
     argon2
         .verify_password(password.unsecure().as_bytes(), &hash)
-        .map_err(|err| anyhow!("Failed to verify password: {err}"))
+        .map_err(|err| match err {
+            argon2::password_hash::Error::Password => WrongPassword.into(),
+            err => anyhow!("Failed to verify password: {err}"),
+        })
+}
+
+/// Hash with the same parameters as password hashes, verified when there is no password hash to verify
+fn dummy_hash() -> &'static secstr::SecUtf8 {
+    static DUMMY_HASH: OnceLock<secstr::SecUtf8> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        let hash = hash_password(&secstr::SecUtf8::from("dummy password"))
+            .expect("Argon2 with default parameters hashes any password");
+        secstr::SecUtf8::from(hash)
+    })
+}
+
+/// Prepares dummy hash, so the first login of unknown user is not slower than the next ones
+pub fn prepare_dummy_hash() {
+    let _ = dummy_hash();
+}
+
+/// Verifies password against `hash`. Without hash it fails after verifying against dummy hash, so it takes as long as
+/// verification of wrong password
+fn verify_password(hash: Option<&secstr::SecUtf8>, password: &secstr::SecUtf8) -> Result<()> {
+    match hash {
+        Some(hash) => validate_password(hash, password),
+        None => {
+            let _ = validate_password(dummy_hash(), password);
+            Err(WrongPassword.into())
+        }
+    }
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = user_password)]
 pub struct UserPasswordNew {
-    pub user_id: i64,
+    pub user_id: user_core::db::user::UserId,
     pub last_updated_date: chrono::DateTime<chrono::Utc>,
     pub password_hash: SecUtf8,
+    pub must_change: bool,
 }
 
 #[derive(Identifiable, Queryable, PartialEq, Eq, Debug, Clone)]
 #[diesel(table_name = user_password)]
 pub struct UserPassword {
     pub id: i64,
-    pub user_id: i64,
+    pub user_id: user_core::db::user::UserId,
     pub last_updated_date: chrono::DateTime<chrono::Utc>,
     pub password_hash: SecUtf8,
+    /// User must change password on the next login
+    pub must_change: bool,
 }
 
 impl UserPassword {
+    /// Adds password of user which has none. Returns [`react_admin::crud::Invalid`] error if password is not allowed
+    /// by `policy`
     pub fn new(
         db: &mut diesel::PgConnection,
+        policy: &crate::policy::Policy,
         user: &user_core::db::user::User,
         password: &secstr::SecUtf8,
+        must_change: bool,
     ) -> Result<Self> {
+        policy.check(&user.username, password)?;
         let password_hash = hash_password(password)?;
         let r = diesel::insert_into(user_password::dsl::user_password)
             .values(&UserPasswordNew {
                 user_id: user.id,
                 last_updated_date: chrono::Utc::now(),
                 password_hash: SecUtf8::from(password_hash),
+                must_change,
             })
             .get_result(db)
             .map_err(|err| anyhow!("Failed to add user password: {err}"))?;
         Ok(r)
     }
 
-    pub fn update_password(
-        &self,
+    /// Adds or replaces password of user. Returns [`react_admin::crud::Invalid`] error if password is not allowed by
+    /// `policy`
+    pub fn set(
         db: &mut diesel::PgConnection,
-        config: &crate::config::Config,
+        policy: &crate::policy::Policy,
+        user: &user_core::db::user::User,
         password: &secstr::SecUtf8,
-    ) -> Result<()> {
-        if password.unsecure().len() < config.min_password_length {
-            return Err(anyhow!(
-                "Password must be at least {} characters long",
-                config.min_password_length
-            ));
-        }
-        let password_hash = hash_password(password)?;
-        diesel::update(user_password::dsl::user_password.find(self.id))
+        must_change: bool,
+    ) -> Result<Self> {
+        policy.check(&user.username, password)?;
+        let password_hash = SecUtf8::from(hash_password(password)?);
+        let now = chrono::Utc::now();
+        let r = diesel::insert_into(user_password::dsl::user_password)
+            .values(&UserPasswordNew {
+                user_id: user.id,
+                last_updated_date: now,
+                password_hash: password_hash.clone(),
+                must_change,
+            })
+            .on_conflict(user_password::dsl::user_id)
+            .do_update()
             .set((
-                user_password::dsl::last_updated_date.eq(chrono::Utc::now()),
+                user_password::dsl::last_updated_date.eq(now),
                 user_password::dsl::password_hash.eq(password_hash),
+                user_password::dsl::must_change.eq(must_change),
             ))
-            .execute(db)
-            .map_err(|err| anyhow!("Failed to update user password: {err}"))?;
-        Ok(())
+            .get_result(db)
+            .map_err(|err| anyhow!("Failed to set user password: {err}"))?;
+        Ok(r)
     }
 
+    /// Returns [`WrongPassword`] error if `password` is incorrect
     pub fn validate_password(&self, password: &SecUtf8) -> Result<()> {
         validate_password(&self.password_hash, password)
     }
 
+    /// Returns user with `username` and their password if `password` is correct, otherwise [`WrongPassword`] error.
+    /// Unknown users and users without password take as long as users with wrong password, so response time does not
+    /// reveal which usernames exist
+    pub fn authenticate(
+        db: &mut diesel::PgConnection,
+        username: &str,
+        password: &SecUtf8,
+    ) -> Result<(user_core::db::user::User, Self)> {
+        let user = user_core::db::user::User::find_by_username(db, username)?;
+        let user_password = match &user {
+            Some(user) => Self::of_user(db, user)?,
+            None => None,
+        };
+        verify_password(user_password.as_ref().map(|v| &*v.password_hash), password)?;
+        match (user, user_password) {
+            (Some(user), Some(user_password)) => Ok((user, user_password)),
+            _ => Err(WrongPassword.into()),
+        }
+    }
+
+    /// Password of user, `None` if user has no password
     pub fn of_user(
         db: &mut diesel::PgConnection,
         user: &user_core::db::user::User,
-    ) -> Result<Self> {
-        let r = user_password::dsl::user_password
+    ) -> Result<Option<Self>> {
+        user_password::dsl::user_password
             .filter(user_password::dsl::user_id.eq(user.id))
             .get_result(db)
-            .map_err(|err| anyhow!("Failed to get user password: {err}"))?;
-        Ok(r)
+            .optional()
+            .map_err(|err| anyhow!("Failed to get user password: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Median duration of `f`
+    fn median(f: impl Fn()) -> std::time::Duration {
+        let mut durations: Vec<_> = (0..5)
+            .map(|_| {
+                let start = std::time::Instant::now();
+                f();
+                start.elapsed()
+            })
+            .collect();
+        durations.sort();
+        durations[durations.len() / 2]
+    }
+
+    #[test]
+    fn missing_password_takes_as_long_as_wrong_password() {
+        let hash =
+            secstr::SecUtf8::from(hash_password(&secstr::SecUtf8::from("right password")).unwrap());
+        let right = secstr::SecUtf8::from("right password");
+        let wrong = secstr::SecUtf8::from("wrong password");
+        assert!(verify_password(Some(&hash), &right).is_ok());
+        prepare_dummy_hash();
+
+        let wrong_password = median(|| assert!(verify_password(Some(&hash), &wrong).is_err()));
+        let missing_password = median(|| assert!(verify_password(None, &wrong).is_err()));
+        let ratio = wrong_password.as_secs_f64() / missing_password.as_secs_f64();
+        assert!(
+            (0.5..2.0).contains(&ratio),
+            "wrong password: {wrong_password:?}, missing password: {missing_password:?}"
+        );
     }
 }
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Password of existing user, it is stored hashed
+#[derive(Deserialize)]
+pub struct UserPasswordFixture {
+    pub user_id: user_core::db::user::UserId,
+    pub password: webapp_core::secstr::SecUtf8,
+}
+
+impl database_pg::seed::Fixture for UserPasswordFixture {
+    const FIXTURE_TYPE: &'static str = "user_password";
+
+    fn load(self, conn: &mut diesel::PgConnection) -> Result<Option<i64>> {
+        use diesel::prelude::*;
+        use {{db_plugin}}::schema::user;
+
+        let user: user_core::db::user::User = user::table.find(self.user_id).get_result(conn)?;
+        // plugin config is not available when fixtures are loaded, so password is checked by the default policy
+        let password = crate::db::user_password::UserPassword::new(
+            conn,
+            &crate::policy::Policy::default(),
+            &user,
+            &Into::<secstr::SecUtf8>::into(self.password),
+            false,
+        )?;
+        Ok(Some(password.id))
+    }
+}
+
+pub fn register(registry: &mut database_pg::seed::Registry) -> Result<()> {
+    registry.register::<UserPasswordFixture>()
+}
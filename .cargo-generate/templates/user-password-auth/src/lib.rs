@@ -1,7 +1,11 @@
 pub mod api;
+pub mod cli;
 pub mod config;
 mod db;
+pub mod fixtures;
+pub mod policy;
 
+use actix_web::web::Data;
 use anyhow::Result;
 use async_trait::async_trait;
 use structdoc::StructDoc;
@@ -44,16 +48,36 @@ async fn init_plugin(&self) -> Result<Box<dyn Plugin>>
         let plugin = PluginImpl::new(self)?;
         Ok(Box::new(plugin))
     }
+
+    async fn run_command(&self, args: &[String]) -> Result<()> {
+        use clap::Parser;
+        crate::cli::CommandLine::parse_from(args)
+            .run(&self.configs_path)
+            .await
+    }
 }
 
-pub struct PluginImpl {}
+pub struct PluginImpl {
+    config: Data<crate::config::Config>,
+    policy: Data<crate::policy::Policy>,
+}
 
 impl PluginImpl {
-    pub fn new(_metadata: &Metadata) -> Result<Self>
+    pub fn new(metadata: &Metadata) -> Result<Self>
     where
         Self: Sized,
     {
-        Ok(Self {})
+        let config: webapp_yaml_config::yaml::Config<crate::config::Config> =
+            webapp_yaml_config::yaml::Config::new(&metadata.configs_path, metadata.plugin_name())?;
+        let policy = crate::policy::Policy::new(&config)?;
+        crate::db::user_password::prepare_dummy_hash();
+        if config.throttling.enabled {
+            crate::db::login_throttle::start_cleanup(&{{db_plugin}}::db::JOBS, &config.throttling)?;
+        }
+        Ok(Self {
+            config: Data::from(config.config.clone()),
+            policy: Data::new(policy),
+        })
     }
 }
 
@@ -62,12 +86,33 @@ fn webapp_initializer(
         &self,
         service_config: &mut actix_web::web::ServiceConfig,
     ) -> utoipa::openapi::OpenApi {
-        let _ = service_config.service(crate::api::login);
+        let _ = service_config
+            .app_data(self.config.clone())
+            .app_data(self.policy.clone())
+            .service(crate::api::login)
+            .service(crate::api::password_change)
+            .service(crate::api::password_reset)
+            .service(crate::api::login_lockout_list)
+            .service(crate::api::login_lockout_clear);
 
         #[derive(OpenApi)]
         #[openapi(
-            paths(crate::api::login),
-            components(schemas(crate::api::LoginRequest, crate::api::LoginResponse))
+            paths(
+                crate::api::login,
+                crate::api::password_change,
+                crate::api::password_reset,
+                crate::api::login_lockout_list,
+                crate::api::login_lockout_clear
+            ),
+            components(schemas(
+                crate::api::LoginRequest,
+                crate::api::LoginResponse,
+                crate::api::PasswordChangeRequest,
+                crate::api::PasswordResetRequest,
+                crate::api::PasswordChangedResponse,
+                crate::db::login_throttle::LoginLockoutId,
+                crate::api::LoginLockoutResponse
+            ))
         )]
         struct ApiDoc;
 
@@ -0,0 +1,194 @@
+//! Requirements to new passwords, set in the plugin config
+
+use crate::config::CharacterClass;
+use anyhow::{anyhow, Result};
+use react_admin::crud::Invalid;
+use std::collections::HashSet;
+
+impl CharacterClass {
+    fn contains(self, c: char) -> bool {
+        match self {
+            Self::Lowercase => c.is_lowercase(),
+            Self::Uppercase => c.is_uppercase(),
+            Self::Digit => c.is_numeric(),
+            Self::Symbol => !c.is_alphanumeric(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Lowercase => "lowercase letter",
+            Self::Uppercase => "uppercase letter",
+            Self::Digit => "digit",
+            Self::Symbol => "symbol",
+        }
+    }
+}
+
+pub struct Policy {
+    min_length: usize,
+    max_length: usize,
+    required_classes: Vec<CharacterClass>,
+    reject_username: bool,
+    breached: HashSet<String>,
+    /// Whether password set by administrator must be changed on the next login
+    pub change_after_reset: bool,
+}
+
+impl Policy {
+    /// Policy of the config, breached passwords are read from the file
+    pub fn new(config: &crate::config::Config) -> Result<Self> {
+        let breached = match &config.breached_passwords_file {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|err| anyhow!("Failed to read breached passwords from {path:?}: {err}"))?
+                .lines()
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_owned())
+                .collect(),
+            None => HashSet::new(),
+        };
+        Ok(Self::with_breached(config, breached))
+    }
+
+    fn with_breached(config: &crate::config::Config, breached: HashSet<String>) -> Self {
+        Self {
+            min_length: config.min_password_length,
+            max_length: config.max_password_length,
+            required_classes: config.required_character_classes.clone(),
+            reject_username: config.reject_username,
+            breached,
+            change_after_reset: config.change_after_reset,
+        }
+    }
+
+    /// Returns reason why `password` of user `username` is not allowed
+    pub fn check(&self, username: &str, password: &secstr::SecUtf8) -> Result<(), Invalid> {
+        let password = password.unsecure();
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(Invalid(format!(
+                "Password must be at least {} characters long",
+                self.min_length
+            )));
+        }
+        if length > self.max_length {
+            return Err(Invalid(format!(
+                "Password must be at most {} characters long",
+                self.max_length
+            )));
+        }
+        if let Some(class) = self
+            .required_classes
+            .iter()
+            .find(|class| !password.chars().any(|c| class.contains(c)))
+        {
+            return Err(Invalid(format!(
+                "Password must contain at least one {}",
+                class.name()
+            )));
+        }
+        if self.reject_username
+            && !username.is_empty()
+            && password.to_lowercase().contains(&username.to_lowercase())
+        {
+            return Err(Invalid("Password must not contain username".to_owned()));
+        }
+        if self.breached.contains(password) {
+            return Err(Invalid(
+                "Password is known from breaches, choose another one".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Policy {
+    /// Policy of default config, without breached passwords
+    fn default() -> Self {
+        Self::with_breached(&crate::config::Config::default(), HashSet::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn check(policy: &Policy, username: &str, password: &str) -> Result<(), String> {
+        policy
+            .check(username, &secstr::SecUtf8::from(password))
+            .map_err(|err| err.0)
+    }
+
+    fn policy(config: Config) -> Policy {
+        Policy::with_breached(&config, HashSet::new())
+    }
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        let policy = policy(Config {
+            min_password_length: 4,
+            max_password_length: 6,
+            ..Config::default()
+        });
+        assert_eq!(
+            check(&policy, "", "äöü"),
+            Err("Password must be at least 4 characters long".to_owned())
+        );
+        assert_eq!(check(&policy, "", "äöüß"), Ok(()));
+        assert_eq!(check(&policy, "", "äöüßäö"), Ok(()));
+        assert_eq!(
+            check(&policy, "", "äöüßäöü"),
+            Err("Password must be at most 6 characters long".to_owned())
+        );
+    }
+
+    #[test]
+    fn required_classes_are_checked() {
+        use crate::config::CharacterClass::*;
+
+        for (class, without, with, name) in [
+            (Lowercase, "ABCD1234", "ABCd1234", "lowercase letter"),
+            (Uppercase, "abcd1234", "abcD1234", "uppercase letter"),
+            (Digit, "abcdefgh", "abcdefg8", "digit"),
+            (Symbol, "abcd1234", "abcd 234", "symbol"),
+        ] {
+            let policy = policy(Config {
+                required_character_classes: vec![class],
+                ..Config::default()
+            });
+            assert_eq!(
+                check(&policy, "", without),
+                Err(format!("Password must contain at least one {name}"))
+            );
+            assert_eq!(check(&policy, "", with), Ok(()));
+        }
+    }
+
+    #[test]
+    fn username_is_rejected_case_insensitively() {
+        let rejecting = Policy::default();
+        assert_eq!(
+            check(&rejecting, "Admin", "my-aDMIN-password"),
+            Err("Password must not contain username".to_owned())
+        );
+        assert_eq!(check(&rejecting, "Admin", "my-secret-password"), Ok(()));
+
+        let allowing = policy(Config {
+            reject_username: false,
+            ..Config::default()
+        });
+        assert_eq!(check(&allowing, "Admin", "my-aDMIN-password"), Ok(()));
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected() {
+        let policy =
+            Policy::with_breached(&Config::default(), HashSet::from(["password1".to_owned()]));
+        assert_eq!(
+            check(&policy, "", "password1"),
+            Err("Password is known from breaches, choose another one".to_owned())
+        );
+        assert_eq!(check(&policy, "", "Password1"), Ok(()));
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+
+/// Operations on background jobs queue
+#[derive(Subcommand)]
+pub enum CommandJobs {
+    /// List jobs in order of their planned execution
+    List {
+        /// Show only jobs with this status: pending or dead
+        #[clap(short, long)]
+        status: Option<database_pg::jobs::Status>,
+        /// Maximum number of jobs to show
+        #[clap(short, long, default_value_t = 100)]
+        limit: i64,
+    },
+    /// Show job details including payload and last error
+    Show {
+        /// Job ID
+        id: database_pg::jobs::JobId,
+    },
+    /// Move dead job back to the queue
+    Requeue {
+        /// Job ID. If not set, all dead jobs are requeued
+        id: Option<database_pg::jobs::JobId>,
+    },
+}
+
+/// Operations on database
+#[derive(Subcommand)]
+pub enum CommandDb {
+    /// Operations on background jobs queue
+    #[command(subcommand)]
+    Jobs(CommandJobs),
+    /// Load fixtures from YAML or JSON file in one transaction
+    Seed {
+        /// Path to fixtures file
+        file: std::path::PathBuf,
+    },
+    /// Re-encrypt values of encrypted column made with old keys using the current key
+    Reencrypt {
+        /// Table name
+        table: String,
+        /// Encrypted column name
+        column: String,
+        /// Primary key column name
+        #[clap(long, default_value = "id")]
+        id_column: String,
+    },
+}
+
+#[derive(Args)]
+pub struct Db {
+    /// Name of DB plugin to operate on
+    #[clap(short, long)]
+    db: String,
+    /// Command to run
+    #[clap(subcommand)]
+    command: CommandDb,
+}
+
+impl Db {
+    fn pool(
+        &self,
+        configs_path: &std::path::Path,
+        plugins_meta: &[Box<dyn webapp_core::plugin::PluginMetadata>],
+    ) -> Result<database_pg::Pool> {
+        let plugin = plugins_meta
+            .iter()
+            .find(|plugin| plugin.plugin_name() == self.db)
+            .ok_or_else(|| anyhow!("No plugin with name {:?} registered", self.db))?;
+        database_pg::Pool::new(plugin.plugin_name(), configs_path)
+    }
+
+    async fn jobs(&self, pool: database_pg::Pool, command: &CommandJobs) -> Result<()> {
+        match *command {
+            CommandJobs::List { status, limit } => {
+                let list = pool
+                    .with_connection(move |conn| database_pg::jobs::list(conn, status, limit))
+                    .await?;
+                for job in list {
+                    println!(
+                        "{}\t{}\t{}\t{}/{}\t{}",
+                        job.id,
+                        job.job_type,
+                        job.status,
+                        job.attempts,
+                        job.max_attempts,
+                        job.run_at
+                    )
+                }
+                Ok(())
+            }
+            CommandJobs::Show { id } => {
+                let job = pool
+                    .with_connection(move |conn| database_pg::jobs::get(conn, id))
+                    .await?;
+                println!("ID: {}", job.id);
+                println!("Type: {}", job.job_type);
+                println!("Status: {}", job.status);
+                println!("Created: {}", job.create_date);
+                println!("Run at: {}", job.run_at);
+                println!("Attempts: {}/{}", job.attempts, job.max_attempts);
+                if let Some(date) = job.last_attempt_date {
+                    println!("Last attempt: {date}");
+                }
+                if let Some(error) = job.last_error {
+                    println!("Last error: {error}");
+                }
+                println!("Payload: {}", job.payload);
+                Ok(())
+            }
+            CommandJobs::Requeue { id: Some(id) } => {
+                pool.with_transaction(move |conn| database_pg::jobs::requeue(conn, id))
+                    .await?;
+                println!("Job {id} requeued");
+                Ok(())
+            }
+            CommandJobs::Requeue { id: None } => {
+                let count = pool
+                    .with_transaction(database_pg::jobs::requeue_dead)
+                    .await?;
+                println!("{count} dead jobs requeued");
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn run(
+        &self,
+        configs_path: &std::path::Path,
+        plugins_meta: &[Box<dyn webapp_core::plugin::PluginMetadata>],
+    ) -> Result<()> {
+        let pool = self.pool(configs_path, plugins_meta)?;
+        match &self.command {
+            CommandDb::Jobs(command) => self.jobs(pool, command).await,
+            CommandDb::Seed { file } => {
+                let registry = crate::plugins::fixtures()?;
+                let rows = database_pg::seed::read(file)?;
+                let count = pool
+                    .with_transaction(move |conn| registry.load(conn, rows))
+                    .await?;
+                println!("{count} fixtures loaded");
+                Ok(())
+            }
+            CommandDb::Reencrypt {
+                table,
+                column,
+                id_column,
+            } => {
+                let keyring = pool.keyring()?;
+                let (table, column, id_column) = (table.clone(), column.clone(), id_column.clone());
+                let count = pool
+                    .with_transaction(move |conn| {
+                        database_pg::crypto::reencrypt(conn, &keyring, &table, &id_column, &column)
+                    })
+                    .await?;
+                println!("{count} values re-encrypted");
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{Parser, Subcommand};
 use std::process::exit;
 
+mod db;
 mod plugins;
+mod typescript;
 mod webapp_run;
 
 const CONFIGS_DEFAULT_PATH: &str = "/etc/{{project-name}}/";
@@ -24,6 +26,18 @@ enum CommandLine {
     Config(CommandConfig),
     /// Run web application
     Run(crate::webapp_run::Run),
+    /// Operations on database
+    Db(crate::db::Db),
+    /// Generate TypeScript sources for react-admin frontend
+    Typescript(crate::typescript::Typescript),
+    /// Run command of plugin, see `plugin <NAME> help`
+    Plugin {
+        /// Plugin name
+        name: String,
+        /// Command and its arguments
+        #[clap(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
 }
 
 /// Application command line
@@ -102,6 +116,25 @@ async fn run_command(&self) -> Result<()> {
                 )
                 .await
             }
+            CommandLine::Db(v) => {
+                v.run(
+                    std::path::Path::new(&self.command_line.configs_path),
+                    &self.plugins,
+                )
+                .await
+            }
+            CommandLine::Typescript(v) => v.run(&self.plugins).await,
+            CommandLine::Plugin { name, args } => {
+                let plugin = self
+                    .plugins
+                    .iter()
+                    .find(|v| v.plugin_name() == name)
+                    .ok_or_else(|| anyhow!("No plugin with name {name:?} registered"))?;
+                let args: Vec<_> = std::iter::once(format!("plugin {name}"))
+                    .chain(args.iter().cloned())
+                    .collect();
+                plugin.run_command(&args).await
+            }
         }
     }
 
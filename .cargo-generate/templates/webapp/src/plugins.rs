@@ -11,6 +11,15 @@ fn list(configs_path: &std::path::Path) -> Result<Vec<Box<dyn PluginMetadata>>>
     Ok(r)
 }
 
+/// Fixture types which can be loaded with `db seed` command
+pub fn fixtures() -> Result<database_pg::seed::Registry> {
+    #[allow(unused_mut)]
+    let mut r = database_pg::seed::Registry::new();
+    // user_core::fixtures::register(&mut r)?;
+    // user_password_auth::fixtures::register(&mut r)?;
+    Ok(r)
+}
+
 pub fn register(configs_path: &std::path::Path) -> Result<Vec<Box<dyn PluginMetadata>>> {
     let list = list(configs_path)?;
     let mut names = HashSet::new();
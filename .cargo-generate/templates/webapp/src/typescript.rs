@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::sync::{Arc, Mutex};
+
+/// Generate TypeScript types, react-admin dataProvider and resource components from OpenAPI documentation. Plugins
+/// are initialized only for documentation: configs and DB must be available, but migrations and jobs do not run
+#[derive(Args)]
+pub struct Typescript {
+    /// Directory to write generated files to
+    #[clap(short, long, default_value = "src/generated")]
+    output: std::path::PathBuf,
+}
+
+impl Typescript {
+    pub async fn run(
+        &self,
+        plugins_meta: &[Box<dyn webapp_core::plugin::PluginMetadata>],
+    ) -> Result<()> {
+        let mut plugins = Vec::new();
+        for plugin_meta in plugins_meta {
+            if plugin_meta.is_core() {
+                continue;
+            }
+
+            let plugin = plugin_meta.init_documentation().await?;
+            plugins.push(Arc::new(Mutex::new(plugin)))
+        }
+
+        let openapi = webapp_core::openapi(&plugins);
+        std::fs::create_dir_all(&self.output)
+            .with_context(|| format!("Failed to create directory {:?}", self.output))?;
+        for file in react_admin::typescript::generate(&openapi)? {
+            let path = self.output.join(file.name);
+            std::fs::write(&path, file.content)
+                .with_context(|| format!("Failed to write {path:?}"))?;
+            println!("{}", path.display());
+        }
+        Ok(())
+    }
+}
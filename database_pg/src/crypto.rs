@@ -0,0 +1,481 @@
+//! Encrypted and hashed columns.
+//!
+//! Values are encrypted with XChaCha20-Poly1305. Every encrypted value and keyed hash is prefixed with the ID of the
+//! key it was made with, so keys can be rotated: add new key, make it current and re-encrypt old values with
+//! [`reencrypt`]. Keyed hashes cannot be recomputed without the original value, so the old key must be kept while
+//! there are hashes made with it.
+//!
+//! Encrypted values are bound to their table and column, which are authenticated along with the header, so a value
+//! copied to another column fails to decrypt.
+
+use anyhow::{anyhow, bail, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use diesel::deserialize::FromSql;
+use diesel::pg::Pg;
+use diesel::sql_types::Bytea;
+use diesel::{deserialize, serialize, AsExpression, FromSqlRow};
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+use structdoc::StructDoc;
+
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 5;
+const NONCE_LEN: usize = 24;
+
+#[derive(Serialize, Deserialize, StructDoc)]
+pub struct Key {
+    /// Key ID. It is stored along with every encrypted value and must never be reused for another key
+    pub id: u32,
+    /// Key material. Use a long random string, at least 32 characters
+    pub secret: webapp_yaml_config::secret::Secret,
+}
+
+#[derive(Serialize, Deserialize, StructDoc)]
+pub struct Config {
+    /// ID of the key used to encrypt and hash new values
+    pub current_key: u32,
+    /// All known keys. Old keys are required to decrypt values until they are re-encrypted
+    pub keys: Vec<Key>,
+}
+
+struct KeyMaterial {
+    id: u32,
+    cipher: XChaCha20Poly1305,
+    hash_key: [u8; 32],
+}
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+/// Keys loaded from config
+pub struct Keyring {
+    current: usize,
+    keys: Vec<KeyMaterial>,
+}
+
+fn header(key_id: u32) -> [u8; HEADER_LEN] {
+    let id = key_id.to_be_bytes();
+    [FORMAT_VERSION, id[0], id[1], id[2], id[3]]
+}
+
+/// Associated data of encrypted value: its header and column
+fn aad(header: &[u8], table: &str, column: &str) -> Vec<u8> {
+    let mut r = header.to_vec();
+    r.extend_from_slice(table.as_bytes());
+    r.push(0);
+    r.extend_from_slice(column.as_bytes());
+    r
+}
+
+fn parse_header(value: &[u8]) -> Result<(u32, &[u8])> {
+    if value.len() < HEADER_LEN || value[0] != FORMAT_VERSION {
+        bail!("Unsupported encrypted value format")
+    }
+    let id = u32::from_be_bytes([value[1], value[2], value[3], value[4]]);
+    Ok((id, &value[HEADER_LEN..]))
+}
+
+impl Keyring {
+    pub fn new(config: &Config) -> Result<Self> {
+        let mut keys = Vec::with_capacity(config.keys.len());
+        for key in &config.keys {
+            if keys.iter().any(|v: &KeyMaterial| v.id == key.id) {
+                bail!("Encryption key with ID {} defined twice", key.id)
+            }
+            let secret = key.secret.unsecure()?;
+            let hkdf = hkdf::Hkdf::<sha2::Sha256>::new(None, secret.as_bytes());
+            let mut cipher_key = [0u8; 32];
+            let mut hash_key = [0u8; 32];
+            hkdf.expand(b"database_pg encryption", &mut cipher_key)
+                .and_then(|_| hkdf.expand(b"database_pg keyed hash", &mut hash_key))
+                .map_err(|err| anyhow!("Failed to derive key {}: {err}", key.id))?;
+            keys.push(KeyMaterial {
+                id: key.id,
+                cipher: XChaCha20Poly1305::new(&cipher_key.into()),
+                hash_key,
+            })
+        }
+        let current = keys
+            .iter()
+            .position(|v| v.id == config.current_key)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Current encryption key {} is not defined",
+                    config.current_key
+                )
+            })?;
+        Ok(Self { current, keys })
+    }
+
+    fn key(&self, id: u32) -> Result<&KeyMaterial> {
+        self.keys
+            .iter()
+            .find(|v| v.id == id)
+            .ok_or_else(|| anyhow!("Unknown encryption key {id}"))
+    }
+
+    pub fn current_key_id(&self) -> u32 {
+        self.keys[self.current].id
+    }
+
+    fn encrypt(&self, table: &str, column: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let key = &self.keys[self.current];
+        let header = header(key.id);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext,
+            aad: &aad(&header, table, column),
+        };
+        let ciphertext = key
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|err| anyhow!("Failed to encrypt value: {err}"))?;
+        let mut r = Vec::with_capacity(HEADER_LEN + NONCE_LEN + ciphertext.len());
+        r.extend_from_slice(&header);
+        r.extend_from_slice(&nonce);
+        r.extend_from_slice(&ciphertext);
+        Ok(r)
+    }
+
+    fn decrypt(&self, table: &str, column: &str, value: &[u8]) -> Result<Vec<u8>> {
+        let (key_id, rest) = parse_header(value)?;
+        if rest.len() < NONCE_LEN {
+            bail!("Encrypted value is too short")
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &aad(&value[..HEADER_LEN], table, column),
+        };
+        self.key(key_id)?
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|err| anyhow!("Failed to decrypt value: {err}"))
+    }
+
+    /// Encrypts value of `table.column`, it can be decrypted only as value of the same column
+    pub fn encrypt_text(
+        &self,
+        table: &str,
+        column: &str,
+        value: &secstr::SecUtf8,
+    ) -> Result<EncryptedText> {
+        self.encrypt(table, column, value.unsecure().as_bytes())
+            .map(EncryptedText)
+    }
+
+    pub fn decrypt_text(
+        &self,
+        table: &str,
+        column: &str,
+        value: &EncryptedText,
+    ) -> Result<secstr::SecUtf8> {
+        let v = String::from_utf8(self.decrypt(table, column, &value.0)?)
+            .map_err(|_| anyhow!("Decrypted value is not valid UTF-8"))?;
+        Ok(secstr::SecUtf8::from(v))
+    }
+
+    /// Encrypts value of `table.column`, it can be decrypted only as value of the same column
+    pub fn encrypt_bytes(
+        &self,
+        table: &str,
+        column: &str,
+        value: &secstr::SecVec<u8>,
+    ) -> Result<EncryptedBytes> {
+        self.encrypt(table, column, value.unsecure())
+            .map(EncryptedBytes)
+    }
+
+    pub fn decrypt_bytes(
+        &self,
+        table: &str,
+        column: &str,
+        value: &EncryptedBytes,
+    ) -> Result<secstr::SecVec<u8>> {
+        self.decrypt(table, column, &value.0)
+            .map(secstr::SecVec::from)
+    }
+
+    fn hash_with(key: &KeyMaterial, value: &[u8]) -> KeyedHash {
+        // this unwrap never fails, HMAC accepts keys of any length
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&key.hash_key).unwrap();
+        mac.update(value);
+        let mut r = header(key.id).to_vec();
+        r.extend_from_slice(&mac.finalize().into_bytes());
+        KeyedHash(r)
+    }
+
+    /// Deterministic keyed hash of the value made with the current key. Use it to store values which must be looked
+    /// up, but must not be stored, like session tokens
+    pub fn hash(&self, value: &[u8]) -> KeyedHash {
+        Self::hash_with(&self.keys[self.current], value)
+    }
+
+    /// Secret for `context` derived from the current key, e.g. to sign short-lived tokens. It changes when the current
+    /// key is changed
+    pub fn derive_secret(&self, context: &str) -> secstr::SecVec<u8> {
+        let hash = self.hash(format!("derived secret: {context}").as_bytes());
+        secstr::SecVec::from(hash.0[HEADER_LEN..].to_vec())
+    }
+
+    /// Hashes of the value made with all known keys. Look up with `column.eq_any(keyring.hash_candidates(value))` to
+    /// find values hashed with old keys too
+    pub fn hash_candidates(&self, value: &[u8]) -> Vec<KeyedHash> {
+        self.keys
+            .iter()
+            .map(|key| Self::hash_with(key, value))
+            .collect()
+    }
+
+    /// Re-encrypts value of `table.column` with the current key. Returns `None` if value is already encrypted with it
+    fn reencrypt(&self, table: &str, column: &str, value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let (key_id, _) = parse_header(value)?;
+        if key_id == self.current_key_id() {
+            return Ok(None);
+        }
+        let plaintext = self.decrypt(table, column, value)?;
+        self.encrypt(table, column, &plaintext).map(Some)
+    }
+}
+
+macro_rules! bytea_newtype {
+    ($name:ident) => {
+        impl $name {
+            /// ID of the key the value was made with
+            pub fn key_id(&self) -> Result<u32> {
+                parse_header(&self.0).map(|(id, _)| id)
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}(***)", stringify!($name))
+            }
+        }
+
+        impl serialize::ToSql<Bytea, Pg> for $name {
+            fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, Pg>) -> serialize::Result {
+                <[u8] as serialize::ToSql<Bytea, Pg>>::to_sql(&self.0, out)
+            }
+        }
+
+        impl FromSql<Bytea, Pg> for $name {
+            fn from_sql(
+                bytes: <Pg as diesel::backend::Backend>::RawValue<'_>,
+            ) -> deserialize::Result<Self> {
+                <Vec<u8> as FromSql<Bytea, Pg>>::from_sql(bytes).map($name)
+            }
+        }
+    };
+}
+
+/// Encrypted UTF-8 string, stored as `bytea`. Use [`Keyring::encrypt_text`] and [`Keyring::decrypt_text`]
+#[derive(PartialEq, Eq, Clone, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Bytea)]
+pub struct EncryptedText(Vec<u8>);
+
+bytea_newtype!(EncryptedText);
+
+/// Encrypted binary data, stored as `bytea`. Use [`Keyring::encrypt_bytes`] and [`Keyring::decrypt_bytes`]
+#[derive(PartialEq, Eq, Clone, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Bytea)]
+pub struct EncryptedBytes(Vec<u8>);
+
+bytea_newtype!(EncryptedBytes);
+
+/// Deterministic keyed hash (HMAC-SHA256), stored as `bytea`. See [`Keyring::hash`]
+#[derive(PartialEq, Eq, Clone, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Bytea)]
+pub struct KeyedHash(Vec<u8>);
+
+bytea_newtype!(KeyedHash);
+
+pub(crate) fn load_keyring(
+    config: &webapp_yaml_config::yaml::Config<crate::Config>,
+) -> Result<Option<std::sync::Arc<Keyring>>> {
+    config
+        .encryption
+        .as_ref()
+        .map(|v| Keyring::new(v).map(std::sync::Arc::new))
+        .transpose()
+}
+
+fn check_identifier(name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .next()
+        .map(|c| c.is_ascii_lowercase() || c == '_')
+        .unwrap_or(false)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid {
+        bail!("Invalid SQL identifier {name:?}")
+    }
+    Ok(())
+}
+
+#[derive(diesel::QueryableByName)]
+struct EncryptedRow {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    id: i64,
+    #[diesel(sql_type = Bytea)]
+    value: Vec<u8>,
+}
+
+/// Re-encrypts all values of `table.column` made with old keys using the current key. Returns number of updated rows
+pub fn reencrypt(
+    conn: &mut diesel::PgConnection,
+    keyring: &Keyring,
+    table: &str,
+    id_column: &str,
+    column: &str,
+) -> Result<usize> {
+    use diesel::RunQueryDsl;
+
+    check_identifier(table)?;
+    check_identifier(id_column)?;
+    check_identifier(column)?;
+
+    let rows: Vec<EncryptedRow> = diesel::sql_query(format!(
+        r#"select "{id_column}" as id, "{column}" as value from "{table}" where "{column}" is not null for update"#
+    ))
+    .load(conn)
+    .map_err(|err| anyhow!("Failed to load {table}.{column}: {err}"))?;
+
+    let mut updated = 0;
+    for row in rows {
+        let value = match keyring
+            .reencrypt(table, column, &row.value)
+            .map_err(|err| {
+                anyhow!(
+                    "Failed to re-encrypt {table}.{column} of row {}: {err}",
+                    row.id
+                )
+            })? {
+            Some(v) => v,
+            None => continue,
+        };
+        diesel::sql_query(format!(
+            r#"update "{table}" set "{column}" = $1 where "{id_column}" = $2"#
+        ))
+        .bind::<Bytea, _>(value)
+        .bind::<diesel::sql_types::BigInt, _>(row.id)
+        .execute(conn)
+        .map_err(|err| anyhow!("Failed to update {table}.{column} of row {}: {err}", row.id))?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring(keys: &[(u32, &str)], current_key: u32) -> Keyring {
+        Keyring::new(&Config {
+            current_key,
+            keys: keys
+                .iter()
+                .map(|(id, secret)| Key {
+                    id: *id,
+                    secret: webapp_yaml_config::secret::Secret::String(
+                        secstr::SecUtf8::from(*secret).into(),
+                    ),
+                })
+                .collect(),
+        })
+        .unwrap()
+    }
+
+    fn secret(value: &str) -> secstr::SecUtf8 {
+        secstr::SecUtf8::from(value)
+    }
+
+    #[test]
+    fn decrypted_value_is_encrypted_one() {
+        let keyring = keyring(&[(1, "first key")], 1);
+        let text = keyring
+            .encrypt_text("user", "note", &secret("text"))
+            .unwrap();
+        assert_eq!(
+            keyring.decrypt_text("user", "note", &text).unwrap(),
+            secret("text")
+        );
+        let bytes = secstr::SecVec::from(vec![0, 1, 2]);
+        let encrypted = keyring.encrypt_bytes("user", "note", &bytes).unwrap();
+        assert_eq!(
+            keyring.decrypt_bytes("user", "note", &encrypted).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn tampered_value_is_not_decrypted() {
+        let keyring = keyring(&[(1, "first key")], 1);
+        let value = keyring
+            .encrypt_text("user", "note", &secret("text"))
+            .unwrap();
+        for i in HEADER_LEN..value.0.len() {
+            let mut tampered = value.clone();
+            tampered.0[i] ^= 1;
+            assert!(keyring.decrypt_text("user", "note", &tampered).is_err());
+        }
+        let mut truncated = value.clone();
+        let _ = truncated.0.pop();
+        assert!(keyring.decrypt_text("user", "note", &truncated).is_err());
+    }
+
+    #[test]
+    fn value_is_not_decrypted_with_wrong_key() {
+        let value = keyring(&[(1, "first key")], 1)
+            .encrypt_text("user", "note", &secret("text"))
+            .unwrap();
+        let other = keyring(&[(1, "other key")], 1);
+        assert!(other.decrypt_text("user", "note", &value).is_err());
+        let unknown = keyring(&[(2, "first key")], 2);
+        assert!(unknown.decrypt_text("user", "note", &value).is_err());
+    }
+
+    #[test]
+    fn value_is_not_decrypted_in_other_column() {
+        let keyring = keyring(&[(1, "first key")], 1);
+        let value = keyring
+            .encrypt_text("user", "note", &secret("text"))
+            .unwrap();
+        assert!(keyring.decrypt_text("user", "person", &value).is_err());
+        assert!(keyring.decrypt_text("role", "note", &value).is_err());
+        assert!(keyring.decrypt_text("usern", "ote", &value).is_err());
+    }
+
+    #[test]
+    fn value_is_reencrypted_with_current_key() {
+        let old = keyring(&[(1, "first key")], 1);
+        let value = old.encrypt_text("user", "note", &secret("text")).unwrap();
+        let rotated = keyring(&[(1, "first key"), (2, "second key")], 2);
+        let reencrypted = EncryptedText(
+            rotated
+                .reencrypt("user", "note", &value.0)
+                .unwrap()
+                .unwrap(),
+        );
+        assert_eq!(reencrypted.key_id().unwrap(), 2);
+        assert_eq!(
+            rotated.decrypt_text("user", "note", &reencrypted).unwrap(),
+            secret("text")
+        );
+        assert!(rotated
+            .reencrypt("user", "note", &reencrypted.0)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn hashes_of_old_keys_are_candidates() {
+        let old = keyring(&[(1, "first key")], 1);
+        let rotated = keyring(&[(1, "first key"), (2, "second key")], 2);
+        let hash = old.hash(b"token");
+        assert_ne!(rotated.hash(b"token"), hash);
+        assert!(rotated.hash_candidates(b"token").contains(&hash));
+    }
+}
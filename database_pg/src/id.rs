@@ -0,0 +1,346 @@
+//! Typed object IDs, see [`make_id!`](crate::make_id).
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+    pub use utoipa;
+}
+
+/// Failed to parse external representation of ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError {
+    pub id_name: &'static str,
+    pub value: String,
+}
+
+impl std::fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid {} value {:?}", self.id_name, self.value)
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+const FEISTEL_ROUNDS: u32 = 4;
+
+fn feistel_round(half: u32, key: u64, round: u32) -> u32 {
+    // splitmix64 finalizer keyed with the round number
+    let mut x = u64::from(half)
+        ^ key.rotate_left(round * 16)
+        ^ u64::from(round).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (x ^ (x >> 31)) as u32
+}
+
+/// Keyed permutation of 64-bit values. It hides sequence of IDs, but it is not a cryptographic protection
+pub fn obfuscate(value: i64, key: u64) -> u64 {
+    let value = value as u64;
+    let (mut left, mut right) = ((value >> 32) as u32, value as u32);
+    for round in 0..FEISTEL_ROUNDS {
+        let next_right = left ^ feistel_round(right, key, round);
+        left = right;
+        right = next_right;
+    }
+    (u64::from(left) << 32) | u64::from(right)
+}
+
+/// Reverse of [`obfuscate`]
+pub fn deobfuscate(value: u64, key: u64) -> i64 {
+    let (mut left, mut right) = ((value >> 32) as u32, value as u32);
+    for round in (0..FEISTEL_ROUNDS).rev() {
+        let prev_left = right ^ feistel_round(left, key, round);
+        right = left;
+        left = prev_left;
+    }
+    ((u64::from(left) << 32) | u64::from(right)) as i64
+}
+
+/// Formats obfuscated ID as `<prefix>_<16 hex digits>`
+pub fn encode(value: i64, prefix: &str, key: u64) -> String {
+    format!("{prefix}_{:016x}", obfuscate(value, key))
+}
+
+/// Parses ID formatted with [`encode`]
+pub fn decode(value: &str, prefix: &str, key: u64) -> Option<i64> {
+    let hex = value.strip_prefix(prefix)?.strip_prefix('_')?;
+    if hex.len() != 16 {
+        return None;
+    }
+    u64::from_str_radix(hex, 16)
+        .ok()
+        .map(|v| deobfuscate(v, key))
+}
+
+/// Generates `BigInt` newtype for object IDs.
+///
+/// The type can be used in diesel models and queries (including `Nullable` columns), in JSON bodies, actix path
+/// params and OpenAPI schemas. `make_id!(UserId)` exposes ID as is, while
+/// `make_id!(UserId, prefix = "usr", key = 0x1234_5678_9abc_def0)` exposes ID as an opaque string like
+/// `usr_8c1f0e9d2a7b4c35`, so sequential values of `object_id_seq` are not visible to clients. Values stored in DB are
+/// the same in both cases.
+#[macro_export]
+macro_rules! make_id {
+    (@common $id_name:ident) => {
+        #[derive(
+            Debug,
+            Eq,
+            Hash,
+            PartialEq,
+            Ord,
+            PartialOrd,
+            Clone,
+            Copy,
+            diesel::expression::AsExpression,
+            diesel::deserialize::FromSqlRow,
+        )]
+        #[diesel(sql_type = diesel::sql_types::BigInt)]
+        pub struct $id_name(i64);
+
+        impl $id_name {
+            pub const fn new(v: i64) -> Self {
+                Self(v)
+            }
+
+            pub const fn get(&self) -> i64 {
+                self.0
+            }
+        }
+
+        impl From<i64> for $id_name {
+            fn from(v: i64) -> Self {
+                Self(v)
+            }
+        }
+
+        impl From<$id_name> for i64 {
+            fn from(v: $id_name) -> Self {
+                v.0
+            }
+        }
+
+        impl<DB> diesel::deserialize::FromSql<diesel::sql_types::BigInt, DB> for $id_name
+        where
+            DB: diesel::backend::Backend,
+            i64: diesel::deserialize::FromSql<diesel::sql_types::BigInt, DB>,
+        {
+            fn from_sql(bytes: DB::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+                <i64 as diesel::deserialize::FromSql<diesel::sql_types::BigInt, DB>>::from_sql(
+                    bytes,
+                )
+                .map($id_name)
+            }
+        }
+
+        impl<DB> diesel::serialize::ToSql<diesel::sql_types::BigInt, DB> for $id_name
+        where
+            DB: diesel::backend::Backend,
+            i64: diesel::serialize::ToSql<diesel::sql_types::BigInt, DB>,
+        {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, DB>,
+            ) -> diesel::serialize::Result {
+                <i64 as diesel::serialize::ToSql<diesel::sql_types::BigInt, DB>>::to_sql(
+                    &self.0, out,
+                )
+            }
+        }
+    };
+    ($id_name:ident) => {
+        $crate::make_id!(@common $id_name);
+
+        impl std::fmt::Display for $id_name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl std::str::FromStr for $id_name {
+            type Err = $crate::id::ParseIdError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($id_name).map_err(|_| $crate::id::ParseIdError {
+                    id_name: stringify!($id_name),
+                    value: s.to_owned(),
+                })
+            }
+        }
+
+        impl $crate::id::__private::serde::Serialize for $id_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::id::__private::serde::Serializer,
+            {
+                serializer.serialize_i64(self.0)
+            }
+        }
+
+        impl<'de> $crate::id::__private::serde::Deserialize<'de> for $id_name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::id::__private::serde::Deserializer<'de>,
+            {
+                <i64 as $crate::id::__private::serde::Deserialize>::deserialize(deserializer)
+                    .map($id_name)
+            }
+        }
+
+        impl<'__s> $crate::id::__private::utoipa::ToSchema<'__s> for $id_name {
+            fn schema() -> (
+                &'__s str,
+                $crate::id::__private::utoipa::openapi::RefOr<
+                    $crate::id::__private::utoipa::openapi::schema::Schema,
+                >,
+            ) {
+                use $crate::id::__private::utoipa::openapi;
+                (
+                    stringify!($id_name),
+                    openapi::ObjectBuilder::new()
+                        .schema_type(openapi::schema::SchemaType::Integer)
+                        .format(Some(openapi::schema::SchemaFormat::KnownFormat(
+                            openapi::KnownFormat::Int64,
+                        )))
+                        .title(Some(stringify!($id_name)))
+                        .into(),
+                )
+            }
+        }
+    };
+    ($id_name:ident, prefix = $prefix:literal, key = $key:expr) => {
+        $crate::make_id!(@common $id_name);
+
+        impl std::fmt::Display for $id_name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&$crate::id::encode(self.0, $prefix, $key))
+            }
+        }
+
+        impl std::str::FromStr for $id_name {
+            type Err = $crate::id::ParseIdError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $crate::id::decode(s, $prefix, $key)
+                    .map($id_name)
+                    .ok_or_else(|| $crate::id::ParseIdError {
+                        id_name: stringify!($id_name),
+                        value: s.to_owned(),
+                    })
+            }
+        }
+
+        impl $crate::id::__private::serde::Serialize for $id_name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::id::__private::serde::Serializer,
+            {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> $crate::id::__private::serde::Deserialize<'de> for $id_name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::id::__private::serde::Deserializer<'de>,
+            {
+                use $crate::id::__private::serde::de::Error;
+
+                let v = <String as $crate::id::__private::serde::Deserialize>::deserialize(
+                    deserializer,
+                )?;
+                v.parse().map_err(D::Error::custom)
+            }
+        }
+
+        impl<'__s> $crate::id::__private::utoipa::ToSchema<'__s> for $id_name {
+            fn schema() -> (
+                &'__s str,
+                $crate::id::__private::utoipa::openapi::RefOr<
+                    $crate::id::__private::utoipa::openapi::schema::Schema,
+                >,
+            ) {
+                use $crate::id::__private::utoipa::openapi;
+                (
+                    stringify!($id_name),
+                    openapi::ObjectBuilder::new()
+                        .schema_type(openapi::schema::SchemaType::String)
+                        .title(Some(stringify!($id_name)))
+                        .description(Some("Opaque object identifier"))
+                        .example(Some(
+                            $crate::id::encode(1, $prefix, $key).into(),
+                        ))
+                        .into(),
+                )
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: u64 = 0x1234_5678_9abc_def0;
+
+    crate::make_id!(PlainId);
+    crate::make_id!(ObfuscatedId, prefix = "obf", key = KEY);
+
+    #[test]
+    fn deobfuscate_reverses_obfuscate() {
+        let values = (-1000..1000).chain([i64::MIN, i64::MIN + 1, i64::MAX - 1, i64::MAX]);
+        for value in values {
+            for key in [0, 1, KEY, u64::MAX] {
+                assert_eq!(deobfuscate(obfuscate(value, key), key), value);
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_values_are_hidden() {
+        let obfuscated: Vec<_> = (1..=100).map(|v| obfuscate(v, KEY)).collect();
+        let mut unique = obfuscated.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), obfuscated.len());
+        assert!(obfuscated.windows(2).all(|v| v[0].abs_diff(v[1]) > 1));
+        assert_ne!(obfuscate(1, KEY), obfuscate(1, KEY + 1));
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        let encoded = encode(42, "usr", KEY);
+        assert!(encoded.starts_with("usr_"));
+        assert_eq!(encoded.len(), 20);
+        assert_eq!(decode(&encoded, "usr", KEY), Some(42));
+        assert_eq!(decode(&encoded, "rol", KEY), None);
+        assert_eq!(decode(&encoded[..19], "usr", KEY), None);
+        assert_eq!(decode(&encoded.replace('_', "-"), "usr", KEY), None);
+        assert_eq!(decode("usr_000000000000000g", "usr", KEY), None);
+    }
+
+    #[test]
+    fn obfuscated_id_is_opaque_string() {
+        let id = ObfuscatedId::new(7);
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", encode(7, "obf", KEY)));
+        assert_eq!(serde_json::from_str::<ObfuscatedId>(&json).unwrap(), id);
+        assert_eq!(id.to_string().parse::<ObfuscatedId>(), Ok(id));
+        assert_eq!(
+            "7".parse::<ObfuscatedId>(),
+            Err(ParseIdError {
+                id_name: "ObfuscatedId",
+                value: "7".to_owned()
+            })
+        );
+        assert!(serde_json::from_str::<ObfuscatedId>("7").is_err());
+    }
+
+    #[test]
+    fn plain_id_is_number() {
+        let id = PlainId::new(7);
+        assert_eq!(serde_json::to_string(&id).unwrap(), "7");
+        assert_eq!(serde_json::from_str::<PlainId>("7").unwrap(), id);
+        assert_eq!("7".parse::<PlainId>(), Ok(id));
+        assert!("obf_0000000000000007".parse::<PlainId>().is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! Background jobs queue on top of Postgres.
+//!
+//! Jobs are stored in the `jobs` table and are enqueued with the connection of a running transaction, so they are
+//! committed (or rolled back) together with the business data. Workers fetch jobs with `SELECT ... FOR UPDATE SKIP
+//! LOCKED` and process them with typed handlers registered in a [`Registry`]. Periodic jobs are scheduled by workers
+//! and schedule their next run themselves, see [`Registry::register_periodic`].
+
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use structdoc::StructDoc;
+
+crate::make_id!(JobId);
+
+diesel::table! {
+    jobs (id) {
+        id -> Int8,
+        #[max_length = 255]
+        job_type -> Varchar,
+        payload -> Jsonb,
+        #[max_length = 16]
+        status -> Varchar,
+        create_date -> Timestamptz,
+        run_at -> Timestamptz,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        last_error -> Nullable<Text>,
+        last_attempt_date -> Nullable<Timestamptz>,
+    }
+}
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+pub struct Config {
+    /// Number of concurrent workers processing jobs
+    pub workers: usize,
+    /// How long to wait before polling the queue again when there are no ready jobs
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: std::time::Duration,
+    /// Delay before the first retry of a failed job. It is doubled on every next attempt
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: std::time::Duration,
+    /// Upper bound for the delay between retries
+    #[serde(with = "humantime_serde")]
+    pub max_retry_delay: std::time::Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            poll_interval: std::time::Duration::from_secs(5),
+            retry_delay: std::time::Duration::from_secs(10),
+            max_retry_delay: std::time::Duration::from_secs(3600),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Job is waiting for processing or for the next retry
+    Pending,
+    /// Job has exhausted all attempts and won't be processed until requeued
+    Dead,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Status {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "dead" => Ok(Self::Dead),
+            _ => Err(anyhow!(
+                "Unknown job status {s:?}, expected pending or dead"
+            )),
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = jobs)]
+pub struct JobRecord {
+    pub id: JobId,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub create_date: chrono::DateTime<chrono::Utc>,
+    pub run_at: chrono::DateTime<chrono::Utc>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub last_error: Option<String>,
+    pub last_attempt_date: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = jobs)]
+struct JobRecordNew<'a> {
+    job_type: &'a str,
+    payload: serde_json::Value,
+    status: &'a str,
+    create_date: chrono::DateTime<chrono::Utc>,
+    run_at: chrono::DateTime<chrono::Utc>,
+    attempts: i32,
+    max_attempts: i32,
+}
+
+/// Job payload. It is stored in the queue as JSON
+pub trait Job: Serialize + DeserializeOwned + Send + 'static {
+    /// Unique name of the job type
+    const JOB_TYPE: &'static str;
+    /// How many times job is tried before it is moved to dead letters
+    const MAX_ATTEMPTS: i32 = 5;
+}
+
+/// Processes jobs of type `J`. Handler runs inside the transaction which holds the lock on the job, any changes done
+/// by a failed handler are rolled back
+pub trait Handler<J: Job>: Send + Sync + 'static {
+    fn handle(&self, conn: &mut diesel::PgConnection, job: J) -> Result<()>;
+}
+
+impl<J, F> Handler<J> for F
+where
+    J: Job,
+    F: Fn(&mut diesel::PgConnection, J) -> Result<()> + Send + Sync + 'static,
+{
+    fn handle(&self, conn: &mut diesel::PgConnection, job: J) -> Result<()> {
+        self(conn, job)
+    }
+}
+
+trait ErasedHandler: Send + Sync {
+    fn handle(&self, conn: &mut diesel::PgConnection, payload: serde_json::Value) -> Result<()>;
+}
+
+struct TypedHandler<J, H> {
+    handler: H,
+    _job: std::marker::PhantomData<fn() -> J>,
+}
+
+impl<J: Job, H: Handler<J>> ErasedHandler for TypedHandler<J, H> {
+    fn handle(&self, conn: &mut diesel::PgConnection, payload: serde_json::Value) -> Result<()> {
+        let job: J = serde_json::from_value(payload)
+            .map_err(|err| anyhow!("Failed to parse payload of job {:?}: {err}", J::JOB_TYPE))?;
+        self.handler.handle(conn, job)
+    }
+}
+
+/// Job which is run every `interval`
+struct Periodic {
+    job_type: &'static str,
+    payload: serde_json::Value,
+    max_attempts: i32,
+}
+
+/// Set of job handlers. Workers only fetch jobs of registered types
+#[derive(Default)]
+pub struct Registry {
+    handlers: RwLock<BTreeMap<&'static str, Arc<dyn ErasedHandler>>>,
+    periodic: RwLock<Vec<Periodic>>,
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            handlers: RwLock::new(BTreeMap::new()),
+            periodic: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn register<J: Job, H: Handler<J>>(&self, handler: H) -> Result<()> {
+        let mut handlers = self.handlers.write().unwrap();
+        if handlers.contains_key(J::JOB_TYPE) {
+            return Err(anyhow!(
+                "Handler for job {:?} already registered",
+                J::JOB_TYPE
+            ));
+        }
+        let _ = handlers.insert(
+            J::JOB_TYPE,
+            Arc::new(TypedHandler {
+                handler,
+                _job: std::marker::PhantomData,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Registers handler of `job` which is run every `interval`. Workers enqueue the job if it is not in the queue yet,
+    /// so it can be registered after workers are started. Each run enqueues the next one
+    pub fn register_periodic<J: Job + Clone + Sync, H: Handler<J>>(
+        &self,
+        job: J,
+        interval: std::time::Duration,
+        handler: H,
+    ) -> Result<()> {
+        let interval = chrono::Duration::from_std(interval)?;
+        let payload = serde_json::to_value(&job)
+            .map_err(|err| anyhow!("Failed to serialize job {:?}: {err}", J::JOB_TYPE))?;
+        self.register(move |conn: &mut diesel::PgConnection, v: J| {
+            handler.handle(conn, v)?;
+            let _ = enqueue_at(conn, &job, chrono::Utc::now() + interval)?;
+            Ok(())
+        })?;
+        self.periodic.write().unwrap().push(Periodic {
+            job_type: J::JOB_TYPE,
+            payload,
+            max_attempts: J::MAX_ATTEMPTS,
+        });
+        Ok(())
+    }
+
+    /// Enqueues periodic jobs which are not in the queue. It is checked every time, since a run which fails
+    /// `max_attempts` times is moved to dead letters and does not enqueue the next one
+    fn schedule_periodic(&self, conn: &mut diesel::PgConnection) -> Result<()> {
+        for periodic in self.periodic.read().unwrap().iter() {
+            if is_type_scheduled(conn, periodic.job_type)? {
+                continue;
+            }
+            conn.transaction(|conn| {
+                // several instances of the application may start at once
+                let _ = diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                    .bind::<diesel::sql_types::Text, _>(periodic.job_type)
+                    .execute(conn)?;
+                if !is_type_scheduled(conn, periodic.job_type)? {
+                    let _ = insert(
+                        conn,
+                        periodic.job_type,
+                        periodic.payload.clone(),
+                        periodic.max_attempts,
+                        chrono::Utc::now(),
+                    )?;
+                }
+                Ok::<_, anyhow::Error>(())
+            })?;
+        }
+        Ok(())
+    }
+
+    fn job_types(&self) -> Vec<&'static str> {
+        self.handlers.read().unwrap().keys().copied().collect()
+    }
+
+    fn handle(
+        &self,
+        conn: &mut diesel::PgConnection,
+        job_type: &str,
+        payload: serde_json::Value,
+    ) -> Result<()> {
+        let handler = self
+            .handlers
+            .read()
+            .unwrap()
+            .get(job_type)
+            .cloned()
+            .ok_or_else(|| anyhow!("No handler registered for job {job_type:?}"))?;
+        handler.handle(conn, payload)
+    }
+}
+
+/// Adds job to the queue. Job will be processed as soon as the current transaction is committed
+pub fn enqueue<J: Job>(conn: &mut diesel::PgConnection, job: &J) -> Result<JobId> {
+    enqueue_at(conn, job, chrono::Utc::now())
+}
+
+/// Adds job to the queue. Job will not be processed earlier than `run_at`
+pub fn enqueue_at<J: Job>(
+    conn: &mut diesel::PgConnection,
+    job: &J,
+    run_at: chrono::DateTime<chrono::Utc>,
+) -> Result<JobId> {
+    let payload = serde_json::to_value(job)
+        .map_err(|err| anyhow!("Failed to serialize job {:?}: {err}", J::JOB_TYPE))?;
+    insert(conn, J::JOB_TYPE, payload, J::MAX_ATTEMPTS, run_at)
+}
+
+fn insert(
+    conn: &mut diesel::PgConnection,
+    job_type: &str,
+    payload: serde_json::Value,
+    max_attempts: i32,
+    run_at: chrono::DateTime<chrono::Utc>,
+) -> Result<JobId> {
+    let id = diesel::insert_into(jobs::table)
+        .values(JobRecordNew {
+            job_type,
+            payload,
+            status: Status::Pending.as_str(),
+            create_date: chrono::Utc::now(),
+            run_at,
+            attempts: 0,
+            max_attempts,
+        })
+        .returning(jobs::id)
+        .get_result(conn)
+        .map_err(|err| anyhow!("Failed to enqueue job {job_type:?}: {err}"))?;
+    Ok(id)
+}
+
+/// Whether a pending job of type `J` is in the queue
+pub fn is_scheduled<J: Job>(conn: &mut diesel::PgConnection) -> Result<bool> {
+    is_type_scheduled(conn, J::JOB_TYPE)
+}
+
+fn is_type_scheduled(conn: &mut diesel::PgConnection, job_type: &str) -> Result<bool> {
+    let r = diesel::select(diesel::dsl::exists(
+        jobs::table
+            .filter(jobs::job_type.eq(job_type))
+            .filter(jobs::status.eq(Status::Pending.as_str())),
+    ))
+    .get_result(conn)
+    .map_err(|err| anyhow!("Failed to check job {job_type:?}: {err}"))?;
+    Ok(r)
+}
+
+/// Lists jobs, optionally filtered by status, in order of their planned execution
+pub fn list(
+    conn: &mut diesel::PgConnection,
+    status: Option<Status>,
+    limit: i64,
+) -> Result<Vec<JobRecord>> {
+    let mut query = jobs::table.into_boxed();
+    if let Some(status) = status {
+        query = query.filter(jobs::status.eq(status.as_str()));
+    }
+    let r = query
+        .order(jobs::run_at.asc())
+        .limit(limit)
+        .load(conn)
+        .map_err(|err| anyhow!("Failed to list jobs: {err}"))?;
+    Ok(r)
+}
+
+/// Returns job by its ID
+pub fn get(conn: &mut diesel::PgConnection, id: JobId) -> Result<JobRecord> {
+    let r = jobs::table
+        .find(id)
+        .get_result(conn)
+        .map_err(|err| anyhow!("Failed to get job {id}: {err}"))?;
+    Ok(r)
+}
+
+/// Moves dead job back to the queue and resets its attempts counter
+pub fn requeue(conn: &mut diesel::PgConnection, id: JobId) -> Result<()> {
+    let updated = diesel::update(
+        jobs::table
+            .find(id)
+            .filter(jobs::status.eq(Status::Dead.as_str())),
+    )
+    .set((
+        jobs::status.eq(Status::Pending.as_str()),
+        jobs::attempts.eq(0),
+        jobs::run_at.eq(chrono::Utc::now()),
+    ))
+    .execute(conn)
+    .map_err(|err| anyhow!("Failed to requeue job {id}: {err}"))?;
+    if updated == 0 {
+        return Err(anyhow!("No dead job with ID {id}"));
+    }
+    Ok(())
+}
+
+/// Moves all dead jobs back to the queue. Returns number of requeued jobs
+pub fn requeue_dead(conn: &mut diesel::PgConnection) -> Result<usize> {
+    diesel::update(jobs::table.filter(jobs::status.eq(Status::Dead.as_str())))
+        .set((
+            jobs::status.eq(Status::Pending.as_str()),
+            jobs::attempts.eq(0),
+            jobs::run_at.eq(chrono::Utc::now()),
+        ))
+        .execute(conn)
+        .map_err(|err| anyhow!("Failed to requeue dead jobs: {err}"))
+}
+
+fn retry_delay(config: &Config, attempts: i32) -> std::time::Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    config
+        .retry_delay
+        .saturating_mul(1 << exponent)
+        .min(config.max_retry_delay)
+}
+
+fn process_next(
+    conn: &mut diesel::PgConnection,
+    registry: &Registry,
+    config: &Config,
+) -> Result<bool> {
+    registry.schedule_periodic(conn)?;
+    let job_types = registry.job_types();
+    if job_types.is_empty() {
+        return Ok(false);
+    }
+
+    conn.build_transaction().read_committed().run(|conn| {
+        let job: Option<JobRecord> = jobs::table
+            .filter(jobs::status.eq(Status::Pending.as_str()))
+            .filter(jobs::run_at.le(chrono::Utc::now()))
+            .filter(jobs::job_type.eq_any(job_types))
+            .order(jobs::run_at.asc())
+            .for_update()
+            .skip_locked()
+            .first(conn)
+            .optional()?;
+        let job = match job {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        let span = tracing::info_span!("job", id = %job.id, job_type = job.job_type);
+        let _span_guard = span.enter();
+
+        // Nested transaction is a savepoint: changes of failed handler are rolled back, but the job stays locked
+        let result =
+            conn.transaction(|conn| registry.handle(conn, &job.job_type, job.payload.clone()));
+        let now = chrono::Utc::now();
+        match result {
+            Ok(()) => {
+                tracing::debug!("Job done");
+                diesel::delete(jobs::table.find(job.id)).execute(conn)?;
+            }
+            Err(err) => {
+                let attempts = job.attempts + 1;
+                let (status, run_at) = if attempts >= job.max_attempts {
+                    tracing::error!("Job failed for the last time, moved to dead letters: {err}");
+                    (Status::Dead, job.run_at)
+                } else {
+                    let delay = chrono::Duration::from_std(retry_delay(config, attempts))?;
+                    tracing::warn!("Job failed, will retry in {delay}: {err}");
+                    (Status::Pending, now + delay)
+                };
+                diesel::update(jobs::table.find(job.id))
+                    .set((
+                        jobs::status.eq(status.as_str()),
+                        jobs::attempts.eq(attempts),
+                        jobs::run_at.eq(run_at),
+                        jobs::last_error.eq(Some(err.to_string())),
+                        jobs::last_attempt_date.eq(Some(now)),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+        Ok(true)
+    })
+}
+
+async fn run_worker(pool: Arc<crate::Pool>, registry: &'static Registry, config: Config) {
+    loop {
+        let worker_config = config.clone();
+        let processed = pool
+            .with_connection(move |conn| process_next(conn, registry, &worker_config))
+            .await;
+        match processed {
+            Ok(true) => continue,
+            Ok(false) => (),
+            Err(err) => tracing::error!("Failed to process jobs queue: {err}"),
+        }
+        tokio::time::sleep(config.poll_interval).await
+    }
+}
+
+/// Starts background workers if jobs processing is configured for the pool
+pub fn spawn_workers(pool: Arc<crate::Pool>, registry: &'static Registry) {
+    let config = match &pool.config.jobs {
+        Some(v) => v.clone(),
+        None => return,
+    };
+    for _ in 0..config.workers {
+        tokio::spawn(run_worker(pool.clone(), registry, config.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config(retry_delay: Duration, max_retry_delay: Duration) -> Config {
+        Config {
+            retry_delay,
+            max_retry_delay,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn retry_delay_is_doubled_on_every_attempt() {
+        let config = config(Duration::from_secs(10), Duration::from_secs(3600));
+        let delays: Vec<_> = (1..=5).map(|v| retry_delay(&config, v).as_secs()).collect();
+        assert_eq!(delays, [10, 20, 40, 80, 160]);
+        assert_eq!(retry_delay(&config, 0), Duration::from_secs(10));
+        assert_eq!(retry_delay(&config, -1), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retry_delay_exponent_is_clamped() {
+        let config = config(Duration::from_millis(1), Duration::MAX);
+        assert_eq!(retry_delay(&config, 17), Duration::from_millis(1 << 16));
+        assert_eq!(retry_delay(&config, 18), Duration::from_millis(1 << 16));
+        assert_eq!(
+            retry_delay(&config, i32::MAX),
+            Duration::from_millis(1 << 16)
+        );
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let config = config(Duration::from_secs(10), Duration::from_secs(60));
+        assert_eq!(retry_delay(&config, 3), Duration::from_secs(40));
+        assert_eq!(retry_delay(&config, 4), Duration::from_secs(60));
+        assert_eq!(retry_delay(&config, 100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn status_round_trip() {
+        for status in [Status::Pending, Status::Dead] {
+            assert_eq!(status.to_string().parse::<Status>().unwrap(), status);
+        }
+        assert_eq!(Status::Dead.to_string(), "dead");
+        assert!("Dead".parse::<Status>().is_err());
+    }
+}
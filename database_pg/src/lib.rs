@@ -1,9 +1,15 @@
+pub mod crypto;
+pub mod id;
+pub mod jobs;
 pub mod secstr;
+pub mod seed;
+pub mod soft_delete;
 pub mod sync;
 
 use anyhow::{anyhow, Result};
 use deadpool_diesel::postgres::Manager;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use structdoc::StructDoc;
 
 #[derive(Serialize, Deserialize, StructDoc)]
@@ -12,11 +18,21 @@ pub struct Config {
     pub database_url: webapp_yaml_config::secret::Secret,
     /// Maximum number of connections to keep opened
     pub max_connections: usize,
+    /// Background jobs processing. If not set, jobs are not processed by this application
+    #[serde(default)]
+    pub jobs: Option<crate::jobs::Config>,
+    /// Keys for encrypted and hashed columns
+    #[serde(default)]
+    pub encryption: Option<crate::crypto::Config>,
+    /// Removal of soft-deleted rows. If not set, they are kept forever
+    #[serde(default)]
+    pub soft_delete: Option<crate::soft_delete::Config>,
 }
 
 pub struct Pool {
     pub config: webapp_yaml_config::yaml::Config<Config>,
     pool: deadpool_diesel::postgres::Pool,
+    keyring: Option<Arc<crate::crypto::Keyring>>,
 }
 
 impl Pool {
@@ -32,8 +48,20 @@ pub fn new(plugin_name: &'static str, configs_path: &std::path::Path) -> Result<
         let pool = Pool::builder(manager)
             .max_size(config.config.max_connections)
             .build()?;
+        let keyring = crate::crypto::load_keyring(&config)?;
 
-        Ok(Self { config, pool })
+        Ok(Self {
+            config,
+            pool,
+            keyring,
+        })
+    }
+
+    /// Keys for encrypted and hashed columns. Fails if encryption is not configured
+    pub fn keyring(&self) -> Result<Arc<crate::crypto::Keyring>> {
+        self.keyring
+            .clone()
+            .ok_or_else(|| anyhow!("Encryption is not configured for DB {:?}", self.config.name))
     }
 
     pub async fn with_connection<RESULT, F>(&self, f: F) -> Result<RESULT>
@@ -58,43 +86,3 @@ pub async fn with_transaction<RESULT, F>(&self, f: F) -> Result<RESULT>
         .await
     }
 }
-
-#[macro_export]
-macro_rules! make_id {
-    ($id_name:ident) => {
-        #[derive(Debug, Eq, Hash, PartialEq, Clone, diesel::expression::AsExpression)]
-        #[diesel(sql_type = diesel::sql_types::BigInt)]
-        pub struct $id_name(i64);
-
-        impl Into<i64> for $id_name {
-            fn into(self) -> i64 {
-                self.0
-            }
-        }
-
-        impl<DB> Queryable<diesel::sql_types::BigInt, DB> for $id_name
-        where
-            DB: diesel::backend::Backend,
-            i64: diesel::deserialize::FromSql<diesel::sql_types::BigInt, DB>,
-        {
-            type Row = i64;
-
-            fn build(v: i64) -> diesel::deserialize::Result<Self> {
-                Ok($id_name(v))
-            }
-        }
-
-        impl<DB> diesel::serialize::ToSql<diesel::sql_types::BigInt, DB> for $id_name
-        where
-            DB: diesel::backend::Backend,
-            i64: diesel::serialize::ToSql<diesel::sql_types::BigInt, DB>,
-        {
-            fn to_sql<'b>(
-                &'b self,
-                out: &mut diesel::serialize::Output<'b, '_, DB>,
-            ) -> diesel::serialize::Result {
-                <i64>::to_sql(&self.0, out)
-            }
-        }
-    };
-}
@@ -0,0 +1,145 @@
+//! Loading of fixtures into a freshly migrated DB.
+//!
+//! Fixture file is a YAML (or JSON) list of rows:
+//!
+//! ```yaml
+//! - type: user
+//!   ref: admin
+//!   data:
+//!     username: admin
+//!     person: Administrator
+//! - type: user_password
+//!   data:
+//!     user_id: { $ref: admin }
+//!     password: secret
+//! ```
+//!
+//! `type` selects the fixture loader registered in [`Registry`], `data` is passed to it. Row with `ref` can be
+//! referenced by later rows with `{ $ref: NAME }`, which is replaced with the ID of the referenced row.
+
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Row of fixture file
+#[derive(Deserialize, Debug)]
+pub struct Row {
+    /// Fixture type
+    #[serde(rename = "type")]
+    pub fixture_type: String,
+    /// Name for references from other rows
+    #[serde(rename = "ref", default)]
+    pub reference: Option<String>,
+    /// Fixture data
+    pub data: serde_json::Value,
+}
+
+/// Fixture type provided by a plugin
+pub trait Fixture: DeserializeOwned + 'static {
+    /// Unique name of the fixture type
+    const FIXTURE_TYPE: &'static str;
+
+    /// Inserts the row. Returns ID of the created object, if it can be referenced
+    fn load(self, conn: &mut diesel::PgConnection) -> Result<Option<i64>>;
+}
+
+type Loader = fn(&mut diesel::PgConnection, serde_json::Value) -> Result<Option<i64>>;
+
+fn load_typed<F: Fixture>(
+    conn: &mut diesel::PgConnection,
+    data: serde_json::Value,
+) -> Result<Option<i64>> {
+    let fixture: F = serde_json::from_value(data)
+        .map_err(|err| anyhow!("Invalid data of fixture {:?}: {err}", F::FIXTURE_TYPE))?;
+    fixture.load(conn)
+}
+
+/// Set of fixture types
+#[derive(Default)]
+pub struct Registry {
+    loaders: BTreeMap<&'static str, Loader>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F: Fixture>(&mut self) -> Result<()> {
+        if self.loaders.contains_key(F::FIXTURE_TYPE) {
+            bail!("Fixture {:?} already registered", F::FIXTURE_TYPE)
+        }
+        let _ = self.loaders.insert(F::FIXTURE_TYPE, load_typed::<F>);
+        Ok(())
+    }
+
+    /// Loads rows in order of appearance. Run it inside a transaction, so partially loaded fixtures are rolled back
+    pub fn load(&self, conn: &mut diesel::PgConnection, rows: Vec<Row>) -> Result<usize> {
+        let mut references = HashMap::new();
+        for (pos, row) in rows.iter().enumerate() {
+            let loader = self.loaders.get(row.fixture_type.as_str()).ok_or_else(|| {
+                anyhow!(
+                    "Row {pos}: unknown fixture type {:?}, known types are: {}",
+                    row.fixture_type,
+                    self.loaders.keys().copied().collect::<Vec<_>>().join(", ")
+                )
+            })?;
+            let data = resolve(row.data.clone(), &references)
+                .map_err(|err| anyhow!("Row {pos}: {err}"))?;
+            let id = loader(conn, data).map_err(|err| anyhow!("Row {pos}: {err}"))?;
+            if let Some(reference) = &row.reference {
+                let id = id.ok_or_else(|| {
+                    anyhow!(
+                        "Row {pos}: fixture {:?} cannot be referenced",
+                        row.fixture_type
+                    )
+                })?;
+                if references.insert(reference.clone(), id).is_some() {
+                    bail!("Row {pos}: reference {reference:?} defined twice")
+                }
+            }
+        }
+        Ok(rows.len())
+    }
+}
+
+fn resolve(
+    value: serde_json::Value,
+    references: &HashMap<String, i64>,
+) -> Result<serde_json::Value> {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) => {
+            if map.len() == 1 {
+                if let Some(reference) = map.get("$ref") {
+                    let reference = reference
+                        .as_str()
+                        .ok_or_else(|| anyhow!("$ref value must be a string"))?;
+                    let id = references.get(reference).ok_or_else(|| {
+                        anyhow!("Unknown reference {reference:?}, it must be defined in one of previous rows")
+                    })?;
+                    return Ok(Value::from(*id));
+                }
+            }
+            map.into_iter()
+                .map(|(k, v)| resolve(v, references).map(|v| (k, v)))
+                .collect::<Result<_>>()
+                .map(Value::Object)
+        }
+        Value::Array(list) => list
+            .into_iter()
+            .map(|v| resolve(v, references))
+            .collect::<Result<_>>()
+            .map(Value::Array),
+        v => Ok(v),
+    }
+}
+
+/// Reads fixture file. JSON files are read too, since JSON is a subset of YAML
+pub fn read(path: &std::path::Path) -> Result<Vec<Row>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed to load fixtures file {path:?}: {err}"))?;
+    serde_yaml::from_str(&content)
+        .map_err(|err| anyhow!("Failed to parse fixtures file {path:?}: {err}"))
+}
@@ -0,0 +1,201 @@
+//! Soft delete: rows are marked with `deleted_at` instead of being removed, and can be restored.
+//!
+//! Tables are declared with [`Table`], together with the tables which rows are deleted and restored with them. Rows
+//! deleted longer than the configured retention are removed by the purge job, see [`start_purge`].
+
+use anyhow::{anyhow, Result};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Timestamptz};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use structdoc::StructDoc;
+
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+pub struct Config {
+    /// How long deleted rows can be restored before they are removed
+    #[serde(with = "humantime_serde")]
+    pub retention: std::time::Duration,
+    /// How often deleted rows are checked for removal
+    #[serde(with = "humantime_serde")]
+    pub purge_interval: std::time::Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            retention: std::time::Duration::from_secs(30 * 24 * 3600),
+            purge_interval: std::time::Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Table with `id` primary key and nullable `deleted_at` column
+#[derive(Clone, Debug)]
+pub struct Table {
+    name: &'static str,
+    /// Tables with `deleted_at` column and their columns referencing this table
+    children: Vec<(&'static str, &'static str)>,
+}
+
+#[derive(QueryableByName)]
+struct DeletedAt {
+    #[diesel(sql_type = Timestamptz)]
+    deleted_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Table {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            children: Vec::new(),
+        }
+    }
+
+    /// Rows of `table` with `column` referencing deleted row are deleted with it, and restored with it if they were
+    /// not deleted before
+    pub fn cascade(mut self, table: &'static str, column: &'static str) -> Self {
+        self.children.push((table, column));
+        self
+    }
+
+    /// Marks row deleted. Returns `false` if there is no row with `id` which is not deleted yet
+    pub fn delete(&self, conn: &mut PgConnection, id: impl Into<i64>) -> Result<bool> {
+        let id = id.into();
+        let now = chrono::Utc::now();
+        let deleted = diesel::sql_query(format!(
+            r#"UPDATE "{}" SET deleted_at = $2 WHERE id = $1 AND deleted_at IS NULL"#,
+            self.name
+        ))
+        .bind::<BigInt, _>(id)
+        .bind::<Timestamptz, _>(now)
+        .execute(conn)
+        .map_err(|err| anyhow!("Failed to delete {} {id}: {err}", self.name))?;
+        if deleted == 0 {
+            return Ok(false);
+        }
+        for (table, column) in &self.children {
+            let _ = diesel::sql_query(format!(
+                r#"UPDATE "{table}" SET deleted_at = $2 WHERE "{column}" = $1 AND deleted_at IS NULL"#
+            ))
+            .bind::<BigInt, _>(id)
+            .bind::<Timestamptz, _>(now)
+            .execute(conn)
+            .map_err(|err| anyhow!("Failed to delete {table} of {} {id}: {err}", self.name))?;
+        }
+        Ok(true)
+    }
+
+    /// Clears deletion mark of row. Returns `false` if there is no deleted row with `id`
+    pub fn restore(&self, conn: &mut PgConnection, id: impl Into<i64>) -> Result<bool> {
+        let id = id.into();
+        let restored = diesel::sql_query(format!(
+            r#"UPDATE "{0}" t SET deleted_at = NULL FROM (SELECT id, deleted_at FROM "{0}" WHERE id = $1 AND deleted_at IS NOT NULL FOR UPDATE) old WHERE t.id = old.id RETURNING old.deleted_at"#,
+            self.name
+        ))
+        .bind::<BigInt, _>(id)
+        .get_result::<DeletedAt>(conn)
+        .optional()
+        .map_err(|err| anyhow!("Failed to restore {} {id}: {err}", self.name))?;
+        let deleted_at = match restored {
+            Some(v) => v.deleted_at,
+            None => return Ok(false),
+        };
+        // only the rows deleted together with this one
+        for (table, column) in &self.children {
+            let _ = diesel::sql_query(format!(
+                r#"UPDATE "{table}" SET deleted_at = NULL WHERE "{column}" = $1 AND deleted_at = $2"#
+            ))
+            .bind::<BigInt, _>(id)
+            .bind::<Timestamptz, _>(deleted_at)
+            .execute(conn)
+            .map_err(|err| anyhow!("Failed to restore {table} of {} {id}: {err}", self.name))?;
+        }
+        Ok(true)
+    }
+
+    /// Removes rows deleted before `before`. Returns number of removed rows of this table
+    pub fn purge(
+        &self,
+        conn: &mut PgConnection,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize> {
+        for (table, _) in &self.children {
+            let _ = diesel::sql_query(format!(r#"DELETE FROM "{table}" WHERE deleted_at < $1"#))
+                .bind::<Timestamptz, _>(before)
+                .execute(conn)
+                .map_err(|err| anyhow!("Failed to purge {table}: {err}"))?;
+        }
+        diesel::sql_query(format!(
+            r#"DELETE FROM "{}" WHERE deleted_at < $1"#,
+            self.name
+        ))
+        .bind::<Timestamptz, _>(before)
+        .execute(conn)
+        .map_err(|err| anyhow!("Failed to purge {}: {err}", self.name))
+    }
+}
+
+/// Tables purged by the purge job. Plugins register their tables here on initialization
+#[derive(Default)]
+pub struct Registry {
+    tables: RwLock<Vec<Table>>,
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self {
+            tables: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn register(&self, table: Table) -> Result<()> {
+        let mut tables = self.tables.write().unwrap();
+        if tables.iter().any(|v| v.name == table.name) {
+            return Err(anyhow!("Table {:?} already registered", table.name));
+        }
+        tables.push(table);
+        Ok(())
+    }
+
+    /// Removes rows of all tables deleted before `before`
+    pub fn purge(
+        &self,
+        conn: &mut PgConnection,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        for table in self.tables.read().unwrap().iter() {
+            let purged = table.purge(conn, before)?;
+            if purged > 0 {
+                tracing::info!("Purged {purged} deleted rows of {}", table.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Job removing expired deleted rows
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Purge {}
+
+impl crate::jobs::Job for Purge {
+    const JOB_TYPE: &'static str = "soft_delete_purge";
+}
+
+/// Registers the purge job, if `soft_delete` section is set in DB config. Jobs must be processed by the application
+/// to run it, see [`spawn_workers`](crate::jobs::spawn_workers)
+pub fn start_purge(
+    pool: &crate::Pool,
+    tables: &'static Registry,
+    jobs: &'static crate::jobs::Registry,
+) -> Result<()> {
+    let config = match &pool.config.soft_delete {
+        Some(v) => v.clone(),
+        None => return Ok(()),
+    };
+    let retention = chrono::Duration::from_std(config.retention)?;
+    jobs.register_periodic(
+        Purge {},
+        config.purge_interval,
+        move |conn: &mut PgConnection, _: Purge| tables.purge(conn, chrono::Utc::now() - retention),
+    )
+}
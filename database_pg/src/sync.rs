@@ -1,10 +1,12 @@
 use anyhow::{anyhow, Result};
 use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
+use std::sync::Arc;
 
 pub struct Pool {
     pub config: webapp_yaml_config::yaml::Config<crate::Config>,
     pool: diesel::r2d2::Pool<ConnectionManager<PgConnection>>,
+    keyring: Option<Arc<crate::crypto::Keyring>>,
 }
 
 impl Pool {
@@ -15,8 +17,20 @@ pub fn new(plugin_name: &'static str, configs_path: &std::path::Path) -> Result<
         let manager =
             ConnectionManager::<PgConnection>::new(config.config.database_url.unsecure()?);
         let pool = diesel::r2d2::Pool::builder().build(manager)?;
+        let keyring = crate::crypto::load_keyring(&config)?;
 
-        Ok(Self { config, pool })
+        Ok(Self {
+            config,
+            pool,
+            keyring,
+        })
+    }
+
+    /// Keys for encrypted and hashed columns. Fails if encryption is not configured
+    pub fn keyring(&self) -> Result<Arc<crate::crypto::Keyring>> {
+        self.keyring
+            .clone()
+            .ok_or_else(|| anyhow!("Encryption is not configured for DB {:?}", self.config.name))
     }
 
     pub fn with_connection<RESULT, F>(&self, f: F) -> Result<RESULT>
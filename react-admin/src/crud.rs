@@ -0,0 +1,1123 @@
+//! Generic react-admin resource: list, getOne, getMany, create, update, updateMany, delete and deleteMany endpoints
+//! with OpenAPI documentation.
+//!
+//! Implement [`Crud`] for the response type of the resource and register it with [`CrudResource`]:
+//!
+//! | Method            | Path              | react-admin method       |
+//! |-------------------|-------------------|--------------------------|
+//! | `GET`             | `/resource`       | getList, getMany (`?id=`)|
+//! | `POST`            | `/resource`       | create                   |
+//! | `PUT`             | `/resource?id=`   | updateMany               |
+//! | `DELETE`          | `/resource?id=`   | deleteMany               |
+//! | `POST`            | `/resource/import`| bulk import, see [`import`](crate::import) |
+//! | `GET`             | `/resource/{id}`  | getOne                   |
+//! | `PUT`, `PATCH`    | `/resource/{id}`  | update                   |
+//! | `DELETE`          | `/resource/{id}`  | delete                   |
+//! | `POST`            | `/resource/restore?id=` | restore several soft-deleted objects |
+//! | `POST`            | `/resource/{id}/restore`| restore soft-deleted object |
+//!
+//! Objects of [`Crud::VERSIONED`] resources are sent with their version and hash of the body in `ETag`. Update
+//! requires `If-Match` with it, so changes made by someone else are not overwritten: 412 with the current object is
+//! returned if versions differ. Version must change on every change which `If-Match` should catch, including soft
+//! delete and restore.
+//! Delete checks `If-Match` if it is set. updateMany and deleteMany do not check versions.
+//!
+//! Created, updated, deleted, restored and imported objects are passed to [`Policy::record`] in the same transaction,
+//! with their state before and after the change, e.g. for audit log.
+//!
+//! Resources with [`Filterable::deleted_at`] are soft-deleted: deleted objects are hidden from lists and getOne unless
+//! `include_deleted=true` is requested, and cannot be updated.
+
+use actix_web::guard;
+use actix_web::http::header::{self, Header};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Data};
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use utoipa::openapi::header::HeaderBuilder;
+use utoipa::openapi::path::{
+    OperationBuilder, Parameter, ParameterBuilder, ParameterIn, PathItemBuilder, PathItemType,
+};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::schema::{ArrayBuilder, ObjectBuilder, SchemaType};
+use utoipa::openapi::security::SecurityRequirement;
+use utoipa::openapi::{
+    ComponentsBuilder, ContentBuilder, OpenApi, OpenApiBuilder, PathsBuilder, Ref, RefOr, Required,
+    ResponseBuilder, Schema,
+};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::cursor::{CursorRequest, Page, Pagination};
+use crate::filter::{Filter, Filterable};
+use crate::import::{ImportFile, ImportReport, ImportRequest, Imported};
+use crate::request_list::PaginatedRequest;
+use crate::sort::{SortRequest, Sortable};
+use crate::APIObject;
+
+/// Action on resource, checked by [`Policy`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    List,
+    Read,
+    Create,
+    Update,
+    Delete,
+    /// Request soft-deleted objects with `include_deleted`
+    ReadDeleted,
+    Restore,
+}
+
+/// Permission policy of resource
+pub trait Policy: 'static {
+    /// Who performs the action, usually authenticated user. Requests are rejected if it cannot be extracted
+    type Subject: actix_web::FromRequest + 'static;
+
+    /// Who makes changes, e.g. their permissions, and where the request came from. It is taken from the request before
+    /// the transaction and passed to [`Crud::check_change`] and [`Policy::record`]
+    type Context: Send + 'static;
+
+    fn allowed(subject: &Self::Subject, action: Action) -> bool;
+
+    fn context(subject: &Self::Subject, req: &actix_web::HttpRequest) -> Self::Context;
+
+    /// Records change of object, e.g. in audit log. Called in the same transaction as the change, does nothing by
+    /// default
+    fn record<R: Crud>(
+        _conn: &mut diesel::PgConnection,
+        _context: &Self::Context,
+        _change: Change<'_, R>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Security requirements of the endpoints performing `action` in OpenAPI
+    fn security(_action: Action) -> Vec<SecurityRequirement> {
+        Vec::new()
+    }
+}
+
+/// Change of object made with [`CrudResource`] endpoints, passed to [`Policy::record`]
+pub struct Change<'a, R: Crud> {
+    /// `Create`, `Update`, `Delete` or `Restore`
+    pub action: Action,
+    pub id: &'a R::Id,
+    /// Object before the change, `None` if it is created
+    pub before: Option<&'a R>,
+    /// Object after the change, `None` if it is deleted without soft delete
+    pub after: Option<&'a R>,
+}
+
+/// Resource exposed with [`CrudResource`]. All methods are called inside a transaction
+pub trait Crud:
+    Sortable + Filterable + Serialize + for<'s> ToSchema<'s> + Send + Sized + 'static
+{
+    /// Object ID, `{id}` path param. It is `BIGINT` in DB, e.g. made with [`database_pg::make_id`], and recorded in
+    /// audit log as number
+    type Id: DeserializeOwned + Serialize + Clone + Into<i64> + Send + 'static;
+    /// Body of `POST` request
+    type Create: DeserializeOwned + for<'s> ToSchema<'s> + Send + 'static;
+    /// Body of `PUT` and `PATCH` requests
+    type Update: DeserializeOwned + for<'s> ToSchema<'s> + Clone + Send + 'static;
+    type Policy: Policy;
+    /// DB registered as app data, which pool is used for queries
+    type Db: AsRef<database_pg::Pool> + 'static;
+    /// Name of objects in records of changes, e.g. `user`
+    const ENTITY: &'static str;
+    /// Objects have version which changes on every update, e.g. `version` or `updated_at` column
+    const VERSIONED: bool = false;
+
+    /// Schema of `Id`, e.g. `UserId::schema().1`
+    fn id_schema() -> RefOr<Schema>;
+
+    fn id(&self) -> Self::Id;
+
+    /// Version of object, if resource is [`VERSIONED`](Self::VERSIONED)
+    fn version(&self) -> Option<String> {
+        None
+    }
+
+    /// Object is soft-deleted
+    fn is_deleted(&self) -> bool {
+        false
+    }
+
+    fn list(
+        conn: &mut diesel::PgConnection,
+        pagination: &Pagination<Self>,
+        filter: &Filter<Self>,
+    ) -> Result<Page<Self>>;
+
+    fn get(conn: &mut diesel::PgConnection, id: &Self::Id) -> Result<Option<Self>>;
+
+    fn create(conn: &mut diesel::PgConnection, data: Self::Create) -> Result<Self>;
+
+    /// Returns `None` if object does not exist
+    fn update(
+        conn: &mut diesel::PgConnection,
+        id: &Self::Id,
+        data: Self::Update,
+    ) -> Result<Option<Self>>;
+
+    /// Returns deleted object, or `None` if it does not exist
+    fn delete(conn: &mut diesel::PgConnection, id: &Self::Id) -> Result<Option<Self>>;
+
+    /// Clears deletion mark of soft-deleted object. Returns `None` if there is no deleted object with the ID
+    fn restore(_conn: &mut diesel::PgConnection, _id: &Self::Id) -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    /// Checks change in the same transaction before it is recorded, e.g. that subject may grant what the object grants.
+    /// Return [`Forbidden`] to reject it with 403, allows all changes by default
+    fn check_change(
+        _conn: &mut diesel::PgConnection,
+        _context: &<Self::Policy as Policy>::Context,
+        _change: &Change<'_, Self>,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Stores a row of bulk import. Creates object by default, override to update existing objects found by a natural
+    /// key, e.g. username
+    fn import(conn: &mut diesel::PgConnection, data: Self::Create) -> Result<ImportedObject<Self>> {
+        Self::create(conn, data).map(ImportedObject::Created)
+    }
+}
+
+/// Object stored by [`Crud::import`]
+pub enum ImportedObject<R> {
+    Created(R),
+    /// Existing object is updated, `before` is its state before the update
+    Updated {
+        before: R,
+        after: R,
+    },
+}
+
+/// Error of [`Crud`] methods about invalid request data, responded with 400
+#[derive(Debug)]
+pub struct Invalid(pub String);
+
+impl std::fmt::Display for Invalid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Invalid {}
+
+/// Error of [`Crud`] methods about change which subject may not make, responded with 403
+#[derive(Debug)]
+pub struct Forbidden(pub String);
+
+impl std::fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Forbidden {}
+
+type Subject<R> = <<R as Crud>::Policy as Policy>::Subject;
+type Context<R> = <<R as Crud>::Policy as Policy>::Context;
+
+fn pool<R: Crud>(db: &Data<R::Db>) -> &database_pg::Pool {
+    db.get_ref().as_ref()
+}
+
+fn check<R: Crud>(subject: &Subject<R>, action: Action) -> actix_web::Result<()> {
+    if R::Policy::allowed(subject, action) {
+        Ok(())
+    } else {
+        Err(actix_web::error::ErrorForbidden("Action is not allowed"))
+    }
+}
+
+/// Status of response to error of [`Crud`] methods and message for the client, `None` for internal errors which
+/// details are not shown
+fn classify(err: &anyhow::Error) -> (StatusCode, Option<String>) {
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    if let Some(err) = err.downcast_ref::<Invalid>() {
+        return (StatusCode::BAD_REQUEST, Some(err.0.clone()));
+    }
+    if let Some(err) = err.downcast_ref::<Forbidden>() {
+        return (StatusCode::FORBIDDEN, Some(err.0.clone()));
+    }
+    match err.downcast_ref::<Error>() {
+        Some(Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation,
+            info,
+        )) => (StatusCode::CONFLICT, Some(info.message().to_owned())),
+        Some(Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _)) => (
+            StatusCode::CONFLICT,
+            Some("Object is changed concurrently, try again".to_owned()),
+        ),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, None),
+    }
+}
+
+/// Converts error of [`Crud`] methods to response: 400 for [`Invalid`], 403 for [`Forbidden`], 409 for constraint violations and
+/// serialization failures, 500 otherwise
+pub fn db_error(err: anyhow::Error) -> actix_web::Error {
+    let (status, message) = classify(&err);
+    actix_web::error::InternalError::new(message.unwrap_or_else(|| err.to_string()), status).into()
+}
+
+/// Message of error of [`Crud`] methods in reports, e.g. of failed import rows. Internal errors are logged and
+/// reported without details
+pub(crate) fn error_message(err: &anyhow::Error) -> String {
+    match classify(err) {
+        (_, Some(message)) => message,
+        (_, None) => {
+            tracing::error!("{err:#}");
+            "Internal error".to_owned()
+        }
+    }
+}
+
+fn not_found() -> actix_web::Error {
+    actix_web::error::ErrorNotFound("Object not found")
+}
+
+/// Soft-deleted objects are not changed
+fn is_deleted<R: Crud>(conn: &mut diesel::PgConnection, id: &R::Id) -> Result<bool> {
+    if R::deleted_at().is_none() {
+        return Ok(false);
+    }
+    Ok(R::get(conn, id)?.is_some_and(|v| v.is_deleted()))
+}
+
+/// Checks change with [`Crud::check_change`] and records it with [`Policy::record`]
+fn record<R: Crud>(
+    conn: &mut diesel::PgConnection,
+    context: &Context<R>,
+    change: Change<'_, R>,
+) -> Result<()> {
+    R::check_change(conn, context, &change)?;
+    R::Policy::record(conn, context, change)
+}
+
+/// Applies change of object `id` and records it with the object before and after it. Returns `None` if object is
+/// not changed
+fn apply<R: Crud>(
+    conn: &mut diesel::PgConnection,
+    context: &Context<R>,
+    action: Action,
+    id: &R::Id,
+    change: impl FnOnce(&mut diesel::PgConnection) -> Result<Option<R>>,
+) -> Result<Option<R>> {
+    let before = R::get(conn, id)?;
+    let r = change(conn)?;
+    if let Some(v) = &r {
+        let after = match action {
+            Action::Delete if R::deleted_at().is_none() => None,
+            _ => Some(v),
+        };
+        record::<R>(
+            conn,
+            context,
+            Change {
+                action,
+                id,
+                before: before.as_ref(),
+                after,
+            },
+        )?;
+    }
+    Ok(r)
+}
+
+/// Object response with version in `ETag`
+fn object<R: Crud>(v: R) -> APIObject<R> {
+    let version = v.version();
+    APIObject::new(v).version(version)
+}
+
+/// `If-Match` request header
+fn if_match(req: &actix_web::HttpRequest) -> actix_web::Result<Option<header::IfMatch>> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+    header::IfMatch::parse(req)
+        .map(Some)
+        .map_err(|_| actix_web::error::ErrorBadRequest("Invalid If-Match header"))
+}
+
+/// State of object before change
+enum Current<R> {
+    /// Can be changed
+    Valid,
+    /// Does not exist or is soft-deleted
+    Missing,
+    /// Version does not match `If-Match`
+    Changed(R),
+}
+
+fn current<R: Crud>(
+    conn: &mut diesel::PgConnection,
+    id: &R::Id,
+    if_match: Option<&header::IfMatch>,
+) -> Result<Current<R>> {
+    if !R::VERSIONED && R::deleted_at().is_none() {
+        return Ok(Current::Valid);
+    }
+    let object = match R::get(conn, id)? {
+        Some(v) if !v.is_deleted() => v,
+        _ => return Ok(Current::Missing),
+    };
+    let matches = match if_match {
+        Some(header::IfMatch::Items(tags)) if R::VERSIONED => {
+            let version = object.version().unwrap_or_default();
+            tags.iter()
+                .any(|v| crate::response::version_matches(v, &version))
+        }
+        _ => true,
+    };
+    Ok(if matches {
+        Current::Valid
+    } else {
+        Current::Changed(object)
+    })
+}
+
+/// Changed object, 404 if it is missing, or 412 with the current object if its version is changed
+fn changed<R: Crud>(r: Result<Option<R>, R>) -> actix_web::Result<APIObject<R>> {
+    match r {
+        Ok(v) => v.map(object).ok_or_else(not_found),
+        Err(current) => Ok(object(current).status(StatusCode::PRECONDITION_FAILED)),
+    }
+}
+
+/// `include_deleted` query param of getOne
+#[derive(serde::Deserialize)]
+struct DeletedRequest {
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+/// IDs from repeated `id` query param
+struct Ids<R: Crud>(Vec<R::Id>);
+
+/// Values of `id` params of `query`. Other params, e.g. `include_deleted`, are not parsed as IDs
+fn parse_ids<I: DeserializeOwned>(query: &str) -> Result<Vec<I>, serde_urlencoded::de::Error> {
+    let ids = serde_urlencoded::from_str::<Vec<(String, String)>>(query)?
+        .into_iter()
+        .filter(|(k, _)| k == "id")
+        .collect::<Vec<_>>();
+    let ids = serde_urlencoded::to_string(ids).map_err(serde::de::Error::custom)?;
+    Ok(serde_urlencoded::from_str::<Vec<(String, I)>>(&ids)?
+        .into_iter()
+        .map(|(_, v)| v)
+        .collect())
+}
+
+impl<R: Crud> actix_web::FromRequest for Ids<R> {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let r = match parse_ids::<R::Id>(req.query_string()) {
+            Ok(v) => v,
+            Err(err) => {
+                return futures::future::err(actix_web::error::ErrorBadRequest(err.to_string()))
+            }
+        };
+        if r.is_empty() {
+            return futures::future::err(actix_web::error::ErrorBadRequest(
+                "id query param is required",
+            ));
+        }
+        futures::future::ok(Ids(r))
+    }
+}
+
+async fn list<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    pagination: Pagination<R>,
+    filter: Filter<R>,
+) -> actix_web::Result<Page<R>> {
+    check::<R>(&subject, Action::List)?;
+    if filter.include_deleted {
+        check::<R>(&subject, Action::ReadDeleted)?;
+    }
+    pool::<R>(&db)
+        .with_transaction(move |conn| R::list(conn, &pagination, &filter))
+        .await
+        .map_err(db_error)
+}
+
+async fn get_one<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    id: web::Path<R::Id>,
+    deleted: web::Query<DeletedRequest>,
+) -> actix_web::Result<APIObject<R>> {
+    check::<R>(&subject, Action::Read)?;
+    let include_deleted = deleted.include_deleted;
+    if include_deleted {
+        check::<R>(&subject, Action::ReadDeleted)?;
+    }
+    let id = id.into_inner();
+    pool::<R>(&db)
+        .with_transaction(move |conn| R::get(conn, &id))
+        .await
+        .map_err(db_error)?
+        .filter(|v| include_deleted || !v.is_deleted())
+        .map(object)
+        .ok_or_else(not_found)
+}
+
+async fn create<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    data: web::Json<R::Create>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<R>> {
+    check::<R>(&subject, Action::Create)?;
+    let context = R::Policy::context(&subject, &req);
+    let data = data.into_inner();
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            let r = R::create(conn, data)?;
+            record::<R>(
+                conn,
+                &context,
+                Change {
+                    action: Action::Create,
+                    id: &r.id(),
+                    before: None,
+                    after: Some(&r),
+                },
+            )?;
+            Ok(r)
+        })
+        .await
+        .map(object)
+        .map_err(db_error)
+}
+
+async fn update<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    id: web::Path<R::Id>,
+    data: web::Json<R::Update>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<R>> {
+    check::<R>(&subject, Action::Update)?;
+    let if_match = if_match(&req)?;
+    if R::VERSIONED && if_match.is_none() {
+        return Err(actix_web::error::InternalError::new(
+            "If-Match header with object version is required",
+            StatusCode::PRECONDITION_REQUIRED,
+        )
+        .into());
+    }
+    let context = R::Policy::context(&subject, &req);
+    let id = id.into_inner();
+    let data = data.into_inner();
+    let r = pool::<R>(&db)
+        .with_transaction(
+            move |conn| match current::<R>(conn, &id, if_match.as_ref())? {
+                Current::Valid => apply(conn, &context, Action::Update, &id, |conn| {
+                    R::update(conn, &id, data)
+                })
+                .map(Ok),
+                Current::Missing => Ok(Ok(None)),
+                Current::Changed(v) => Ok(Err(v)),
+            },
+        )
+        .await
+        .map_err(db_error)?;
+    changed(r)
+}
+
+async fn update_many<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    ids: Ids<R>,
+    data: web::Json<R::Update>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<Vec<R::Id>>> {
+    check::<R>(&subject, Action::Update)?;
+    let context = R::Policy::context(&subject, &req);
+    let data = data.into_inner();
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            let mut updated = Vec::with_capacity(ids.0.len());
+            for id in ids.0 {
+                if is_deleted::<R>(conn, &id)? {
+                    continue;
+                }
+                let data = data.clone();
+                if apply(conn, &context, Action::Update, &id, |conn| {
+                    R::update(conn, &id, data)
+                })?
+                .is_some()
+                {
+                    updated.push(id)
+                }
+            }
+            Ok(updated)
+        })
+        .await
+        .map(APIObject::new)
+        .map_err(db_error)
+}
+
+async fn delete<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    id: web::Path<R::Id>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<R>> {
+    check::<R>(&subject, Action::Delete)?;
+    let if_match = if_match(&req)?;
+    let context = R::Policy::context(&subject, &req);
+    let id = id.into_inner();
+    let r = pool::<R>(&db)
+        .with_transaction(
+            move |conn| match current::<R>(conn, &id, if_match.as_ref())? {
+                Current::Valid => apply(conn, &context, Action::Delete, &id, |conn| {
+                    R::delete(conn, &id)
+                })
+                .map(Ok),
+                Current::Missing => Ok(Ok(None)),
+                Current::Changed(v) => Ok(Err(v)),
+            },
+        )
+        .await
+        .map_err(db_error)?;
+    changed(r)
+}
+
+async fn delete_many<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    ids: Ids<R>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<Vec<R::Id>>> {
+    check::<R>(&subject, Action::Delete)?;
+    let context = R::Policy::context(&subject, &req);
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            let mut deleted = Vec::with_capacity(ids.0.len());
+            for id in ids.0 {
+                if is_deleted::<R>(conn, &id)? {
+                    continue;
+                }
+                if apply(conn, &context, Action::Delete, &id, |conn| {
+                    R::delete(conn, &id)
+                })?
+                .is_some()
+                {
+                    deleted.push(id)
+                }
+            }
+            Ok(deleted)
+        })
+        .await
+        .map(APIObject::new)
+        .map_err(db_error)
+}
+
+async fn restore<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    id: web::Path<R::Id>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<R>> {
+    check::<R>(&subject, Action::Restore)?;
+    let context = R::Policy::context(&subject, &req);
+    let id = id.into_inner();
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            apply(conn, &context, Action::Restore, &id, |conn| {
+                R::restore(conn, &id)
+            })
+        })
+        .await
+        .map_err(db_error)?
+        .map(object)
+        .ok_or_else(not_found)
+}
+
+async fn restore_many<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    ids: Ids<R>,
+    req: actix_web::HttpRequest,
+) -> actix_web::Result<APIObject<Vec<R::Id>>> {
+    check::<R>(&subject, Action::Restore)?;
+    let context = R::Policy::context(&subject, &req);
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            let mut restored = Vec::with_capacity(ids.0.len());
+            for id in ids.0 {
+                if apply(conn, &context, Action::Restore, &id, |conn| {
+                    R::restore(conn, &id)
+                })?
+                .is_some()
+                {
+                    restored.push(id)
+                }
+            }
+            Ok(restored)
+        })
+        .await
+        .map(APIObject::new)
+        .map_err(db_error)
+}
+
+async fn import<R: Crud>(
+    db: Data<R::Db>,
+    subject: Subject<R>,
+    req: actix_web::HttpRequest,
+    query: web::Query<ImportRequest>,
+    payload: web::Payload,
+) -> actix_web::Result<APIObject<ImportReport<R::Id>>> {
+    check::<R>(&subject, Action::Create)?;
+    let may_update = R::Policy::allowed(&subject, Action::Update);
+    let context = R::Policy::context(&subject, &req);
+    let file = ImportFile::new(&req, &query, payload).await?;
+    let rows = file
+        .rows::<R::Create>()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    let dry_run = query.dry_run;
+    pool::<R>(&db)
+        .with_transaction(move |conn| {
+            crate::import::run(conn, rows, dry_run, |conn, data| {
+                let (action, before, after) = match R::import(conn, data)? {
+                    ImportedObject::Created(after) => (Action::Create, None, after),
+                    ImportedObject::Updated { .. } if !may_update => {
+                        return Err(
+                            Invalid("Object exists, update is not allowed".to_owned()).into()
+                        )
+                    }
+                    ImportedObject::Updated { before, after } => {
+                        (Action::Update, Some(before), after)
+                    }
+                };
+                let id = after.id();
+                record::<R>(
+                    conn,
+                    &context,
+                    Change {
+                        action,
+                        id: &id,
+                        before: before.as_ref(),
+                        after: Some(&after),
+                    },
+                )?;
+                Ok(match before {
+                    Some(_) => Imported::Updated(id),
+                    None => Imported::Created(id),
+                })
+            })
+        })
+        .await
+        .map(APIObject::new)
+        .map_err(db_error)
+}
+
+/// Registers endpoints of resource `R` under `path`
+pub struct CrudResource<R> {
+    path: String,
+    tag: Option<String>,
+    _resource: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: Crud> CrudResource<R> {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            tag: None,
+            _resource: std::marker::PhantomData,
+        }
+    }
+
+    /// OpenAPI tag of the endpoints
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Adds endpoints to the app, returns their OpenAPI documentation
+    pub fn register(self, service_config: &mut web::ServiceConfig) -> OpenApi {
+        let openapi = self.openapi();
+        let _ = service_config
+            .service(
+                web::resource(self.path.as_str())
+                    .route(web::get().to(list::<R>))
+                    .route(web::post().to(create::<R>))
+                    .route(web::put().to(update_many::<R>))
+                    .route(web::delete().to(delete_many::<R>)),
+            )
+            // Before `{id}`, which matches it too
+            .service(
+                web::resource(format!("{}/import", self.path)).route(web::post().to(import::<R>)),
+            );
+        if R::deleted_at().is_some() {
+            let _ = service_config
+                .service(
+                    web::resource(format!("{}/restore", self.path))
+                        .route(web::post().to(restore_many::<R>)),
+                )
+                .service(
+                    web::resource(format!("{}/{{id}}/restore", self.path))
+                        .route(web::post().to(restore::<R>)),
+                );
+        }
+        let _ = service_config.service(
+            web::resource(format!("{}/{{id}}", self.path))
+                // Other methods fall through, so e.g. `POST /resource/action` of other plugins is not answered with 405
+                .guard(
+                    guard::Any(guard::Get())
+                        .or(guard::Put())
+                        .or(guard::Patch())
+                        .or(guard::Delete()),
+                )
+                .route(web::get().to(get_one::<R>))
+                .route(web::put().to(update::<R>))
+                .route(web::patch().to(update::<R>))
+                .route(web::delete().to(delete::<R>)),
+        );
+        openapi
+    }
+
+    fn openapi(&self) -> OpenApi {
+        let (name, schema) = R::schema();
+        let (create_name, create_schema) = R::Create::schema();
+        let (update_name, update_schema) = R::Update::schema();
+        let id = R::id_schema();
+        let object = Ref::from_schema_name(name);
+        let ids = ArrayBuilder::new().items(id.clone()).build();
+        let resource = self.path.rsplit('/').next().unwrap_or_default();
+
+        let operation = |operation_id: &str, action: Action, summary: String| {
+            let mut r = OperationBuilder::new()
+                .operation_id(Some(format!("{resource}_{operation_id}")))
+                .summary(Some(summary))
+                .securities(Some(R::Policy::security(action)))
+                .response("400", ResponseBuilder::new().description("Invalid request"))
+                .response(
+                    "401",
+                    ResponseBuilder::new().description("User not logged in"),
+                )
+                .response(
+                    "403",
+                    ResponseBuilder::new().description("Action is not allowed"),
+                );
+            if let Some(tag) = &self.tag {
+                r = r.tag(tag.clone())
+            }
+            r
+        };
+        let json = |description: &str, schema: RefOr<Schema>| {
+            ResponseBuilder::new().description(description).content(
+                "application/json",
+                ContentBuilder::new().schema(schema).build(),
+            )
+        };
+        let body = |schema: &str| {
+            Some(
+                RequestBodyBuilder::new()
+                    .required(Some(Required::True))
+                    .content(
+                        "application/json",
+                        ContentBuilder::new()
+                            .schema(Ref::from_schema_name(schema))
+                            .build(),
+                    )
+                    .build(),
+            )
+        };
+        let id_param = |parameter_in: ParameterIn, description: &str| -> Parameter {
+            ParameterBuilder::new()
+                .name("id")
+                .parameter_in(parameter_in)
+                .required(Required::True)
+                .description(Some(description))
+                .schema(Some(id.clone()))
+                .build()
+        };
+        let object_response = |description: &str| {
+            let r = json(description, object.clone().into());
+            if !R::VERSIONED {
+                return r;
+            }
+            r.header(
+                "ETag",
+                HeaderBuilder::new()
+                    .schema(ObjectBuilder::new().schema_type(SchemaType::String))
+                    .description(Some("Version of object"))
+                    .build(),
+            )
+        };
+        // update requires `If-Match`, delete checks it if set
+        let precondition = |op: OperationBuilder, required: bool| {
+            if !R::VERSIONED {
+                return op;
+            }
+            let op = op
+                .parameter(
+                    ParameterBuilder::new()
+                        .name("If-Match")
+                        .parameter_in(ParameterIn::Header)
+                        .required(if required {
+                            Required::True
+                        } else {
+                            Required::False
+                        })
+                        .description(Some("Version of object from `ETag` of the last response"))
+                        .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String)))
+                        .build(),
+                )
+                .response(
+                    "412",
+                    object_response("Object is changed by someone else, current object"),
+                );
+            if required {
+                op.response(
+                    "428",
+                    ResponseBuilder::new().description("If-Match header is missing"),
+                )
+            } else {
+                op
+            }
+        };
+        let not_found = ResponseBuilder::new()
+            .description("Object not found")
+            .build();
+        let conflict = ResponseBuilder::new()
+            .description("Constraint violation")
+            .build();
+
+        let mut list_params = Vec::new();
+        list_params.extend(PaginatedRequest::into_params(|| Some(ParameterIn::Query)));
+        list_params.extend(CursorRequest::into_params(|| Some(ParameterIn::Query)));
+        list_params.extend(SortRequest::into_params(|| Some(ParameterIn::Query)));
+        list_params.extend(Filter::<R>::into_params(|| Some(ParameterIn::Query)));
+
+        let collection = PathItemBuilder::new()
+            .operation(
+                PathItemType::Get,
+                operation("list", Action::List, format!("List of {resource}"))
+                    .parameters(Some(list_params))
+                    .response(
+                        "200",
+                        json(
+                            "List of objects",
+                            ArrayBuilder::new().items(object.clone()).build().into(),
+                        )
+                        .header(
+                            "X-Total-Count",
+                            HeaderBuilder::new()
+                                .schema(ObjectBuilder::new().schema_type(SchemaType::Integer))
+                                .description(Some("Total count of objects"))
+                                .build(),
+                        )
+                        .header(
+                            "Link",
+                            HeaderBuilder::new()
+                                .description(Some(
+                                    "Links to next and previous pages of cursor pagination",
+                                ))
+                                .build(),
+                        ),
+                    ),
+            )
+            .operation(
+                PathItemType::Post,
+                operation("create", Action::Create, format!("Create {resource}"))
+                    .request_body(body(create_name))
+                    .response("200", object_response("Created object"))
+                    .response("409", conflict.clone()),
+            )
+            .operation(
+                PathItemType::Put,
+                operation(
+                    "update_many",
+                    Action::Update,
+                    format!("Update several {resource}"),
+                )
+                .parameter(id_param(
+                    ParameterIn::Query,
+                    "IDs of objects, repeat the param for each object",
+                ))
+                .request_body(body(update_name))
+                .response("200", json("IDs of updated objects", ids.clone().into()))
+                .response("409", conflict.clone()),
+            )
+            .operation(
+                PathItemType::Delete,
+                operation(
+                    "delete_many",
+                    Action::Delete,
+                    format!("Delete several {resource}"),
+                )
+                .parameter(id_param(
+                    ParameterIn::Query,
+                    "IDs of objects, repeat the param for each object",
+                ))
+                .response("200", json("IDs of deleted objects", ids.clone().into()))
+                .response("409", conflict.clone()),
+            )
+            .build();
+
+        let update = |operation_id: &str| {
+            precondition(
+                operation(operation_id, Action::Update, format!("Update {resource}"))
+                    .parameter(id_param(ParameterIn::Path, "Object ID"))
+                    .request_body(body(update_name))
+                    .response("200", object_response("Updated object"))
+                    .response("404", not_found.clone())
+                    .response("409", conflict.clone()),
+                true,
+            )
+        };
+        let item = PathItemBuilder::new()
+            .operation(
+                PathItemType::Get,
+                operation("get", Action::Read, format!("Get {resource}"))
+                    .parameter(id_param(ParameterIn::Path, "Object ID"))
+                    // `None` would remove the ID param
+                    .parameters(Some(
+                        R::deleted_at().map(|_| crate::filter::deleted_param(ParameterIn::Query)),
+                    ))
+                    .response("200", object_response("Object"))
+                    .response("404", not_found.clone()),
+            )
+            .operation(PathItemType::Put, update("update"))
+            .operation(PathItemType::Patch, update("patch"))
+            .operation(
+                PathItemType::Delete,
+                precondition(
+                    operation("delete", Action::Delete, format!("Delete {resource}"))
+                        .parameter(id_param(ParameterIn::Path, "Object ID"))
+                        .response("200", object_response("Deleted object"))
+                        .response("404", not_found.clone())
+                        .response("409", conflict.clone()),
+                    false,
+                ),
+            )
+            .build();
+
+        let file = || {
+            ContentBuilder::new()
+                .schema(Ref::from_schema_name(create_name))
+                .build()
+        };
+        let import = PathItemBuilder::new()
+            .operation(
+                PathItemType::Post,
+                operation("import", Action::Create, format!("Import {resource} from file"))
+                    .description(Some(
+                        "Rows are fields of the created object, CSV must have header. Failed rows are skipped, \
+                         the rest of the file is imported",
+                    ))
+                    .parameters(Some(ImportRequest::into_params(|| {
+                        Some(ParameterIn::Query)
+                    })))
+                    .request_body(Some(
+                        RequestBodyBuilder::new()
+                            .required(Some(Required::True))
+                            .content("text/csv", file())
+                            .content("application/jsonl", file())
+                            .build(),
+                    ))
+                    .response(
+                        "200",
+                        json(
+                            "Result of import of each row",
+                            crate::import::report_schema(id.clone()).into(),
+                        ),
+                    )
+                    .response(
+                        "413",
+                        ResponseBuilder::new().description("File is too large"),
+                    )
+                    .response(
+                        "415",
+                        ResponseBuilder::new().description("Unsupported file format"),
+                    ),
+            )
+            .build();
+
+        let mut paths = PathsBuilder::new()
+            .path(self.path.as_str(), collection)
+            .path(format!("{}/import", self.path), import)
+            .path(format!("{}/{{id}}", self.path), item);
+        if R::deleted_at().is_some() {
+            paths = paths
+                .path(
+                    format!("{}/restore", self.path),
+                    PathItemBuilder::new()
+                        .operation(
+                            PathItemType::Post,
+                            operation(
+                                "restore_many",
+                                Action::Restore,
+                                format!("Restore several deleted {resource}"),
+                            )
+                            .parameter(id_param(
+                                ParameterIn::Query,
+                                "IDs of objects, repeat the param for each object",
+                            ))
+                            .response("200", json("IDs of restored objects", ids.into()))
+                            .response("409", conflict.clone()),
+                        )
+                        .build(),
+                )
+                .path(
+                    format!("{}/{{id}}/restore", self.path),
+                    PathItemBuilder::new()
+                        .operation(
+                            PathItemType::Post,
+                            operation(
+                                "restore",
+                                Action::Restore,
+                                format!("Restore deleted {resource}"),
+                            )
+                            .parameter(id_param(ParameterIn::Path, "Object ID"))
+                            .response("200", object_response("Restored object"))
+                            .response("404", not_found.clone())
+                            .response("409", conflict.clone()),
+                        )
+                        .build(),
+                );
+        }
+
+        OpenApiBuilder::new()
+            .paths(paths)
+            .components(Some(
+                ComponentsBuilder::new()
+                    .schema(name, schema)
+                    .schema(create_name, create_schema)
+                    .schema(update_name, update_schema)
+                    .build(),
+            ))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_parsed_from_id_params_only() {
+        let ids = parse_ids::<i64>("id=1&include_deleted=true&id=2").unwrap();
+        assert_eq!(ids, [1, 2]);
+        assert!(parse_ids::<i64>("include_deleted=true").unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_id_is_error() {
+        assert!(parse_ids::<i64>("id=1&id=x").is_err());
+    }
+}
@@ -0,0 +1,545 @@
+//! Keyset (cursor) pagination.
+//!
+//! Instead of `OFFSET`, the next page is selected by the sort key of the last record of the previous one, so deep
+//! pages are as fast as the first one. The key is passed in an opaque signed `_cursor` param, links to the next and
+//! previous pages are returned in `Link` response header. Sorting is allowed only by columns created with
+//! [`SortColumn::keyset`](crate::sort::SortColumn::keyset).
+//!
+//! [`Pagination`] selects cursor pagination when request has `_cursor` or `_limit` param, and falls back to the
+//! usual `_start`/`_end` pagination otherwise, so the same endpoint serves react-admin and clients of big lists.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use diesel::query_dsl::methods::{FilterDsl, LimitDsl, LoadQuery, OffsetDsl, ThenOrderDsl};
+use diesel::{BoolExpressionMethods, PgConnection, QueryResult, RunQueryDsl};
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::db::{Countable, Counted, Paginate, Paginated};
+use crate::filter::FilterExpression;
+use crate::request_list::ProcessedPaginatedRequest;
+use crate::sort::{Cmp, Order, OrderExpression, Sort, SortColumn, Sortable};
+
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+/// Key used to sign cursors. Register it as app data, cursor pagination requests are rejected with 400 without it
+pub struct CursorKey(Vec<u8>);
+
+impl CursorKey {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+
+    fn mac(&self, payload: &[u8]) -> HmacSha256 {
+        // this unwrap never fails, HMAC accepts keys of any length
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.0).unwrap();
+        mac.update(payload);
+        mac
+    }
+}
+
+fn default_limit() -> u64 {
+    10
+}
+
+/// Cursor pagination request data
+#[derive(Deserialize, IntoParams)]
+pub struct CursorRequest {
+    /// Opaque cursor from `Link` header of the previous response
+    #[serde(rename = "_cursor", default)]
+    pub cursor: Option<String>,
+    /// Maximum number of records in output
+    #[serde(rename = "_limit", default = "default_limit")]
+    pub limit: u64,
+}
+
+/// How total number of records is calculated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TotalCount {
+    /// `COUNT(*)` of the filtered query
+    Exact,
+    /// Planner estimation of the number of rows in the table, see [`crate::db::estimated_count`]. Filters are not
+    /// taken into account, including exclusion of soft deleted rows
+    Estimated(&'static str),
+    /// Total count is not calculated, `X-Total-Count` header is not sent
+    Skip,
+}
+
+impl TotalCount {
+    fn load<'a, Q>(self, conn: &mut PgConnection, query: Q) -> QueryResult<Option<i64>>
+    where
+        Counted<Q>: LoadQuery<'a, PgConnection, i64>,
+    {
+        match self {
+            TotalCount::Exact => query.counted().get_result(conn).map(Some),
+            TotalCount::Estimated(table) => crate::db::estimated_count(conn, table),
+            TotalCount::Skip => Ok(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    #[serde(rename = "n")]
+    Next,
+    #[serde(rename = "p")]
+    Prev,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Cursor {
+    #[serde(rename = "s")]
+    sort: String,
+    #[serde(rename = "o")]
+    order: Order,
+    #[serde(rename = "d")]
+    direction: Direction,
+    /// Value of the sort column
+    #[serde(rename = "k")]
+    key: serde_json::Value,
+    /// Value of the unique column
+    #[serde(rename = "u")]
+    unique: serde_json::Value,
+}
+
+impl Cursor {
+    fn encode(&self, key: &CursorKey) -> String {
+        // this unwrap never fails, cursor contains only strings and JSON values
+        let payload = serde_json::to_vec(self).unwrap();
+        let signature = key.mac(&payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    fn decode(value: &str, key: &CursorKey) -> Option<Self> {
+        let (payload, signature) = value.split_once('.')?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        key.mac(&payload).verify_slice(&signature).ok()?;
+        serde_json::from_slice(&payload).ok()
+    }
+
+    /// Comparison of records after the cursor in its direction with the cursor
+    fn cmp(&self) -> Cmp {
+        match (self.order, self.direction) {
+            (Order::Asc, Direction::Next) | (Order::Desc, Direction::Prev) => Cmp::Gt,
+            (Order::Desc, Direction::Next) | (Order::Asc, Direction::Prev) => Cmp::Lt,
+        }
+    }
+
+    /// Condition selecting records after the cursor in its direction
+    fn condition<QS: 'static>(
+        &self,
+        column: &SortColumn<QS>,
+        unique: &SortColumn<QS>,
+    ) -> Result<FilterExpression<QS>, String> {
+        let cmp = self.cmp();
+        let after_unique = unique.compare(cmp, &self.unique)?;
+        if column.name == unique.name {
+            return Ok(after_unique);
+        }
+        let after = column.compare(cmp, &self.key)?;
+        let same = column.compare(Cmp::Eq, &self.key)?;
+        Ok(Box::new(after.or(same.and(after_unique))))
+    }
+}
+
+/// Validated cursor pagination request for resource `R`
+pub struct CursorPagination<R> {
+    pub sort: Sort<R>,
+    pub limit: i64,
+    cursor: Option<Cursor>,
+}
+
+/// Pagination request for resource `R`: cursor pagination if request has `_cursor` or `_limit` param, `_start`/`_end`
+/// otherwise
+pub enum Pagination<R> {
+    Offset(ProcessedPaginatedRequest, Sort<R>),
+    Cursor(CursorPagination<R>),
+}
+
+/// Page of records, responds with JSON list, `X-Total-Count` and `Link` headers
+pub struct Page<DATA> {
+    pub list: Vec<DATA>,
+    pub total_count: Option<i64>,
+    links: Option<Links>,
+}
+
+struct Links {
+    sort: &'static str,
+    unique: &'static str,
+    order: Order,
+    next: bool,
+    prev: bool,
+}
+
+impl<R: Sortable> CursorPagination<R> {
+    /// Loads page of records. `query` must return the same filtered query on every call, it is called twice when
+    /// exact total count is requested
+    pub fn load<'a, U, Q>(
+        &self,
+        conn: &mut PgConnection,
+        query: impl Fn() -> Q,
+        total_count: TotalCount,
+    ) -> QueryResult<Page<U>>
+    where
+        Q: FilterDsl<FilterExpression<R::QuerySource>, Output = Q>
+            + ThenOrderDsl<OrderExpression<R::QuerySource>, Output = Q>
+            + LimitDsl<Output = Q>
+            + RunQueryDsl<PgConnection>
+            + LoadQuery<'a, PgConnection, U>,
+        Counted<Q>: LoadQuery<'a, PgConnection, i64>,
+    {
+        let columns = R::sort_columns();
+        let direction = self
+            .cursor
+            .as_ref()
+            .map(|v| v.direction)
+            .unwrap_or(Direction::Next);
+
+        let mut q = query();
+        if let Some(cursor) = &self.cursor {
+            let condition = columns
+                .iter()
+                .find(|v| v.name == self.sort.field)
+                .ok_or_else(|| format!("Unknown sort column {:?}", self.sort.field))
+                .and_then(|column| cursor.condition(column, &columns[0]))
+                .map_err(|err| diesel::result::Error::QueryBuilderError(err.into()))?;
+            q = FilterDsl::filter(q, condition);
+        }
+        q = match direction {
+            Direction::Next => self.sort.apply(q),
+            Direction::Prev => Sort::<R>::new(self.sort.field, self.sort.order.reverse()).apply(q),
+        };
+        q = LimitDsl::limit(q, self.limit + 1);
+
+        let mut list: Vec<U> = q.load(conn)?;
+        let more = list.len() as i64 > self.limit;
+        list.truncate(self.limit as usize);
+        let (next, prev) = match (direction, &self.cursor) {
+            (Direction::Next, None) => (more, false),
+            (Direction::Next, Some(_)) => (more, true),
+            (Direction::Prev, _) => {
+                list.reverse();
+                (true, more)
+            }
+        };
+
+        Ok(Page {
+            list,
+            total_count: total_count.load(conn, query())?,
+            links: Some(Links {
+                sort: self.sort.field,
+                unique: columns[0].name,
+                order: self.sort.order,
+                next,
+                prev,
+            }),
+        })
+    }
+}
+
+impl<R: Sortable> Pagination<R> {
+    /// Loads page of records, see [`CursorPagination::load`]
+    pub fn load<'a, U, Q>(
+        &self,
+        conn: &mut PgConnection,
+        query: impl Fn() -> Q,
+        total_count: TotalCount,
+    ) -> QueryResult<Page<U>>
+    where
+        Q: FilterDsl<FilterExpression<R::QuerySource>, Output = Q>
+            + ThenOrderDsl<OrderExpression<R::QuerySource>, Output = Q>
+            + LimitDsl<Output = Q>
+            + OffsetDsl<Output = Q>
+            + RunQueryDsl<PgConnection>
+            + LoadQuery<'a, PgConnection, U>,
+        Counted<Q>: LoadQuery<'a, PgConnection, i64>,
+        Paginated<Q>: LoadQuery<'a, PgConnection, (U, i64)>,
+    {
+        match self {
+            Pagination::Cursor(pagination) => pagination.load(conn, query, total_count),
+            Pagination::Offset(pagination, sort) if total_count == TotalCount::Exact => {
+                let (list, count) = sort
+                    .apply(query())
+                    .paginate(pagination.offset, pagination.limit)
+                    .load_and_count_pages(conn)?;
+                Ok(Page::new(list, Some(count)))
+            }
+            Pagination::Offset(pagination, sort) => {
+                let q = OffsetDsl::offset(sort.apply(query()), pagination.offset);
+                let list = LimitDsl::limit(q, pagination.limit).load(conn)?;
+                Ok(Page::new(list, total_count.load(conn, query())?))
+            }
+        }
+    }
+}
+
+fn bad_request<T>(err: impl Into<String>) -> futures::future::Ready<actix_web::Result<T>> {
+    futures::future::err(actix_web::error::ErrorBadRequest(err.into()))
+}
+
+impl<R: Sortable> actix_web::FromRequest for CursorPagination<R> {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = req.query_string();
+        let query = match serde_urlencoded::from_str::<CursorRequest>(query) {
+            Ok(v) => v,
+            Err(err) => return bad_request(err.to_string()),
+        };
+
+        if query.limit == 0 || query.limit > 1000 {
+            return bad_request("_limit query param must be between 1 and 1000");
+        }
+
+        let key = match req.app_data::<actix_web::web::Data<CursorKey>>() {
+            Some(v) => v,
+            None => {
+                return bad_request(
+                    "Cursor pagination requires DB encryption key, use _start and _end",
+                )
+            }
+        };
+
+        let columns = R::sort_columns();
+        let cursor = match query.cursor.map(|v| Cursor::decode(&v, key)) {
+            None => None,
+            Some(None) => return bad_request("Invalid _cursor query param"),
+            Some(Some(v)) => Some(v),
+        };
+        let sort = match &cursor {
+            Some(cursor) => match columns.iter().find(|v| v.name == cursor.sort) {
+                Some(column) => Sort::new(column.name, cursor.order),
+                None => return bad_request("Invalid _cursor query param"),
+            },
+            None => {
+                match <Sort<R> as actix_web::FromRequest>::from_request(req, payload).into_inner() {
+                    Ok(v) => v,
+                    Err(err) => return futures::future::err(err),
+                }
+            }
+        };
+
+        let column = columns.iter().find(|v| v.name == sort.field);
+        match column {
+            Some(column) if column.supports_keyset() && columns[0].supports_keyset() => {
+                if let Some(cursor) = &cursor {
+                    if let Err(err) = cursor.condition(column, &columns[0]) {
+                        return bad_request(err);
+                    }
+                }
+            }
+            _ => {
+                let allowed: Vec<_> = columns
+                    .iter()
+                    .filter(|v| v.supports_keyset())
+                    .map(|v| v.name)
+                    .collect();
+                return bad_request(format!(
+                    "Cursor pagination cannot be sorted by {:?}, allowed _sort values are: {}",
+                    sort.field,
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        futures::future::ok(CursorPagination {
+            sort,
+            limit: query.limit as i64,
+            cursor,
+        })
+    }
+}
+
+impl<R: Sortable> actix_web::FromRequest for Pagination<R> {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = req.query_string();
+        let cursor = match serde_urlencoded::from_str::<Vec<(String, String)>>(query) {
+            Ok(v) => v.iter().any(|(k, _)| k == "_cursor" || k == "_limit"),
+            Err(err) => return bad_request(err.to_string()),
+        };
+
+        let r = if cursor {
+            CursorPagination::from_request(req, payload)
+                .into_inner()
+                .map(Pagination::Cursor)
+        } else {
+            ProcessedPaginatedRequest::from_request(req, payload)
+                .into_inner()
+                .and_then(|pagination| {
+                    Sort::from_request(req, payload)
+                        .into_inner()
+                        .map(|sort| Pagination::Offset(pagination, sort))
+                })
+        };
+        futures::future::ready(r)
+    }
+}
+
+impl<DATA> Page<DATA> {
+    pub fn new(list: Vec<DATA>, total_count: Option<i64>) -> Self {
+        Self {
+            list,
+            total_count,
+            links: None,
+        }
+    }
+
+    pub fn map<T>(self, f: impl FnMut(DATA) -> T) -> Page<T> {
+        Page {
+            list: self.list.into_iter().map(f).collect(),
+            total_count: self.total_count,
+            links: self.links,
+        }
+    }
+}
+
+impl Links {
+    fn link(
+        &self,
+        req: &actix_web::HttpRequest,
+        key: &CursorKey,
+        direction: Direction,
+        item: &serde_json::Value,
+    ) -> Option<String> {
+        let cursor = Cursor {
+            sort: self.sort.to_owned(),
+            order: self.order,
+            direction,
+            key: item.get(self.sort)?.clone(),
+            unique: item.get(self.unique)?.clone(),
+        };
+        let mut query: Vec<(String, String)> =
+            serde_urlencoded::from_str(req.query_string()).unwrap_or_default();
+        query.retain(|(k, _)| k != "_cursor");
+        query.push(("_cursor".to_owned(), cursor.encode(key)));
+        let rel = match direction {
+            Direction::Next => "next",
+            Direction::Prev => "prev",
+        };
+        Some(format!(
+            "<{}?{}>; rel=\"{rel}\"",
+            req.path(),
+            serde_urlencoded::to_string(query).ok()?
+        ))
+    }
+}
+
+impl<DATA: Serialize> actix_web::Responder for Page<DATA> {
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        let list = match serde_json::to_value(&self.list) {
+            Ok(serde_json::Value::Array(v)) => v,
+            Ok(_) | Err(_) => {
+                return actix_web::HttpResponse::InternalServerError()
+                    .body("Failed to serialize response")
+            }
+        };
+
+        let mut r = actix_web::HttpResponseBuilder::new(actix_web::http::StatusCode::OK);
+        if let Some(total_count) = self.total_count {
+            r.append_header(("X-Total-Count", total_count.to_string()));
+            if self.links.is_none() {
+                let start = crate::response::request_start(req);
+                r.append_header((
+                    actix_web::http::header::CONTENT_RANGE,
+                    crate::response::content_range(start, list.len(), total_count),
+                ));
+            }
+        }
+        let key = req.app_data::<actix_web::web::Data<CursorKey>>();
+        if let (Some(links), Some(key)) = (&self.links, key) {
+            let next = list
+                .last()
+                .filter(|_| links.next)
+                .and_then(|v| links.link(req, key, Direction::Next, v));
+            let prev = list
+                .first()
+                .filter(|_| links.prev)
+                .and_then(|v| links.link(req, key, Direction::Prev, v));
+            let header: Vec<_> = next.into_iter().chain(prev).collect();
+            if !header.is_empty() {
+                r.append_header((actix_web::http::header::LINK, header.join(", ")));
+            }
+        }
+        crate::response::respond(req, r, &list, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(order: Order, direction: Direction) -> Cursor {
+        Cursor {
+            sort: "name".to_owned(),
+            order,
+            direction,
+            key: serde_json::json!("x"),
+            unique: serde_json::json!(7),
+        }
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let key = CursorKey::new("key");
+        let encoded = cursor(Order::Desc, Direction::Prev).encode(&key);
+        let decoded = Cursor::decode(&encoded, &key).unwrap();
+        assert_eq!(decoded.sort, "name");
+        assert_eq!(decoded.order, Order::Desc);
+        assert_eq!(decoded.direction, Direction::Prev);
+        assert_eq!(decoded.key, serde_json::json!("x"));
+        assert_eq!(decoded.unique, serde_json::json!(7));
+    }
+
+    #[test]
+    fn cursor_of_other_key_is_rejected() {
+        let encoded = cursor(Order::Asc, Direction::Next).encode(&CursorKey::new("key"));
+        assert!(Cursor::decode(&encoded, &CursorKey::new("other")).is_none());
+    }
+
+    #[test]
+    fn tampered_cursor_is_rejected() {
+        let key = CursorKey::new("key");
+        let encoded = cursor(Order::Asc, Direction::Next).encode(&key);
+        let (payload, signature) = encoded.split_once('.').unwrap();
+
+        let mut tampered = URL_SAFE_NO_PAD.decode(payload).unwrap();
+        let unique = tampered.iter().rposition(|v| *v == b'7').unwrap();
+        tampered[unique] = b'8';
+        let tampered = format!("{}.{signature}", URL_SAFE_NO_PAD.encode(tampered));
+        assert!(Cursor::decode(&tampered, &key).is_none());
+
+        let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap();
+        signature[0] ^= 1;
+        let tampered = format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature));
+        assert!(Cursor::decode(&tampered, &key).is_none());
+
+        assert!(Cursor::decode(payload, &key).is_none());
+        assert!(Cursor::decode("", &key).is_none());
+    }
+
+    #[test]
+    fn cmp_follows_order_and_direction() {
+        assert_eq!(cursor(Order::Asc, Direction::Next).cmp(), Cmp::Gt);
+        assert_eq!(cursor(Order::Asc, Direction::Prev).cmp(), Cmp::Lt);
+        assert_eq!(cursor(Order::Desc, Direction::Next).cmp(), Cmp::Lt);
+        assert_eq!(cursor(Order::Desc, Direction::Prev).cmp(), Cmp::Gt);
+    }
+}
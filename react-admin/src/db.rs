@@ -32,7 +32,7 @@ pub fn load_and_count_pages<'a, U>(self, conn: &mut PgConnection) -> QueryResult
         Self: LoadQuery<'a, PgConnection, (U, i64)>,
     {
         let results = self.load::<(U, i64)>(conn)?;
-        let total = results.get(0).map(|x| x.1).unwrap_or(0);
+        let total = results.first().map(|x| x.1).unwrap_or(0);
         let records = results.into_iter().map(|x| x.0).collect();
         Ok((records, total))
     }
@@ -58,3 +58,54 @@ fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
         Ok(())
     }
 }
+
+/// `SELECT COUNT(*)` of the query results
+#[derive(Debug, Clone, Copy, QueryId)]
+pub struct Counted<T> {
+    query: T,
+}
+
+pub trait Countable: Sized {
+    fn counted(self) -> Counted<Self>;
+}
+
+impl<T> Countable for T {
+    fn counted(self) -> Counted<Self> {
+        Counted { query: self }
+    }
+}
+
+impl<T: Query> Query for Counted<T> {
+    type SqlType = BigInt;
+}
+
+impl<T> RunQueryDsl<PgConnection> for Counted<T> {}
+
+impl<T> QueryFragment<Pg> for Counted<T>
+where
+    T: QueryFragment<Pg>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("SELECT COUNT(*) FROM (");
+        self.query.walk_ast(out.reborrow())?;
+        out.push_sql(") t");
+        Ok(())
+    }
+}
+
+#[derive(QueryableByName)]
+struct Reltuples {
+    #[diesel(sql_type = BigInt)]
+    reltuples: i64,
+}
+
+/// Number of rows in the table estimated by planner statistics. Returns `None` if table was never analyzed
+pub fn estimated_count(conn: &mut PgConnection, table: &str) -> QueryResult<Option<i64>> {
+    let r: Option<Reltuples> = diesel::sql_query(
+        "SELECT reltuples::bigint AS reltuples FROM pg_class WHERE oid = to_regclass($1)",
+    )
+    .bind::<diesel::sql_types::Text, _>(table)
+    .get_result(conn)
+    .optional()?;
+    Ok(r.map(|v| v.reltuples).filter(|v| *v >= 0))
+}
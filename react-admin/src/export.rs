@@ -0,0 +1,395 @@
+//! Export of lists to files: CSV, JSON Lines and XLSX.
+//!
+//! Rows are loaded one by one and written to a temporary file, so the whole list is never kept in memory. The file is
+//! sent to the client in chunks after the DB transaction ends, so a slow download does not hold a DB connection.
+
+use actix_web::http::header::Header;
+use actix_web::web::Bytes;
+use anyhow::{anyhow, Result};
+use diesel::pg::PgRowByRowLoadingMode;
+use diesel::query_dsl::LoadQuery;
+use diesel::PgConnection;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, Write};
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+/// Size of chunks sent to the client
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Limit of rows per worksheet in XLSX, including header
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+
+/// File format of export
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Xlsx,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Jsonl => "application/jsonl",
+            Format::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+            Format::Xlsx => "xlsx",
+        }
+    }
+
+    pub(crate) fn of_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "text/csv" => Some(Format::Csv),
+            "application/jsonl" | "application/x-ndjson" | "application/x-jsonlines" => {
+                Some(Format::Jsonl)
+            }
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => {
+                Some(Format::Xlsx)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Export request data
+#[derive(serde::Deserialize, IntoParams)]
+pub struct ExportRequest {
+    /// File format: csv, jsonl or xlsx. If not set, it is chosen by `Accept` header, CSV by default
+    #[serde(rename = "_format", default)]
+    #[param(value_type = Option<String>)]
+    pub format: Option<Format>,
+}
+
+impl actix_web::FromRequest for Format {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = req.query_string();
+        let query = match serde_urlencoded::from_str::<ExportRequest>(query) {
+            Ok(v) => v,
+            Err(err) => {
+                return futures::future::err(actix_web::error::ErrorBadRequest(err.to_string()))
+            }
+        };
+        if let Some(format) = query.format {
+            return futures::future::ok(format);
+        }
+
+        let accept = actix_web::http::header::Accept::parse(req)
+            .map(|v| v.ranked())
+            .unwrap_or_default();
+        let format = accept
+            .iter()
+            .find_map(|v| Format::of_mime(v.essence_str()))
+            .unwrap_or(Format::Csv);
+        futures::future::ok(format)
+    }
+}
+
+/// Exported resource
+pub trait Exportable: Serialize {
+    /// Names of exported fields, columns of the file are in this order
+    const COLUMNS: &'static [&'static str];
+}
+
+/// Fields of row in the order of columns, for JSON Lines
+struct Ordered<'a>(
+    &'a [&'static str],
+    &'a serde_json::Map<String, serde_json::Value>,
+);
+
+impl Serialize for Ordered<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for name in self.0 {
+            map.serialize_entry(name, self.1.get(*name).unwrap_or(&serde_json::Value::Null))?;
+        }
+        map.end()
+    }
+}
+
+enum Writer {
+    Csv(Box<csv::Writer<BufWriter<File>>>),
+    Jsonl(BufWriter<File>),
+    Xlsx {
+        workbook: Box<rust_xlsxwriter::Workbook>,
+        row: u32,
+    },
+}
+
+impl Writer {
+    fn new(format: Format, columns: &[&str]) -> Result<Self> {
+        let file = || -> Result<_> { Ok(BufWriter::new(tempfile::tempfile()?)) };
+        let mut writer = match format {
+            Format::Csv => Self::Csv(Box::new(csv::Writer::from_writer(file()?))),
+            Format::Jsonl => Self::Jsonl(file()?),
+            Format::Xlsx => {
+                let mut workbook = rust_xlsxwriter::Workbook::new();
+                let _ = workbook.add_worksheet_with_constant_memory();
+                Self::Xlsx {
+                    workbook: Box::new(workbook),
+                    row: 0,
+                }
+            }
+        };
+        writer.write_header(columns)?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self, columns: &[&str]) -> Result<()> {
+        match self {
+            Self::Csv(w) => w.write_record(columns)?,
+            Self::Jsonl(_) => {}
+            Self::Xlsx { workbook, row } => {
+                let sheet = workbook.worksheet_from_index(0)?;
+                for (col, name) in columns.iter().enumerate() {
+                    let _ = sheet.write_string(*row, col as u16, *name)?;
+                }
+                *row += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, columns: &[&'static str], value: &serde_json::Value) -> Result<()> {
+        let empty = serde_json::Map::new();
+        let object = value.as_object().unwrap_or(&empty);
+        let fields = columns
+            .iter()
+            .map(|v| object.get(*v).unwrap_or(&serde_json::Value::Null));
+        match self {
+            Self::Jsonl(w) => {
+                serde_json::to_writer(&mut *w, &Ordered(columns, object))?;
+                w.write_all(b"\n")?;
+            }
+            Self::Csv(w) => w.write_record(fields.map(csv_cell))?,
+            Self::Xlsx { workbook, row } => {
+                if *row >= XLSX_MAX_ROWS {
+                    return Err(anyhow!("Too many rows for XLSX, use CSV"));
+                }
+                let sheet = workbook.worksheet_from_index(0)?;
+                for (col, field) in fields.enumerate() {
+                    let col = col as u16;
+                    let _ = match field {
+                        serde_json::Value::Null => continue,
+                        serde_json::Value::Bool(v) => sheet.write_boolean(*row, col, *v)?,
+                        serde_json::Value::Number(v) => match v.as_f64() {
+                            Some(v) => sheet.write_number(*row, col, v)?,
+                            None => sheet.write_string(*row, col, v.to_string())?,
+                        },
+                        v => sheet.write_string(*row, col, cell_text(v))?,
+                    };
+                }
+                *row += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the complete file, positioned at the start
+    fn finish(self) -> Result<File> {
+        let mut file = match self {
+            Self::Csv(w) => w
+                .into_inner()
+                .map_err(|err| anyhow!("{}", err.error()))?
+                .into_inner()?,
+            Self::Jsonl(w) => w.into_inner()?,
+            Self::Xlsx { mut workbook, .. } => {
+                let mut file = tempfile::tempfile()?;
+                workbook.save_to_writer(&mut file)?;
+                file
+            }
+        };
+        file.rewind()?;
+        Ok(file)
+    }
+}
+
+/// Strings as is, nested objects and arrays as JSON
+fn cell_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(v) => v.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Cell text for CSV. Text which spreadsheets would take for a formula is prefixed with `'`, so opening the file
+/// does not run it
+fn csv_cell(value: &serde_json::Value) -> String {
+    let text = cell_text(value);
+    match (value, text.chars().next()) {
+        (serde_json::Value::String(_), Some('=' | '+' | '-' | '@' | '\t' | '\r')) => {
+            format!("'{text}")
+        }
+        _ => text,
+    }
+}
+
+/// Reads the next chunk of the file in a blocking task, the stream ends after an error
+async fn read_chunk(file: Option<File>) -> Option<(Result<Bytes, std::io::Error>, Option<File>)> {
+    let mut file = file?;
+    let r = actix_web::web::block(move || {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let n = file.read(&mut chunk)?;
+        chunk.truncate(n);
+        Ok::<_, std::io::Error>((chunk, file))
+    })
+    .await
+    .map_err(std::io::Error::other);
+    match r {
+        Ok(Ok((chunk, _))) if chunk.is_empty() => None,
+        Ok(Ok((chunk, file))) => Some((Ok(Bytes::from(chunk)), Some(file))),
+        Ok(Err(err)) | Err(err) => Some((Err(err), None)),
+    }
+}
+
+/// List exported to file, columns are [`Exportable::COLUMNS`]
+pub struct Export {
+    format: Format,
+    file_name: String,
+    file: File,
+}
+
+impl Export {
+    /// Exports `query` rows, converted with `map`. `query` is run in a separate transaction, it is usually built with
+    /// [`Sort::apply`](crate::sort::Sort::apply) and [`Filter::apply`](crate::filter::Filter::apply). The file is
+    /// written while the transaction is open, so errors are reported with the error status, and sent after it ends
+    pub async fn new<U, Q, R>(
+        pool: Arc<database_pg::Pool>,
+        format: Format,
+        name: &str,
+        query: impl FnOnce() -> Q + Send + 'static,
+        map: impl Fn(U) -> R + Send + 'static,
+    ) -> Result<Self>
+    where
+        Q: LoadQuery<'static, PgConnection, U, PgRowByRowLoadingMode> + 'static,
+        U: 'static,
+        R: Exportable,
+    {
+        let file = pool
+            .with_transaction(move |conn| {
+                let mut writer = Writer::new(format, R::COLUMNS)?;
+                let rows =
+                    diesel::RunQueryDsl::load_iter::<U, PgRowByRowLoadingMode>(query(), conn)?;
+                for row in rows {
+                    writer.write(R::COLUMNS, &serde_json::to_value(map(row?))?)?;
+                }
+                writer.finish()
+            })
+            .await?;
+        Ok(Self {
+            format,
+            file_name: format!("{name}.{}", format.extension()),
+            file,
+        })
+    }
+}
+
+impl actix_web::Responder for Export {
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(self, _req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+
+        let body = futures::stream::unfold(Some(self.file), read_chunk);
+        actix_web::HttpResponse::Ok()
+            .content_type(self.format.content_type())
+            .insert_header((
+                actix_web::http::header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                "Content-Disposition",
+            ))
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(self.file_name)],
+            })
+            .streaming(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const COLUMNS: &[&str] = &["id", "name", "tags"];
+
+    fn export(format: Format, rows: &[serde_json::Value]) -> Vec<u8> {
+        let mut writer = Writer::new(format, COLUMNS).unwrap();
+        for row in rows {
+            writer.write(COLUMNS, row).unwrap();
+        }
+        let mut data = Vec::new();
+        let _ = writer.finish().unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn csv_has_header_and_columns_in_order() {
+        let data = export(
+            Format::Csv,
+            &[
+                json!({"tags": ["a", "b"], "name": "x, y", "id": 1}),
+                json!({"id": 2, "extra": true}),
+            ],
+        );
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "id,name,tags\n1,\"x, y\",\"[\"\"a\"\",\"\"b\"\"]\"\n2,,\n"
+        );
+    }
+
+    #[test]
+    fn csv_of_empty_list_has_header() {
+        assert_eq!(export(Format::Csv, &[]), b"id,name,tags\n");
+    }
+
+    #[test]
+    fn csv_formulas_are_escaped() {
+        let data = export(
+            Format::Csv,
+            &[
+                json!({"id": -1, "name": "=1+2", "tags": "+x"}),
+                json!({"id": 2, "name": "-x", "tags": "@SUM(A1)"}),
+                json!({"id": 3, "name": "\tx", "tags": "a=b"}),
+            ],
+        );
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "id,name,tags\n-1,'=1+2,'+x\n2,'-x,'@SUM(A1)\n3,'\tx,a=b\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_has_columns_in_order() {
+        let data = export(
+            Format::Jsonl,
+            &[json!({"tags": null, "name": "=x", "id": 1, "extra": true})],
+        );
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "{\"id\":1,\"name\":\"=x\",\"tags\":null}\n"
+        );
+    }
+
+    #[test]
+    fn xlsx_is_zip_archive() {
+        let data = export(Format::Xlsx, &[json!({"id": 1, "name": "x"})]);
+        assert!(data.starts_with(b"PK\x03\x04"));
+    }
+}
@@ -0,0 +1,653 @@
+use diesel::dsl;
+use diesel::expression::{AsExpression, BoxableExpression};
+use diesel::pg::Pg;
+use diesel::query_dsl::methods::FilterDsl;
+use diesel::sql_types::{Bool, SqlType, Text};
+use diesel::{BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods};
+use utoipa::openapi::path::{Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, Schema, SchemaFormat, SchemaType};
+use utoipa::openapi::Required;
+use utoipa::IntoParams;
+
+use crate::Resource;
+
+/// Boxed `WHERE` condition for query on `QS`
+pub type FilterExpression<QS> = Box<dyn BoxableExpression<QS, Pg, SqlType = Bool>>;
+
+/// Filter operator, selected by suffix of the query param name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// `field=value`, repeated param matches any of the values
+    Eq,
+    /// `field_ne=value`, repeated param matches none of the values
+    Ne,
+    /// `field_gte=value`
+    Gte,
+    /// `field_lte=value`
+    Lte,
+    /// `field_like=value`, case-insensitive substring match
+    Like,
+}
+
+impl Op {
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Op::Eq => "",
+            Op::Ne => "_ne",
+            Op::Gte => "_gte",
+            Op::Lte => "_lte",
+            Op::Like => "_like",
+        }
+    }
+
+    fn description(self, field: &str) -> String {
+        match self {
+            Op::Eq => format!("Only records with {field} equal to the value. Repeat the param to match any of values"),
+            Op::Ne => format!("Only records with {field} not equal to the value. Repeat the param to exclude several values"),
+            Op::Gte => format!("Only records with {field} greater than or equal to the value"),
+            Op::Lte => format!("Only records with {field} less than or equal to the value"),
+            Op::Like => format!("Only records with {field} containing the value, case-insensitive"),
+        }
+    }
+}
+
+/// Type of filter value. Implement it for own types (like opaque IDs) to use them in filters
+pub trait FilterValue: std::str::FromStr + 'static {
+    /// OpenAPI schema of the value
+    fn schema() -> Schema {
+        ObjectBuilder::new().schema_type(SchemaType::String).into()
+    }
+}
+
+macro_rules! filter_value {
+    ($type:ty, $schema_type:expr) => {
+        filter_value!($type, $schema_type, None);
+    };
+    ($type:ty, $schema_type:expr, $format:expr) => {
+        impl FilterValue for $type {
+            fn schema() -> Schema {
+                ObjectBuilder::new()
+                    .schema_type($schema_type)
+                    .format($format.map(SchemaFormat::KnownFormat))
+                    .into()
+            }
+        }
+    };
+}
+
+filter_value!(String, SchemaType::String);
+filter_value!(bool, SchemaType::Boolean);
+filter_value!(i16, SchemaType::Integer, Some(KnownFormat::Int32));
+filter_value!(i32, SchemaType::Integer, Some(KnownFormat::Int32));
+filter_value!(i64, SchemaType::Integer, Some(KnownFormat::Int64));
+filter_value!(f32, SchemaType::Number, Some(KnownFormat::Float));
+filter_value!(f64, SchemaType::Number, Some(KnownFormat::Double));
+filter_value!(
+    chrono::NaiveDate,
+    SchemaType::String,
+    Some(KnownFormat::Date)
+);
+filter_value!(
+    chrono::NaiveDateTime,
+    SchemaType::String,
+    Some(KnownFormat::DateTime)
+);
+filter_value!(
+    chrono::DateTime<chrono::Utc>,
+    SchemaType::String,
+    Some(KnownFormat::DateTime)
+);
+
+type Builder<QS> = Box<dyn Fn(Op, &[String]) -> Result<FilterExpression<QS>, String>>;
+
+fn parse<T: FilterValue>(field: &str, values: &[String]) -> Result<Vec<T>, String> {
+    values
+        .iter()
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("Invalid value {v:?} of filter {field:?}"))
+        })
+        .collect()
+}
+
+fn single<T>(field: &str, op: Op, mut values: Vec<T>) -> Result<T, String> {
+    match values.len() {
+        1 => Ok(values.remove(0)),
+        _ => Err(format!(
+            "Filter {:?} accepts exactly one value",
+            format!("{field}{}", op.suffix())
+        )),
+    }
+}
+
+/// Column which can be used for filtering
+pub struct FilterColumn<QS> {
+    pub name: &'static str,
+    pub ops: &'static [Op],
+    schema: fn() -> Schema,
+    build: Builder<QS>,
+}
+
+impl<QS: 'static> FilterColumn<QS> {
+    /// Filters by `name` and `name_ne`. Use `column.assume_not_null()` for nullable columns, rows with NULL never
+    /// match then
+    pub fn new<T, C>(name: &'static str, column: C) -> Self
+    where
+        T: FilterValue + AsExpression<C::SqlType>,
+        C: ExpressionMethods + Copy + 'static,
+        C::SqlType: SqlType,
+        dsl::Eq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::NotEq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        Self {
+            name,
+            ops: &[Op::Eq, Op::Ne],
+            schema: T::schema,
+            build: Box::new(move |op, values| {
+                let values = parse::<T>(name, values)?.into_iter();
+                // repeated params are combined with OR (AND for `_ne`), `eq_any` cannot be used generically
+                let r = match op {
+                    Op::Eq => values
+                        .map(|v| -> FilterExpression<QS> { Box::new(column.eq(v)) })
+                        .reduce(|a, b| Box::new(a.or(b))),
+                    Op::Ne => values
+                        .map(|v| -> FilterExpression<QS> { Box::new(column.ne(v)) })
+                        .reduce(|a, b| Box::new(a.and(b))),
+                    op => return Err(format!("Unsupported filter operator {op:?}")),
+                };
+                r.ok_or_else(|| format!("Filter {name:?} requires a value"))
+            }),
+        }
+    }
+
+    /// Filters by `name`, `name_ne`, `name_gte` and `name_lte`
+    pub fn ordered<T, C>(name: &'static str, column: C) -> Self
+    where
+        T: FilterValue + AsExpression<C::SqlType>,
+        C: ExpressionMethods + Copy + 'static,
+        C::SqlType: SqlType,
+        dsl::Eq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::NotEq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::GtEq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::LtEq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        let equality = Self::new::<T, C>(name, column);
+        Self {
+            ops: &[Op::Eq, Op::Ne, Op::Gte, Op::Lte],
+            build: Box::new(move |op, values| match op {
+                Op::Gte => Ok(Box::new(column.ge(single(
+                    name,
+                    op,
+                    parse::<T>(name, values)?,
+                )?))),
+                Op::Lte => Ok(Box::new(column.le(single(
+                    name,
+                    op,
+                    parse::<T>(name, values)?,
+                )?))),
+                op => (equality.build)(op, values),
+            }),
+            ..equality
+        }
+    }
+
+    /// Text column, filters by `name`, `name_ne` and `name_like`
+    pub fn text<C>(name: &'static str, column: C) -> Self
+    where
+        C: ExpressionMethods + PgTextExpressionMethods + Copy + 'static,
+        C::SqlType: SqlType,
+        String: AsExpression<C::SqlType>,
+        dsl::Eq<C, String>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::NotEq<C, String>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        dsl::ILike<C, String>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        let equality = Self::new::<String, C>(name, column);
+        Self {
+            ops: &[Op::Eq, Op::Ne, Op::Like],
+            build: Box::new(move |op, values| match op {
+                Op::Like => {
+                    let value = single(name, op, values.to_vec())?;
+                    Ok(Box::new(column.ilike(like_pattern(&value))))
+                }
+                op => (equality.build)(op, values),
+            }),
+            ..equality
+        }
+    }
+
+    fn param_names(&self) -> impl Iterator<Item = String> + '_ {
+        self.ops
+            .iter()
+            .map(|op| format!("{}{}", self.name, op.suffix()))
+    }
+}
+
+/// `%value%` with `LIKE` wildcards escaped
+fn like_pattern(value: &str) -> String {
+    let mut r = String::with_capacity(value.len() + 2);
+    r.push('%');
+    for c in value.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            r.push('\\');
+        }
+        r.push(c);
+    }
+    r.push('%');
+    r
+}
+
+type Matcher<QS> = Box<dyn Fn(&str) -> FilterExpression<QS>>;
+
+/// Search by `q` param. Records matching any of the configured conditions are returned
+pub struct Search<QS> {
+    matchers: Vec<Matcher<QS>>,
+}
+
+impl<QS: 'static> Default for Search<QS> {
+    fn default() -> Self {
+        Self {
+            matchers: Vec::new(),
+        }
+    }
+}
+
+impl<QS: 'static> Search<QS> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Case-insensitive substring match of text column
+    pub fn ilike<C>(mut self, column: C) -> Self
+    where
+        C: PgTextExpressionMethods + Copy + 'static,
+        dsl::ILike<C, String>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        self.matchers
+            .push(Box::new(move |q| Box::new(column.ilike(like_pattern(q)))));
+        self
+    }
+
+    /// Full-text match of `document` against `plainto_tsquery(config, q)`. `document` is SQL expression of type
+    /// `tsvector`, usually name of a generated column with GIN index on it
+    pub fn tsvector(mut self, config: &'static str, document: &'static str) -> Self {
+        self.matchers.push(Box::new(move |q| {
+            Box::new(
+                dsl::sql::<Bool>(&format!("({document}) @@ plainto_tsquery('{config}', "))
+                    .bind::<Text, _>(q.to_owned())
+                    .sql(")"),
+            )
+        }));
+        self
+    }
+
+    fn build(&self, q: &str) -> Option<FilterExpression<QS>> {
+        self.matchers
+            .iter()
+            .map(|matcher| matcher(q))
+            .reduce(|a, b| Box::new(a.or(b)))
+    }
+}
+
+/// Soft delete column, see [`database_pg::soft_delete`]
+pub struct DeletedAt<QS> {
+    not_deleted: Box<dyn Fn() -> FilterExpression<QS>>,
+}
+
+impl<QS: 'static> DeletedAt<QS> {
+    pub fn new<C>(column: C) -> Self
+    where
+        C: ExpressionMethods + Copy + 'static,
+        dsl::IsNull<C>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        Self {
+            not_deleted: Box::new(move || Box::new(column.is_null())),
+        }
+    }
+}
+
+/// Resource which list can be filtered
+pub trait Filterable: Resource {
+    /// Whitelist of columns allowed for filtering
+    fn filter_columns() -> Vec<FilterColumn<Self::QuerySource>>;
+
+    /// Search by `q` param. Without it `q` is rejected as unknown filter
+    fn search() -> Option<Search<Self::QuerySource>> {
+        None
+    }
+
+    /// Soft delete column. Deleted records are excluded unless `include_deleted=true` is requested
+    fn deleted_at() -> Option<DeletedAt<Self::QuerySource>> {
+        None
+    }
+}
+
+struct Condition {
+    field: &'static str,
+    op: Op,
+    values: Vec<String>,
+}
+
+/// Validated filter request for resource `R`. All query params not starting with `_` are treated as filters
+pub struct Filter<R> {
+    conditions: Vec<Condition>,
+    pub q: Option<String>,
+    /// Soft-deleted records are requested too
+    pub include_deleted: bool,
+    _resource: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: Filterable> Filter<R> {
+    /// No filters and no search in request
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty() && self.q.is_none()
+    }
+
+    /// Adds `WHERE` conditions to the query
+    pub fn apply<Q>(&self, query: Q) -> Q
+    where
+        Q: FilterDsl<FilterExpression<R::QuerySource>, Output = Q>,
+    {
+        let columns = R::filter_columns();
+        let mut query = query;
+        for condition in &self.conditions {
+            let expression = columns
+                .iter()
+                .find(|v| v.name == condition.field)
+                .and_then(|v| (v.build)(condition.op, &condition.values).ok());
+            if let Some(expression) = expression {
+                query = FilterDsl::filter(query, expression);
+            }
+        }
+        if let Some(q) = &self.q {
+            if let Some(expression) = R::search().and_then(|v| v.build(q)) {
+                query = FilterDsl::filter(query, expression);
+            }
+        }
+        if !self.include_deleted {
+            if let Some(deleted_at) = R::deleted_at() {
+                query = FilterDsl::filter(query, (deleted_at.not_deleted)());
+            }
+        }
+        query
+    }
+}
+
+fn find_column<'a, QS>(
+    columns: &'a [FilterColumn<QS>],
+    param: &str,
+) -> Option<(&'a FilterColumn<QS>, Op)> {
+    columns.iter().find_map(|column| {
+        let suffix = param.strip_prefix(column.name)?;
+        column
+            .ops
+            .iter()
+            .find(|op| op.suffix() == suffix)
+            .map(|op| (column, *op))
+    })
+}
+
+impl<R: Filterable> actix_web::FromRequest for Filter<R> {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = req.query_string();
+        let query = match serde_urlencoded::from_str::<Vec<(String, String)>>(query) {
+            Ok(v) => v,
+            Err(err) => {
+                return futures::future::err(actix_web::error::ErrorBadRequest(err.to_string()))
+            }
+        };
+
+        let columns = R::filter_columns();
+        let search = R::search().is_some();
+        let soft_delete = R::deleted_at().is_some();
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut q = None;
+        let mut include_deleted = false;
+        for (param, value) in query {
+            // pagination, sorting and other service params
+            if param.starts_with('_') {
+                continue;
+            }
+            if search && param == "q" {
+                if !value.is_empty() {
+                    q = Some(value);
+                }
+                continue;
+            }
+            if soft_delete && param == "include_deleted" {
+                include_deleted = match value.parse() {
+                    Ok(v) => v,
+                    Err(_) => {
+                        return futures::future::err(actix_web::error::ErrorBadRequest(format!(
+                            "Invalid value {value:?} of include_deleted, expected true or false"
+                        )))
+                    }
+                };
+                continue;
+            }
+            let (column, op) = match find_column(&columns, &param) {
+                Some(v) => v,
+                None => {
+                    let mut allowed: Vec<_> =
+                        columns.iter().flat_map(|v| v.param_names()).collect();
+                    if search {
+                        allowed.push("q".to_owned());
+                    }
+                    if soft_delete {
+                        allowed.push("include_deleted".to_owned());
+                    }
+                    return futures::future::err(actix_web::error::ErrorBadRequest(format!(
+                        "Unknown filter {param:?}, allowed filters are: {}",
+                        allowed.join(", ")
+                    )));
+                }
+            };
+            match conditions
+                .iter_mut()
+                .find(|v| v.field == column.name && v.op == op)
+            {
+                Some(condition) => condition.values.push(value),
+                None => conditions.push(Condition {
+                    field: column.name,
+                    op,
+                    values: vec![value],
+                }),
+            }
+        }
+
+        for condition in &conditions {
+            let column = columns.iter().find(|v| v.name == condition.field);
+            if let Some(Err(err)) = column.map(|v| (v.build)(condition.op, &condition.values)) {
+                return futures::future::err(actix_web::error::ErrorBadRequest(err));
+            }
+        }
+
+        futures::future::ok(Filter {
+            conditions,
+            q,
+            include_deleted,
+            _resource: std::marker::PhantomData,
+        })
+    }
+}
+
+/// `include_deleted` query param
+pub(crate) fn deleted_param(parameter_in: ParameterIn) -> Parameter {
+    ParameterBuilder::new()
+        .name("include_deleted")
+        .parameter_in(parameter_in)
+        .description(Some("Include soft-deleted records"))
+        .required(Required::False)
+        .schema(Some(ObjectBuilder::new().schema_type(SchemaType::Boolean)))
+        .build()
+}
+
+impl<R: Filterable> IntoParams for Filter<R> {
+    fn into_params(parameter_in_provider: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        let parameter_in = parameter_in_provider().unwrap_or(ParameterIn::Query);
+        let mut r = Vec::new();
+        for column in R::filter_columns() {
+            for op in column.ops {
+                r.push(
+                    ParameterBuilder::new()
+                        .name(format!("{}{}", column.name, op.suffix()))
+                        .parameter_in(parameter_in.clone())
+                        .description(Some(op.description(column.name)))
+                        .required(Required::False)
+                        .schema(Some((column.schema)()))
+                        .build(),
+                )
+            }
+        }
+        if R::search().is_some() {
+            r.push(
+                ParameterBuilder::new()
+                    .name("q")
+                    .parameter_in(parameter_in.clone())
+                    .description(Some("Full-text search"))
+                    .required(Required::False)
+                    .schema(Some(ObjectBuilder::new().schema_type(SchemaType::String)))
+                    .build(),
+            )
+        }
+        if R::deleted_at().is_some() {
+            r.push(deleted_param(parameter_in))
+        }
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::FromRequest;
+    use diesel::query_dsl::QueryDsl;
+
+    diesel::table! {
+        item (id) {
+            id -> Int8,
+            name -> Text,
+            count -> Int4,
+            deleted_at -> Nullable<Timestamptz>,
+        }
+    }
+
+    struct Item;
+
+    impl Resource for Item {
+        type QuerySource = item::table;
+    }
+
+    impl Filterable for Item {
+        fn filter_columns() -> Vec<FilterColumn<item::table>> {
+            vec![
+                FilterColumn::new::<i64, _>("id", item::id),
+                FilterColumn::text("name", item::name),
+                FilterColumn::ordered::<i32, _>("count", item::count),
+            ]
+        }
+
+        fn search() -> Option<Search<item::table>> {
+            Some(Search::new().ilike(item::name))
+        }
+
+        fn deleted_at() -> Option<DeletedAt<item::table>> {
+            Some(DeletedAt::new(item::deleted_at))
+        }
+    }
+
+    fn filter(query: &str) -> actix_web::Result<Filter<Item>> {
+        let req =
+            actix_web::test::TestRequest::with_uri(&format!("/item?{query}")).to_http_request();
+        Filter::<Item>::from_request(&req, &mut actix_web::dev::Payload::None).into_inner()
+    }
+
+    fn sql(query: &str) -> String {
+        let query = filter(query).unwrap().apply(item::table.into_boxed());
+        diesel::debug_query::<Pg, _>(&query).to_string()
+    }
+
+    fn bad_request(query: &str) -> String {
+        let err = filter(query).err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            actix_web::http::StatusCode::BAD_REQUEST
+        );
+        err.to_string()
+    }
+
+    #[test]
+    fn column_is_found_by_suffix_of_operator() {
+        let columns = Item::filter_columns();
+        let found = |param| find_column(&columns, param).map(|(column, op)| (column.name, op));
+        assert_eq!(found("name"), Some(("name", Op::Eq)));
+        assert_eq!(found("name_ne"), Some(("name", Op::Ne)));
+        assert_eq!(found("name_like"), Some(("name", Op::Like)));
+        assert_eq!(found("count_gte"), Some(("count", Op::Gte)));
+        assert_eq!(found("count_lte"), Some(("count", Op::Lte)));
+        // operators which column does not support
+        assert_eq!(found("name_gte"), None);
+        assert_eq!(found("count_like"), None);
+        assert_eq!(found("id_lte"), None);
+        assert_eq!(found("nam"), None);
+        assert_eq!(found("name_"), None);
+        assert_eq!(found("names"), None);
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(like_pattern("abc"), "%abc%");
+        assert_eq!(like_pattern("100%"), "%100\\%%");
+        assert_eq!(like_pattern("a_b"), "%a\\_b%");
+        assert_eq!(like_pattern("a\\b"), "%a\\\\b%");
+        assert_eq!(like_pattern(""), "%%");
+    }
+
+    #[test]
+    fn unknown_filter_is_rejected() {
+        let err = bad_request("secret=1");
+        assert!(err.contains("Unknown filter \"secret\""), "{err}");
+        assert!(err.contains("name_like"), "{err}");
+        assert!(err.contains("include_deleted"), "{err}");
+        assert!(bad_request("name_gte=a").contains("Unknown filter"));
+    }
+
+    #[test]
+    fn invalid_value_is_rejected() {
+        assert!(bad_request("count=x").contains("Invalid value \"x\" of filter \"count\""));
+        assert!(bad_request("count_gte=1&count_gte=2").contains("accepts exactly one value"));
+        assert!(bad_request("include_deleted=yes").contains("include_deleted"));
+    }
+
+    #[test]
+    fn service_params_are_not_filters() {
+        let filter = filter("_sort=id&_order=ASC&_start=0&_end=10").unwrap();
+        assert!(filter.is_empty());
+        assert!(!filter.include_deleted);
+    }
+
+    #[test]
+    fn conditions_are_combined() {
+        let sql = sql("id=1&id=2&name_like=a%25&count_gte=3&include_deleted=true");
+        assert!(
+            sql.contains(r#"(("item"."id" = $1) OR ("item"."id" = $2))"#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#""item"."name" ILIKE $3"#), "{sql}");
+        assert!(sql.contains(r#""item"."count" >= $4"#), "{sql}");
+        assert!(sql.contains(r#""%a\\%%""#), "{sql}");
+        assert!(!sql.contains("IS NULL"), "{sql}");
+    }
+
+    #[test]
+    fn deleted_are_excluded_by_default() {
+        let sql = sql("q=x");
+        assert!(sql.contains(r#""item"."name" ILIKE $1"#), "{sql}");
+        assert!(sql.contains(r#""item"."deleted_at" IS NULL"#), "{sql}");
+    }
+}
@@ -0,0 +1,430 @@
+//! Bulk import of CSV and JSON Lines files.
+//!
+//! Each row is deserialized into the insert type of the resource and stored in its own savepoint, so failed rows are
+//! reported and skipped while the rest of the file is imported in one transaction. In dry run the whole transaction
+//! is rolled back, the report shows what would be done.
+
+use actix_web::http::header::{self, Header};
+use actix_web::http::StatusCode;
+use anyhow::{anyhow, Result};
+use diesel::{Connection, PgConnection};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use utoipa::openapi::schema::{ArrayBuilder, ObjectBuilder, SchemaType};
+use utoipa::openapi::{RefOr, Schema};
+use utoipa::IntoParams;
+
+use crate::export::Format;
+
+/// Limit of uploaded file size
+pub const MAX_SIZE: usize = 32 * 1024 * 1024;
+
+/// Import request data
+#[derive(serde::Deserialize, IntoParams)]
+pub struct ImportRequest {
+    /// Validate and import rows, but roll back all changes
+    #[serde(default)]
+    pub dry_run: bool,
+    /// File format: csv or jsonl. If not set, it is chosen by `Content-Type` header, CSV by default
+    #[serde(rename = "_format", default)]
+    #[param(value_type = Option<String>)]
+    pub format: Option<Format>,
+}
+
+/// Uploaded file
+pub struct ImportFile {
+    pub format: Format,
+    pub data: Vec<u8>,
+}
+
+impl ImportFile {
+    /// Reads request body of `format` set by `_format` param or `Content-Type` header
+    pub async fn new(
+        req: &actix_web::HttpRequest,
+        query: &ImportRequest,
+        mut payload: actix_web::web::Payload,
+    ) -> actix_web::Result<Self> {
+        let format = match query.format {
+            Some(v) => v,
+            None => header::ContentType::parse(req)
+                .ok()
+                .and_then(|v| Format::of_mime(v.0.essence_str()))
+                .unwrap_or(Format::Csv),
+        };
+        if format == Format::Xlsx {
+            return Err(actix_web::error::InternalError::new(
+                "Only CSV and JSON Lines files can be imported",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            )
+            .into());
+        }
+
+        let mut data = Vec::new();
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk?;
+            if data.len() + chunk.len() > MAX_SIZE {
+                return Err(actix_web::error::InternalError::new(
+                    format!("File is larger than {MAX_SIZE} bytes"),
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                )
+                .into());
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(Self { format, data })
+    }
+
+    /// Rows of the file with their line numbers, rows which cannot be deserialized are errors
+    pub fn rows<T: DeserializeOwned>(&self) -> Result<Vec<Row<T>>> {
+        match self.format {
+            Format::Csv => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .trim(csv::Trim::All)
+                    .from_reader(self.data.as_slice());
+                let headers = reader.headers()?.clone();
+                Ok(reader
+                    .records()
+                    .map(|record| match record {
+                        Ok(v) => Row {
+                            line: v.position().map(|v| v.line()).unwrap_or_default(),
+                            data: v.deserialize(Some(&headers)).map_err(|err| err.to_string()),
+                        },
+                        Err(err) => Row {
+                            line: err.position().map(|v| v.line()).unwrap_or_default(),
+                            data: Err(err.to_string()),
+                        },
+                    })
+                    .collect())
+            }
+            Format::Jsonl => {
+                let text = std::str::from_utf8(&self.data)?;
+                Ok(text
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, v)| !v.trim().is_empty())
+                    .map(|(i, v)| Row {
+                        line: i as u64 + 1,
+                        data: serde_json::from_str(v).map_err(|err| err.to_string()),
+                    })
+                    .collect())
+            }
+            Format::Xlsx => Err(anyhow!("XLSX files cannot be imported")),
+        }
+    }
+}
+
+/// Row of imported file
+pub struct Row<T> {
+    /// Line number in the file, starting from 1
+    pub line: u64,
+    pub data: Result<T, String>,
+}
+
+/// What is done with imported row
+pub enum Imported<Id> {
+    Created(Id),
+    Updated(Id),
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    Created,
+    Updated,
+    Failed,
+}
+
+#[derive(Serialize)]
+pub struct RowReport<Id> {
+    pub line: u64,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<Id>,
+    /// Why row is not imported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of import, rows are in the order of the file
+#[derive(Serialize)]
+pub struct ImportReport<Id> {
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub failed: usize,
+    pub rows: Vec<RowReport<Id>>,
+}
+
+impl<Id> ImportReport<Id> {
+    fn push(&mut self, line: u64, r: Result<Imported<Id>, String>) {
+        let (status, id, error) = match r {
+            Ok(Imported::Created(id)) => (RowStatus::Created, Some(id), None),
+            Ok(Imported::Updated(id)) => (RowStatus::Updated, Some(id), None),
+            Err(err) => (RowStatus::Failed, None, Some(err)),
+        };
+        match status {
+            RowStatus::Created => self.created += 1,
+            RowStatus::Updated => self.updated += 1,
+            RowStatus::Failed => self.failed += 1,
+        }
+        self.rows.push(RowReport {
+            line,
+            status,
+            id,
+            error,
+        });
+    }
+}
+
+/// Imports `rows` with `import`, each in its own savepoint. Must be called inside a transaction, e.g.
+/// [`Pool::with_transaction`](database_pg::Pool::with_transaction)
+pub fn run<T, Id>(
+    conn: &mut PgConnection,
+    rows: Vec<Row<T>>,
+    dry_run: bool,
+    mut import: impl FnMut(&mut PgConnection, T) -> Result<Imported<Id>>,
+) -> Result<ImportReport<Id>> {
+    let mut report = ImportReport {
+        dry_run,
+        created: 0,
+        updated: 0,
+        failed: 0,
+        rows: Vec::with_capacity(rows.len()),
+    };
+    let r = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        for row in rows {
+            let r = row.data.and_then(|data| {
+                conn.transaction::<_, anyhow::Error, _>(|conn| import(conn, data))
+                    .map_err(|err| crate::crud::error_message(&err))
+            });
+            report.push(row.line, r);
+        }
+        if dry_run {
+            Err(diesel::result::Error::RollbackTransaction)
+        } else {
+            Ok(())
+        }
+    });
+    match r {
+        Ok(()) | Err(diesel::result::Error::RollbackTransaction) => Ok(report),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// OpenAPI schema of [`ImportReport`]
+pub fn report_schema(id: RefOr<Schema>) -> Schema {
+    let integer = || ObjectBuilder::new().schema_type(SchemaType::Integer);
+    let row = ObjectBuilder::new()
+        .property(
+            "line",
+            integer().description(Some("Line number in the file, starting from 1")),
+        )
+        .required("line")
+        .property(
+            "status",
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .enum_values(Some(["created", "updated", "failed"])),
+        )
+        .required("status")
+        .property("id", id)
+        .property(
+            "error",
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .description(Some("Why row is not imported")),
+        );
+    ObjectBuilder::new()
+        .property(
+            "dry_run",
+            ObjectBuilder::new().schema_type(SchemaType::Boolean),
+        )
+        .required("dry_run")
+        .property("created", integer())
+        .required("created")
+        .property("updated", integer())
+        .required("updated")
+        .property("failed", integer())
+        .required("failed")
+        .property("rows", ArrayBuilder::new().items(row))
+        .required("rows")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::RunQueryDsl;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Item {
+        name: String,
+        count: i64,
+    }
+
+    fn file(format: Format, data: &str) -> ImportFile {
+        ImportFile {
+            format,
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    fn summary(rows: Vec<Row<Item>>) -> Vec<(u64, Result<Item, bool>)> {
+        rows.into_iter()
+            .map(|v| (v.line, v.data.map_err(|err| !err.is_empty())))
+            .collect()
+    }
+
+    #[test]
+    fn csv_rows_are_deserialized_by_header() {
+        let rows = file(Format::Csv, "count,name\n1, a \nx,b\n3,c\n")
+            .rows::<Item>()
+            .unwrap();
+        assert_eq!(
+            summary(rows),
+            vec![
+                (
+                    2,
+                    Ok(Item {
+                        name: "a".to_owned(),
+                        count: 1
+                    })
+                ),
+                (3, Err(true)),
+                (
+                    4,
+                    Ok(Item {
+                        name: "c".to_owned(),
+                        count: 3
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn jsonl_empty_lines_are_skipped() {
+        let rows = file(
+            Format::Jsonl,
+            "{\"name\":\"a\",\"count\":1}\n\n{\"name\":\"b\"}\n{\"name\":\"c\",\"count\":3}",
+        )
+        .rows::<Item>()
+        .unwrap();
+        assert_eq!(
+            summary(rows),
+            vec![
+                (
+                    1,
+                    Ok(Item {
+                        name: "a".to_owned(),
+                        count: 1
+                    })
+                ),
+                (3, Err(true)),
+                (
+                    4,
+                    Ok(Item {
+                        name: "c".to_owned(),
+                        count: 3
+                    })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn xlsx_is_not_imported() {
+        assert!(file(Format::Xlsx, "").rows::<Item>().is_err());
+    }
+
+    /// Connection to test DB at `DATABASE_URL` in a transaction which is never committed
+    fn connection() -> PgConnection {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+        let mut conn = PgConnection::establish(&url).unwrap();
+        conn.begin_test_transaction().unwrap();
+        let _ = diesel::sql_query("CREATE TEMPORARY TABLE import_test (name TEXT PRIMARY KEY)")
+            .execute(&mut conn)
+            .unwrap();
+        conn
+    }
+
+    fn import(conn: &mut PgConnection, item: Item) -> Result<Imported<String>> {
+        if item.count < 0 {
+            return Err(crate::crud::Invalid("Negative count".to_owned()).into());
+        }
+        let _ = diesel::sql_query("INSERT INTO import_test (name) VALUES ($1)")
+            .bind::<diesel::sql_types::Text, _>(&item.name)
+            .execute(conn)?;
+        Ok(Imported::Created(item.name))
+    }
+
+    fn names(conn: &mut PgConnection) -> Vec<String> {
+        #[derive(diesel::QueryableByName)]
+        struct Name {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            name: String,
+        }
+        diesel::sql_query("SELECT name FROM import_test ORDER BY name")
+            .load::<Name>(conn)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.name)
+            .collect()
+    }
+
+    const ROWS: &str = "name,count\na,1\nb,-1\na,2\nc,x\nd,4\n";
+
+    #[test]
+    #[ignore = "requires DATABASE_URL"]
+    fn failed_rows_are_reported_and_skipped() {
+        let mut conn = connection();
+        let rows = file(Format::Csv, ROWS).rows().unwrap();
+        let report = run(&mut conn, rows, false, import).unwrap();
+        assert_eq!((report.created, report.updated, report.failed), (2, 0, 3));
+        let errors: Vec<_> = report
+            .rows
+            .iter()
+            .map(|v| (v.line, v.status, v.error.as_deref()))
+            .collect();
+        assert_eq!(errors[0], (2, RowStatus::Created, None));
+        assert_eq!(errors[1], (3, RowStatus::Failed, Some("Negative count")));
+        assert_eq!(
+            errors[2],
+            (
+                4,
+                RowStatus::Failed,
+                Some("duplicate key value violates unique constraint \"import_test_pkey\"")
+            )
+        );
+        assert_eq!((errors[3].0, errors[3].1), (5, RowStatus::Failed));
+        assert_eq!(errors[4], (6, RowStatus::Created, None));
+        assert_eq!(names(&mut conn), ["a", "d"]);
+    }
+
+    #[test]
+    #[ignore = "requires DATABASE_URL"]
+    fn dry_run_is_rolled_back() {
+        let mut conn = connection();
+        let rows = file(Format::Csv, ROWS).rows().unwrap();
+        let report = run(&mut conn, rows, true, import).unwrap();
+        assert!(report.dry_run);
+        assert_eq!((report.created, report.updated, report.failed), (2, 0, 3));
+        assert!(names(&mut conn).is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires DATABASE_URL"]
+    fn internal_errors_are_not_shown() {
+        let mut conn = connection();
+        let rows = file(Format::Jsonl, "{\"name\":\"a\",\"count\":1}\n")
+            .rows()
+            .unwrap();
+        let report = run(&mut conn, rows, false, |conn, item: Item| {
+            let _ = diesel::sql_query("SELECT no_such_column FROM import_test").execute(conn)?;
+            import(conn, item)
+        })
+        .unwrap();
+        assert_eq!(report.rows[0].error.as_deref(), Some("Internal error"));
+    }
+}
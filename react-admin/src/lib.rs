@@ -1,8 +1,28 @@
+pub mod crud;
+pub mod cursor;
 pub mod db;
+pub mod export;
+pub mod filter;
+pub mod import;
 pub mod request_list;
+pub mod response;
+pub mod sort;
+pub mod typescript;
 
 use actix_web::body::BoxBody;
 
+/// Resource exposed to react-admin, usually response type of the list endpoint
+pub trait Resource: 'static {
+    /// Diesel table (or join) the list is queried from
+    type QuerySource;
+}
+
+/// Body of successful response without data
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct OKResponse;
+
+/// List response: JSON, MessagePack or CBOR array, chosen by `Accept` header, with total count in `X-Total-Count` and
+/// `Content-Range` headers
 pub struct APIList<DATA> {
     pub total_count: usize,
     pub list: std::vec::Vec<DATA>,
@@ -24,34 +44,56 @@ pub fn ok(list: std::vec::Vec<DATA>, total_count: i64) -> actix_web::Result<APIL
 impl<DATA: serde::Serialize> actix_web::Responder for APIList<DATA> {
     type Body = BoxBody;
 
-    fn respond_to(self, _req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
-        let body: String = serde_json::ser::to_string(&self.list).unwrap();
-        actix_web::HttpResponseBuilder::new(actix_web::http::StatusCode::OK)
-            .append_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
-            .append_header(("X-Total-Count", self.total_count.to_string()))
-            .body(body)
+    fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        let content_range = crate::response::content_range(
+            crate::response::request_start(req),
+            self.list.len(),
+            self.total_count as i64,
+        );
+        let mut r = actix_web::HttpResponseBuilder::new(actix_web::http::StatusCode::OK);
+        r.append_header(("X-Total-Count", self.total_count.to_string()))
+            .append_header((actix_web::http::header::CONTENT_RANGE, content_range));
+        crate::response::respond(req, r, &self.list, None)
     }
 }
 
-pub struct APIObject<DATA>(DATA);
+/// Single object response, serialized as [`APIList`]
+pub struct APIObject<DATA> {
+    data: DATA,
+    status: actix_web::http::StatusCode,
+    version: Option<String>,
+}
 
 impl<DATA> APIObject<DATA> {
     pub fn new(val: DATA) -> APIObject<DATA> {
-        Self(val)
+        Self {
+            data: val,
+            status: actix_web::http::StatusCode::OK,
+            version: None,
+        }
     }
 
     pub fn ok(val: DATA) -> actix_web::Result<APIObject<DATA>> {
         Ok(Self::new(val))
     }
+
+    pub fn status(mut self, status: actix_web::http::StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Object version, sent in `ETag` together with hash of the body and checked in `If-Match`
+    pub fn version(mut self, version: Option<String>) -> Self {
+        self.version = version;
+        self
+    }
 }
 
 impl<DATA: serde::Serialize> actix_web::Responder for APIObject<DATA> {
     type Body = BoxBody;
 
-    fn respond_to(self, _req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
-        let body: String = serde_json::ser::to_string(&self.0).unwrap();
-        actix_web::HttpResponseBuilder::new(actix_web::http::StatusCode::OK)
-            .append_header((actix_web::http::header::CONTENT_TYPE, "application/json"))
-            .body(body)
+    fn respond_to(self, req: &actix_web::HttpRequest) -> actix_web::HttpResponse<Self::Body> {
+        let r = actix_web::HttpResponseBuilder::new(self.status);
+        crate::response::respond(req, r, &self.data, self.version.as_deref())
     }
 }
@@ -0,0 +1,189 @@
+//! Serialization of API responses: format negotiated by `Accept` header, conditional requests with `ETag`.
+
+use actix_web::http::header::{self, EntityTag, Header};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use base64::Engine;
+use serde::Serialize;
+use sha2::Digest;
+
+/// Response headers read by react-admin, must be exposed to it through CORS
+pub const EXPOSED_HEADERS: &str = "X-Total-Count, Content-Range, Link, ETag";
+
+/// Serialization format of response body
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Encoding {
+    /// The most preferred format in `Accept` header, JSON if none is supported
+    pub fn of_request(req: &HttpRequest) -> Self {
+        header::Accept::parse(req)
+            .map(|v| v.ranked())
+            .unwrap_or_default()
+            .iter()
+            .find_map(|v| Self::of_mime(v.essence_str()))
+            .unwrap_or(Encoding::Json)
+    }
+
+    fn of_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "application/json" | "*/*" | "application/*" => Some(Encoding::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Encoding::MessagePack)
+            }
+            "application/cbor" => Some(Encoding::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            Encoding::MessagePack => "application/msgpack",
+            Encoding::Cbor => "application/cbor",
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value).map_err(|err| err.to_string()),
+            Encoding::MessagePack => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+            Encoding::Cbor => {
+                let mut r = Vec::new();
+                ciborium::into_writer(value, &mut r).map_err(|err| err.to_string())?;
+                Ok(r)
+            }
+        }
+    }
+}
+
+fn hash(body: &[u8]) -> String {
+    let hash = sha2::Sha256::digest(body);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&hash[..16])
+}
+
+/// Weak, since compression middleware may change bytes of the body
+fn etag(body: &[u8]) -> EntityTag {
+    EntityTag::new_weak(hash(body))
+}
+
+fn not_modified(req: &HttpRequest, etag: &EntityTag) -> bool {
+    if req.method() != actix_web::http::Method::GET && req.method() != actix_web::http::Method::HEAD
+    {
+        return false;
+    }
+    match header::IfNoneMatch::parse(req) {
+        Ok(header::IfNoneMatch::Any) => true,
+        Ok(header::IfNoneMatch::Items(items)) => items.iter().any(|v| v.weak_eq(etag)),
+        Err(_) => false,
+    }
+}
+
+/// Offset of list page in request with `_start`/`_end` pagination
+pub(crate) fn request_start(req: &HttpRequest) -> u64 {
+    serde_urlencoded::from_str::<crate::request_list::PaginatedRequest>(req.query_string())
+        .map(|v| v.start)
+        .unwrap_or_default()
+}
+
+/// `Content-Range` of list page, as expected by ra-data-simple-rest
+pub(crate) fn content_range(start: u64, len: usize, total_count: i64) -> String {
+    if len == 0 {
+        format!("items */{total_count}")
+    } else {
+        format!("items {start}-{}/{total_count}", start + len as u64 - 1)
+    }
+}
+
+/// Strong `ETag` of object version and hash of the body. `If-Match` is checked only against the version with
+/// [`version_matches`], while `If-None-Match` compares the whole tag, so fields changed without new version, like last
+/// login date, are not missed. Compression, by the app or a proxy, may make the tag weak
+fn version_etag(version: &str, body: &[u8]) -> EntityTag {
+    EntityTag::new_strong(format!("{}.{}", version.replace('"', ""), hash(body)))
+}
+
+/// Whether `etag` sent by [`respond`] is of object `version`. Only the version is compared, so tags made weak by
+/// compression match too
+pub fn version_matches(etag: &EntityTag, version: &str) -> bool {
+    // the hash has no dots, version may have them
+    etag.tag()
+        .rsplit_once('.')
+        .is_some_and(|(v, _)| v == version.replace('"', ""))
+}
+
+/// Serializes `value` in the format requested by client and completes the response. `ETag` is the hash of the body,
+/// prefixed with object `version` if it is set. Responds with 304 if client has the same data, and with 500 if
+/// serialization fails
+pub(crate) fn respond<T: Serialize>(
+    req: &HttpRequest,
+    mut builder: HttpResponseBuilder,
+    value: &T,
+    version: Option<&str>,
+) -> HttpResponse {
+    let encoding = Encoding::of_request(req);
+    let body = match encoding.encode(value) {
+        Ok(v) => v,
+        Err(err) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Failed to serialize response: {err}"))
+        }
+    };
+    let etag = match version {
+        Some(v) => version_etag(v, &body),
+        None => etag(&body),
+    };
+
+    builder
+        .insert_header((header::VARY, "Accept"))
+        .insert_header((header::ACCESS_CONTROL_EXPOSE_HEADERS, EXPOSED_HEADERS));
+    if not_modified(req, &etag) {
+        return builder
+            .status(actix_web::http::StatusCode::NOT_MODIFIED)
+            .insert_header(header::ETag(etag))
+            .finish();
+    }
+    builder
+        .insert_header((header::CONTENT_TYPE, encoding.content_type()))
+        .insert_header(header::ETag(etag))
+        .body(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_is_matched_without_body_hash() {
+        let etag = version_etag("2026-10-19T09:00:00.123Z", b"{}");
+        assert!(version_matches(&etag, "2026-10-19T09:00:00.123Z"));
+        assert!(!version_matches(&etag, "2026-10-19T09:00:00.124Z"));
+    }
+
+    #[test]
+    fn body_change_changes_etag_of_the_same_version() {
+        let before = version_etag("1", br#"{"login_count":1}"#);
+        let after = version_etag("1", br#"{"login_count":2}"#);
+        assert!(!before.strong_eq(&after));
+        assert!(version_matches(&after, "1"));
+    }
+
+    #[test]
+    fn weak_etag_matches_version() {
+        let etag = version_etag("1", b"{}");
+        let weak = EntityTag::new_weak(etag.tag().to_owned());
+        assert!(version_matches(&weak, "1"));
+        assert!(!version_matches(&weak, "2"));
+    }
+
+    #[test]
+    fn hash_only_etag_does_not_match_version() {
+        assert!(!version_matches(&etag(b"1"), "1"));
+        assert!(!version_matches(
+            &EntityTag::new_strong("1".to_owned()),
+            "1"
+        ));
+    }
+}
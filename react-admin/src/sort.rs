@@ -0,0 +1,294 @@
+use diesel::expression::expression_types::NotSelectable;
+use diesel::expression::BoxableExpression;
+use diesel::pg::Pg;
+use diesel::query_dsl::methods::ThenOrderDsl;
+use diesel::sql_types::{Bool, SqlType};
+use diesel::ExpressionMethods;
+use utoipa::IntoParams;
+
+use crate::filter::FilterExpression;
+use crate::Resource;
+
+/// Sort order
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    #[serde(rename = "ASC", alias = "asc")]
+    Asc,
+    #[serde(rename = "DESC", alias = "desc")]
+    Desc,
+}
+
+impl Order {
+    pub fn reverse(self) -> Self {
+        match self {
+            Order::Asc => Order::Desc,
+            Order::Desc => Order::Asc,
+        }
+    }
+}
+
+/// Sort request data
+#[derive(serde::Deserialize, IntoParams)]
+pub struct SortRequest {
+    /// Field to sort by
+    #[serde(rename = "_sort", default)]
+    pub sort: Option<String>,
+    /// Sort order: ASC or DESC
+    #[serde(rename = "_order", default)]
+    #[param(value_type = Option<String>)]
+    pub order: Option<Order>,
+}
+
+/// Boxed `ORDER BY` expression for query on `QS`
+pub type OrderExpression<QS> = Box<dyn BoxableExpression<QS, Pg, SqlType = NotSelectable>>;
+
+/// Comparison of column with a value of cursor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Cmp {
+    Gt,
+    Lt,
+    Eq,
+}
+
+type KeysetBuilder<QS> =
+    Box<dyn Fn(Cmp, &serde_json::Value) -> Result<FilterExpression<QS>, String>>;
+
+/// Column which can be used for sorting
+pub struct SortColumn<QS> {
+    pub name: &'static str,
+    order_by: Box<dyn Fn(Order) -> OrderExpression<QS>>,
+    keyset: Option<KeysetBuilder<QS>>,
+}
+
+impl<QS> SortColumn<QS> {
+    pub fn new<C>(name: &'static str, column: C) -> Self
+    where
+        C: ExpressionMethods + Copy + 'static,
+        diesel::dsl::Asc<C>: BoxableExpression<QS, Pg, SqlType = NotSelectable> + 'static,
+        diesel::dsl::Desc<C>: BoxableExpression<QS, Pg, SqlType = NotSelectable> + 'static,
+    {
+        Self {
+            name,
+            order_by: Box::new(move |order| match order {
+                Order::Asc => Box::new(column.asc()),
+                Order::Desc => Box::new(column.desc()),
+            }),
+            keyset: None,
+        }
+    }
+
+    /// Column which can also be used with [`CursorPagination`](crate::cursor::CursorPagination). Column must be not
+    /// nullable, `T` must be serialized in response the same way as it is deserialized from cursor
+    pub fn keyset<T, C>(name: &'static str, column: C) -> Self
+    where
+        T: serde::de::DeserializeOwned + diesel::expression::AsExpression<C::SqlType> + 'static,
+        C: ExpressionMethods + Copy + 'static,
+        C::SqlType: SqlType,
+        diesel::dsl::Asc<C>: BoxableExpression<QS, Pg, SqlType = NotSelectable> + 'static,
+        diesel::dsl::Desc<C>: BoxableExpression<QS, Pg, SqlType = NotSelectable> + 'static,
+        diesel::dsl::Gt<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        diesel::dsl::Lt<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+        diesel::dsl::Eq<C, T>: BoxableExpression<QS, Pg, SqlType = Bool> + 'static,
+    {
+        Self {
+            keyset: Some(Box::new(move |cmp, value| {
+                let value = T::deserialize(value)
+                    .map_err(|err| format!("Invalid cursor value of {name:?}: {err}"))?;
+                Ok(match cmp {
+                    Cmp::Gt => Box::new(column.gt(value)),
+                    Cmp::Lt => Box::new(column.lt(value)),
+                    Cmp::Eq => Box::new(column.eq(value)),
+                })
+            })),
+            ..Self::new(name, column)
+        }
+    }
+
+    pub fn order_by(&self, order: Order) -> OrderExpression<QS> {
+        (self.order_by)(order)
+    }
+
+    pub fn supports_keyset(&self) -> bool {
+        self.keyset.is_some()
+    }
+
+    pub(crate) fn compare(
+        &self,
+        cmp: Cmp,
+        value: &serde_json::Value,
+    ) -> Result<FilterExpression<QS>, String> {
+        match &self.keyset {
+            Some(keyset) => keyset(cmp, value),
+            None => Err(format!(
+                "Cursor pagination is not supported for {:?}",
+                self.name
+            )),
+        }
+    }
+}
+
+/// Resource which list can be sorted
+pub trait Sortable: Resource {
+    /// Whitelist of columns allowed for sorting. The first column must be unique (usually it is primary key), it is
+    /// added to every sort to make pages stable
+    fn sort_columns() -> Vec<SortColumn<Self::QuerySource>>;
+
+    /// Sort used when request has no `_sort` param
+    fn default_sort() -> (&'static str, Order) {
+        (Self::sort_columns()[0].name, Order::Asc)
+    }
+}
+
+/// Validated sort request for resource `R`
+pub struct Sort<R> {
+    pub field: &'static str,
+    pub order: Order,
+    _resource: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<R: Sortable> Sort<R> {
+    pub(crate) fn new(field: &'static str, order: Order) -> Self {
+        Self {
+            field,
+            order,
+            _resource: std::marker::PhantomData,
+        }
+    }
+
+    /// Adds `ORDER BY` clauses to the query
+    pub fn apply<Q>(&self, query: Q) -> Q
+    where
+        Q: ThenOrderDsl<OrderExpression<R::QuerySource>, Output = Q>,
+    {
+        let columns = R::sort_columns();
+        let mut query = query;
+        if let Some(column) = columns.iter().find(|v| v.name == self.field) {
+            query = query.then_order_by(column.order_by(self.order));
+        }
+        if columns[0].name != self.field {
+            query = query.then_order_by(columns[0].order_by(self.order));
+        }
+        query
+    }
+}
+
+impl<R: Sortable> actix_web::FromRequest for Sort<R> {
+    type Error = actix_web::Error;
+    type Future = futures::future::Ready<actix_web::Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let query = req.query_string();
+        let query = match serde_urlencoded::from_str::<SortRequest>(query) {
+            Ok(v) => v,
+            Err(err) => {
+                return futures::future::err(actix_web::error::ErrorBadRequest(err.to_string()))
+            }
+        };
+
+        let (default_field, default_order) = R::default_sort();
+        let field = match query.sort {
+            None => default_field,
+            Some(sort) => {
+                let columns = R::sort_columns();
+                match columns.iter().find(|v| v.name == sort) {
+                    Some(v) => v.name,
+                    None => {
+                        let allowed: Vec<_> = columns.iter().map(|v| v.name).collect();
+                        return futures::future::err(actix_web::error::ErrorBadRequest(format!(
+                            "Cannot sort by {sort:?}, allowed _sort values are: {}",
+                            allowed.join(", ")
+                        )));
+                    }
+                }
+            }
+        };
+
+        futures::future::ok(Sort::new(field, query.order.unwrap_or(default_order)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::FromRequest;
+    use diesel::query_dsl::QueryDsl;
+
+    diesel::table! {
+        item (id) {
+            id -> Int8,
+            name -> Text,
+            secret -> Text,
+        }
+    }
+
+    struct Item;
+
+    impl Resource for Item {
+        type QuerySource = item::table;
+    }
+
+    impl Sortable for Item {
+        fn sort_columns() -> Vec<SortColumn<item::table>> {
+            vec![
+                SortColumn::new("id", item::id),
+                SortColumn::new("name", item::name),
+            ]
+        }
+    }
+
+    fn sort(query: &str) -> actix_web::Result<Sort<Item>> {
+        let req =
+            actix_web::test::TestRequest::with_uri(&format!("/item?{query}")).to_http_request();
+        Sort::<Item>::from_request(&req, &mut actix_web::dev::Payload::None).into_inner()
+    }
+
+    fn order_by(query: &str) -> String {
+        let query = sort(query).unwrap().apply(item::table.into_boxed());
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        sql.split_once(" ORDER BY ").unwrap().1.to_owned()
+    }
+
+    #[test]
+    fn column_not_in_whitelist_is_rejected() {
+        let err = sort("_sort=secret").err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            actix_web::http::StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            err.to_string(),
+            "Cannot sort by \"secret\", allowed _sort values are: id, name"
+        );
+        assert!(sort("_order=up").is_err());
+    }
+
+    #[test]
+    fn default_sort_is_by_unique_column() {
+        let sort = sort("").unwrap();
+        assert_eq!((sort.field, sort.order), ("id", Order::Asc));
+        assert_eq!(order_by(""), r#""item"."id" ASC -- binds: []"#);
+    }
+
+    #[test]
+    fn unique_column_breaks_ties() {
+        assert_eq!(
+            order_by("_sort=name&_order=DESC"),
+            r#""item"."name" DESC, "item"."id" DESC -- binds: []"#
+        );
+        assert_eq!(
+            order_by("_sort=name&_order=asc"),
+            r#""item"."name" ASC, "item"."id" ASC -- binds: []"#
+        );
+    }
+
+    #[test]
+    fn unique_column_is_not_repeated() {
+        assert_eq!(
+            order_by("_sort=id&_order=DESC"),
+            r#""item"."id" DESC -- binds: []"#
+        );
+    }
+}
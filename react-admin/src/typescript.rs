@@ -0,0 +1,687 @@
+//! Generator of TypeScript sources for react-admin frontend from OpenAPI documentation of the app: interfaces of
+//! schemas, dataProvider for the conventions of this crate, and skeleton list/edit/create components of resources.
+//!
+//! Resource is a path with `GET` returning an array, which also has `{id}` sub-path, like ones registered by
+//! [`CrudResource`](crate::crud::CrudResource).
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fmt::Write;
+
+const HEADER: &str =
+    "// Generated from OpenAPI documentation of the backend. Do not edit, regenerate instead.\n\n";
+
+/// Generated source file
+pub struct File {
+    pub name: &'static str,
+    pub content: String,
+}
+
+pub fn generate(openapi: &utoipa::openapi::OpenApi) -> Result<Vec<File>> {
+    let mut doc = serde_json::to_value(openapi)?;
+    let names: Vec<String> = doc
+        .pointer("/components/schemas")
+        .and_then(Value::as_object)
+        .map(|v| v.keys().cloned().collect())
+        .unwrap_or_default();
+    fix_refs(&mut doc, &names);
+    let schemas = doc
+        .pointer("/components/schemas")
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    let resources = resources(&doc, &schemas);
+
+    Ok(vec![
+        File {
+            name: "types.ts",
+            content: types(&schemas)?,
+        },
+        File {
+            name: "dataProvider.ts",
+            content: data_provider(&resources)?,
+        },
+        File {
+            name: "resources.tsx",
+            content: components(&resources)?,
+        },
+    ])
+}
+
+type Schemas = serde_json::Map<String, Value>;
+
+/// `Foo::Bar<T>` is not valid TypeScript name
+fn type_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// `audit_log` → `AuditLog`
+fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            let mut chars = v.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn property_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        name.to_owned()
+    } else {
+        Value::String(name.to_owned()).to_string()
+    }
+}
+
+fn ref_name(schema: &Value) -> Option<&str> {
+    schema
+        .get("$ref")?
+        .as_str()?
+        .strip_prefix("#/components/schemas/")
+}
+
+/// Fields of path-qualified types refer to `crate.db.user.UserId` while the schema is named `UserId`
+fn fix_refs(value: &mut Value, names: &[String]) {
+    match value {
+        Value::Object(v) => {
+            if let Some(Value::String(reference)) = v.get_mut("$ref") {
+                if let Some(name) = reference.strip_prefix("#/components/schemas/") {
+                    let short = name.rsplit('.').next().unwrap_or(name);
+                    if !names.iter().any(|v| v == name) && names.iter().any(|v| v == short) {
+                        *reference = format!("#/components/schemas/{short}");
+                    }
+                }
+            }
+            v.values_mut().for_each(|v| fix_refs(v, names));
+        }
+        Value::Array(v) => v.iter_mut().for_each(|v| fix_refs(v, names)),
+        _ => {}
+    }
+}
+
+fn resolve<'a>(schemas: &'a Schemas, schema: &'a Value) -> &'a Value {
+    match ref_name(schema).and_then(|v| schemas.get(v)) {
+        Some(v) => v,
+        None => schema,
+    }
+}
+
+fn doc_comment(out: &mut String, indent: &str, schema: &Value) -> std::fmt::Result {
+    let Some(description) = schema.get("description").and_then(Value::as_str) else {
+        return Ok(());
+    };
+    writeln!(out, "{indent}/**")?;
+    for line in description.replace("*/", "*\\/").lines() {
+        writeln!(out, "{indent} * {line}")?;
+    }
+    writeln!(out, "{indent} */")
+}
+
+fn ts_type(schema: &Value) -> String {
+    let r = ts_type_not_null(schema);
+    if schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+        format!("{r} | null")
+    } else {
+        r
+    }
+}
+
+fn ts_type_not_null(schema: &Value) -> String {
+    if let Some(name) = ref_name(schema) {
+        return type_name(name);
+    }
+    for (key, separator) in [("oneOf", " | "), ("anyOf", " | "), ("allOf", " & ")] {
+        if let Some(items) = schema.get(key).and_then(Value::as_array) {
+            let items: Vec<_> = items.iter().map(|v| format!("({})", ts_type(v))).collect();
+            return items.join(separator);
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values: Vec<_> = values.iter().map(Value::to_string).collect();
+        return values.join(" | ");
+    }
+    match schema.get("type").and_then(Value::as_str) {
+        Some("string") => "string".to_owned(),
+        Some("integer" | "number") => "number".to_owned(),
+        Some("boolean") => "boolean".to_owned(),
+        Some("array") => format!(
+            "Array<{}>",
+            schema.get("items").map(ts_type).unwrap_or("unknown".into())
+        ),
+        Some("object") => match schema.get("properties").and_then(Value::as_object) {
+            Some(properties) => {
+                let fields: Vec<_> = properties
+                    .iter()
+                    .map(|(name, v)| {
+                        let optional = if is_required(schema, name) { "" } else { "?" };
+                        format!("{}{optional}: {}", property_name(name), ts_type(v))
+                    })
+                    .collect();
+                format!("{{ {} }}", fields.join("; "))
+            }
+            None => match schema.get("additionalProperties") {
+                Some(v) if v.is_object() => format!("Record<string, {}>", ts_type(v)),
+                _ => "Record<string, unknown>".to_owned(),
+            },
+        },
+        _ => "unknown".to_owned(),
+    }
+}
+
+fn is_required(schema: &Value, name: &str) -> bool {
+    schema
+        .get("required")
+        .and_then(Value::as_array)
+        .is_some_and(|v| v.iter().any(|v| v == name))
+}
+
+fn types(schemas: &Schemas) -> Result<String> {
+    let mut out = HEADER.to_owned();
+    for (name, schema) in schemas {
+        doc_comment(&mut out, "", schema)?;
+        let name = type_name(name);
+        match schema.get("properties").and_then(Value::as_object) {
+            Some(properties) if schema.get("nullable") != Some(&Value::Bool(true)) => {
+                writeln!(out, "export interface {name} {{")?;
+                for (field, v) in properties {
+                    doc_comment(&mut out, "  ", v)?;
+                    let optional = if is_required(schema, field) { "" } else { "?" };
+                    writeln!(out, "  {}{optional}: {};", property_name(field), ts_type(v))?;
+                }
+                writeln!(out, "}}\n")?;
+            }
+            _ => writeln!(out, "export type {name} = {};\n", ts_type(schema))?,
+        }
+    }
+    Ok(out)
+}
+
+/// How field is shown and edited in react-admin
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Number,
+    Boolean,
+    Date,
+    DateTime,
+    /// Objects and arrays, not shown in skeleton components
+    Other,
+}
+
+impl Kind {
+    fn of(schemas: &Schemas, schema: &Value) -> Self {
+        let schema = resolve(schemas, schema);
+        if let Some(items) = schema.get("oneOf").and_then(Value::as_array) {
+            return items.first().map_or(Kind::Other, |v| Kind::of(schemas, v));
+        }
+        match (
+            schema.get("type").and_then(Value::as_str),
+            schema.get("format").and_then(Value::as_str),
+        ) {
+            (Some("string"), Some("date-time")) => Kind::DateTime,
+            (Some("string"), Some("date")) => Kind::Date,
+            (Some("string"), _) => Kind::Text,
+            (Some("integer" | "number"), _) => Kind::Number,
+            (Some("boolean"), _) => Kind::Boolean,
+            _ => Kind::Other,
+        }
+    }
+
+    fn field(self, source: &str) -> Option<String> {
+        Some(match self {
+            Kind::Text => format!("<TextField source=\"{source}\" />"),
+            Kind::Number => format!("<NumberField source=\"{source}\" />"),
+            Kind::Boolean => format!("<BooleanField source=\"{source}\" />"),
+            Kind::Date => format!("<DateField source=\"{source}\" />"),
+            Kind::DateTime => format!("<DateField source=\"{source}\" showTime />"),
+            Kind::Other => return None,
+        })
+    }
+
+    fn input(self, source: &str, extra: &str) -> Option<String> {
+        Some(match self {
+            Kind::Text => format!("<TextInput source=\"{source}\"{extra} />"),
+            Kind::Number => format!("<NumberInput source=\"{source}\"{extra} />"),
+            Kind::Boolean => format!("<BooleanInput source=\"{source}\"{extra} />"),
+            Kind::Date => format!("<DateInput source=\"{source}\"{extra} />"),
+            Kind::DateTime => format!("<DateTimeInput source=\"{source}\"{extra} />"),
+            Kind::Other => return None,
+        })
+    }
+}
+
+struct Resource {
+    /// Name in react-admin, the last segment of the path
+    name: String,
+    path: String,
+    fields: Vec<(String, Kind)>,
+    filters: Vec<(String, Kind)>,
+    search: bool,
+    create: Option<Vec<(String, Kind)>>,
+    update: Option<Vec<(String, Kind)>>,
+}
+
+fn properties(schemas: &Schemas, schema: &Value) -> Vec<(String, Kind)> {
+    resolve(schemas, schema)
+        .get("properties")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, v)| (name.clone(), Kind::of(schemas, v)))
+        .collect()
+}
+
+fn body_properties(schemas: &Schemas, operation: Option<&Value>) -> Option<Vec<(String, Kind)>> {
+    let schema = operation?.pointer("/requestBody/content/application~1json/schema")?;
+    Some(properties(schemas, schema))
+}
+
+fn resources(doc: &Value, schemas: &Schemas) -> Vec<Resource> {
+    let Some(paths) = doc.get("paths").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let mut r = Vec::new();
+    for (path, item) in paths {
+        let Some(list) = item.get("get") else {
+            continue;
+        };
+        let Some(element) = list.pointer("/responses/200/content/application~1json/schema/items")
+        else {
+            continue;
+        };
+        let Some(object) = paths.get(&format!("{path}/{{id}}")) else {
+            continue;
+        };
+        let params = list
+            .get("parameters")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|v| v.get("in").and_then(Value::as_str) == Some("query"));
+        let mut filters = Vec::new();
+        let mut search = false;
+        for param in params {
+            let Some(name) = param.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            if name == "q" {
+                search = true;
+            } else if !name.starts_with('_') {
+                let kind = param
+                    .get("schema")
+                    .map_or(Kind::Text, |v| Kind::of(schemas, v));
+                filters.push((name.to_owned(), kind));
+            }
+        }
+        r.push(Resource {
+            name: path.rsplit('/').next().unwrap_or_default().to_owned(),
+            path: path.clone(),
+            fields: properties(schemas, element),
+            filters,
+            search,
+            create: body_properties(schemas, item.get("post")),
+            update: body_properties(schemas, object.get("put")),
+        });
+    }
+    r
+}
+
+const DATA_PROVIDER: &str = r#"const httpClient = (url: string, options: fetchUtils.Options = {}) =>
+  fetchUtils.fetchJson(url, { credentials: "include", ...options });
+
+/** Query string, arrays are sent as repeated params */
+const query = (params: [string, unknown][]): string => {
+  const r = new URLSearchParams();
+  for (const [name, value] of params) {
+    for (const v of Array.isArray(value) ? value : [value]) {
+      if (v !== undefined && v !== null && v !== "") {
+        r.append(name, String(v));
+      }
+    }
+  }
+  return r.toString();
+};
+
+const ids = (ids: Identifier[]): [string, unknown][] => ids.map((id) => ["id", id]);
+
+/**
+ * Lists are paginated with `_start`/`_end`, sorted with `_sort`/`_order`, filters are query params named as the
+ * filter sources, total count is in `X-Total-Count` header. `ETag` of the last loaded version of record is sent in
+ * `If-Match` on update and delete, they fail with 412 if the record is changed by someone else
+ */
+export const dataProvider = (apiUrl = ""): DataProvider => {
+  const url = (resource: string) => apiUrl + (resourcePaths[resource] ?? `/${resource}`);
+  const versions = new Map<string, string>();
+  const item = async (resource: string, id: Identifier, options: fetchUtils.Options = {}) => {
+    const key = `${resource}/${id}`;
+    const headers = new Headers(options.headers as HeadersInit | undefined);
+    const version = versions.get(key);
+    if (version && options.method) {
+      headers.set("If-Match", version);
+    }
+    const r = await httpClient(`${url(resource)}/${encodeURIComponent(id)}`, { ...options, headers });
+    const etag = r.headers.get("etag");
+    if (etag) {
+      versions.set(key, etag);
+    }
+    return r;
+  };
+  const list = async (resource: string, params: GetListParams) => {
+    const { page, perPage } = params.pagination ?? { page: 1, perPage: 10 };
+    const sort: [string, unknown][] = params.sort
+      ? [["_sort", params.sort.field], ["_order", params.sort.order]]
+      : [];
+    const q = query([
+      ["_start", (page - 1) * perPage],
+      ["_end", page * perPage],
+      ...sort,
+      ...Object.entries(params.filter ?? {}),
+    ]);
+    const { headers, json } = await httpClient(`${url(resource)}?${q}`);
+    return { data: json, total: parseInt(headers.get("x-total-count") ?? "0", 10) };
+  };
+
+  return {
+    getList: list,
+    getOne: async (resource, params) => {
+      const { json } = await item(resource, params.id);
+      return { data: json };
+    },
+    getMany: async (resource, params) => {
+      const { json } = await httpClient(`${url(resource)}?${query(ids(params.ids))}`);
+      return { data: json };
+    },
+    getManyReference: (resource, params) =>
+      list(resource, { ...params, filter: { ...params.filter, [params.target]: params.id } }),
+    create: async (resource, params) => {
+      const { json } = await httpClient(url(resource), {
+        method: "POST",
+        body: JSON.stringify(params.data),
+      });
+      return { data: json };
+    },
+    update: async (resource, params) => {
+      const { json } = await item(resource, params.id, {
+        method: "PUT",
+        body: JSON.stringify(params.data),
+      });
+      return { data: json };
+    },
+    updateMany: async (resource, params) => {
+      const { json } = await httpClient(`${url(resource)}?${query(ids(params.ids))}`, {
+        method: "PUT",
+        body: JSON.stringify(params.data),
+      });
+      return { data: json };
+    },
+    delete: async (resource, params) => {
+      const { json } = await item(resource, params.id, {
+        method: "DELETE",
+      });
+      return { data: json };
+    },
+    deleteMany: async (resource, params) => {
+      const { json } = await httpClient(`${url(resource)}?${query(ids(params.ids))}`, {
+        method: "DELETE",
+      });
+      return { data: json };
+    },
+    /** Restores soft-deleted record */
+    restore: async (resource: string, params: { id: Identifier }) => {
+      const { json } = await httpClient(`${url(resource)}/${encodeURIComponent(params.id)}/restore`, {
+        method: "POST",
+      });
+      return { data: json };
+    },
+    restoreMany: async (resource: string, params: { ids: Identifier[] }) => {
+      const { json } = await httpClient(`${url(resource)}/restore?${query(ids(params.ids))}`, {
+        method: "POST",
+      });
+      return { data: json };
+    },
+    /** Bulk import of CSV or JSON Lines file, returns the report of each row */
+    import: async (resource: string, file: File, dryRun = false) => {
+      const type = file.type || (/\.(jsonl|ndjson)$/i.test(file.name) ? "application/jsonl" : "text/csv");
+      const { json } = await httpClient(`${url(resource)}/import?${query([["dry_run", dryRun]])}`, {
+        method: "POST",
+        body: file,
+        headers: new Headers({ "Content-Type": type }),
+      });
+      return { data: json };
+    },
+  };
+};
+"#;
+
+fn data_provider(resources: &[Resource]) -> Result<String> {
+    let mut out = HEADER.to_owned();
+    writeln!(
+        out,
+        "import {{ DataProvider, fetchUtils, GetListParams, Identifier }} from \"react-admin\";\n"
+    )?;
+    writeln!(out, "/** API paths of resources */")?;
+    writeln!(
+        out,
+        "export const resourcePaths: Record<string, string> = {{"
+    )?;
+    for resource in resources {
+        writeln!(
+            out,
+            "  {}: {},",
+            property_name(&resource.name),
+            Value::String(resource.path.clone())
+        )?;
+    }
+    writeln!(out, "}};\n")?;
+    out.push_str(DATA_PROVIDER);
+    Ok(out)
+}
+
+fn form(
+    out: &mut String,
+    component: &str,
+    name: &str,
+    fields: &[(String, Kind)],
+) -> std::fmt::Result {
+    writeln!(out, "export const {name}{component} = () => (")?;
+    writeln!(out, "  <{component}>")?;
+    writeln!(out, "    <SimpleForm>")?;
+    for (source, kind) in fields {
+        if let Some(v) = kind.input(source, "") {
+            writeln!(out, "      {v}")?;
+        }
+    }
+    writeln!(out, "    </SimpleForm>")?;
+    writeln!(out, "  </{component}>")?;
+    writeln!(out, ");\n")
+}
+
+fn components(resources: &[Resource]) -> Result<String> {
+    let mut out = HEADER.to_owned();
+    writeln!(
+        out,
+        "import {{\n  BooleanField,\n  BooleanInput,\n  Create,\n  Datagrid,\n  DateField,\n  DateInput,\n  \
+         DateTimeInput,\n  Edit,\n  List,\n  NumberField,\n  NumberInput,\n  Resource,\n  SearchInput,\n  \
+         SimpleForm,\n  TextField,\n  TextInput,\n}} from \"react-admin\";\n"
+    )?;
+
+    let mut registrations = Vec::new();
+    for resource in resources {
+        let name = pascal_case(&resource.name);
+        if name.is_empty() {
+            return Err(anyhow!("Invalid resource name {:?}", resource.name));
+        }
+        let filters = format!("{}{}Filters", name[..1].to_ascii_lowercase(), &name[1..]);
+        writeln!(out, "const {filters} = [")?;
+        if resource.search {
+            writeln!(out, "  <SearchInput key=\"q\" source=\"q\" alwaysOn />,")?;
+        }
+        for (source, kind) in &resource.filters {
+            let kind = if *kind == Kind::Other {
+                Kind::Text
+            } else {
+                *kind
+            };
+            if let Some(v) = kind.input(source, &format!(" key=\"{source}\"")) {
+                writeln!(out, "  {v},")?;
+            }
+        }
+        writeln!(out, "];\n")?;
+
+        writeln!(out, "export const {name}List = () => (")?;
+        writeln!(out, "  <List filters={{{filters}}}>")?;
+        if resource.update.is_some() {
+            writeln!(out, "    <Datagrid rowClick=\"edit\">")?;
+        } else {
+            writeln!(out, "    <Datagrid>")?;
+        }
+        for (source, kind) in &resource.fields {
+            if let Some(v) = kind.field(source) {
+                writeln!(out, "      {v}")?;
+            }
+        }
+        writeln!(out, "    </Datagrid>")?;
+        writeln!(out, "  </List>")?;
+        writeln!(out, ");\n")?;
+
+        let mut registration = format!(
+            "  <Resource key=\"{0}\" name=\"{0}\" list={{{name}List}}",
+            resource.name
+        );
+        if let Some(fields) = &resource.update {
+            form(&mut out, "Edit", &name, fields)?;
+            registration.push_str(&format!(" edit={{{name}Edit}}"));
+        }
+        if let Some(fields) = &resource.create {
+            form(&mut out, "Create", &name, fields)?;
+            registration.push_str(&format!(" create={{{name}Create}}"));
+        }
+        registration.push_str(" />,");
+        registrations.push(registration);
+    }
+
+    writeln!(out, "/** Children of `<Admin>` */")?;
+    writeln!(out, "export const resources = [")?;
+    for v in registrations {
+        writeln!(out, "{v}")?;
+    }
+    writeln!(out, "];")?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Resource with list, create and update, and path-qualified reference to ID schema
+    fn openapi() -> utoipa::openapi::OpenApi {
+        let item = serde_json::json!({
+            "description": "Item of the list",
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": {"$ref": "#/components/schemas/app.db.ItemId"},
+                "name": {"type": "string", "description": "Name of item"},
+                "created": {"type": "string", "format": "date-time"},
+                "tags": {"type": "array", "items": {"type": "string"}, "nullable": true},
+                "active": {"type": "boolean"},
+                "status": {"type": "string", "enum": ["new", "done"]},
+            },
+        });
+        let create = serde_json::json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": {"type": "string"},
+                "active": {"type": "boolean"},
+            },
+        });
+        let param = |name: &str, schema: Value| serde_json::json!({"name": name, "in": "query", "required": false, "schema": schema});
+        let body = |schema: &str| {
+            serde_json::json!({"content": {"application/json": {
+                "schema": {"$ref": format!("#/components/schemas/{schema}")},
+            }}})
+        };
+        serde_json::from_value(serde_json::json!({
+            "openapi": "3.0.3",
+            "info": {"title": "test", "version": "1"},
+            "paths": {
+                "/api/v1/item": {
+                    "get": {
+                        "parameters": [
+                            param("_start", serde_json::json!({"type": "integer"})),
+                            param("q", serde_json::json!({"type": "string"})),
+                            param("name", serde_json::json!({"type": "string"})),
+                            param("active", serde_json::json!({"type": "boolean"})),
+                        ],
+                        "responses": {"200": {"description": "List", "content": {"application/json": {
+                            "schema": {"type": "array", "items": {"$ref": "#/components/schemas/Item"}},
+                        }}}},
+                    },
+                    "post": {
+                        "requestBody": body("ItemCreate"),
+                        "responses": {"200": {"description": "Created"}},
+                    },
+                },
+                "/api/v1/item/{id}": {
+                    "put": {
+                        "requestBody": body("ItemCreate"),
+                        "responses": {"200": {"description": "Updated"}},
+                    },
+                },
+            },
+            "components": {"schemas": {
+                "Item": item,
+                "ItemCreate": create,
+                "ItemId": {"type": "integer", "format": "int64"},
+            }},
+        }))
+        .unwrap()
+    }
+
+    /// Compares generated file with the snapshot in `tests/snapshots`, or updates the snapshot if `UPDATE_SNAPSHOTS`
+    /// is set
+    fn assert_snapshot(file: &File) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/snapshots")
+            .join(file.name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &file.content).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            file.content == expected,
+            "{} differs from {path:?}, run tests with UPDATE_SNAPSHOTS=1 to update it:\n{}",
+            file.name,
+            file.content
+        );
+    }
+
+    #[test]
+    fn generated_sources_match_snapshots() {
+        let files = generate(&openapi()).unwrap();
+        let names: Vec<_> = files.iter().map(|v| v.name).collect();
+        assert_eq!(names, ["types.ts", "dataProvider.ts", "resources.tsx"]);
+        assert_snapshot(&files[0]);
+        assert_snapshot(&files[2]);
+        let paths = files[1].content.strip_suffix(DATA_PROVIDER).unwrap();
+        assert_eq!(
+            paths.strip_prefix(HEADER).unwrap(),
+            "import { DataProvider, fetchUtils, GetListParams, Identifier } from \"react-admin\";\n\n\
+             /** API paths of resources */\n\
+             export const resourcePaths: Record<string, string> = {\n  item: \"/api/v1/item\",\n};\n\n"
+        );
+    }
+}
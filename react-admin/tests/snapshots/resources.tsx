@@ -0,0 +1,61 @@
+// Generated from OpenAPI documentation of the backend. Do not edit, regenerate instead.
+
+import {
+  BooleanField,
+  BooleanInput,
+  Create,
+  Datagrid,
+  DateField,
+  DateInput,
+  DateTimeInput,
+  Edit,
+  List,
+  NumberField,
+  NumberInput,
+  Resource,
+  SearchInput,
+  SimpleForm,
+  TextField,
+  TextInput,
+} from "react-admin";
+
+const itemFilters = [
+  <SearchInput key="q" source="q" alwaysOn />,
+  <TextInput source="name" key="name" />,
+  <BooleanInput source="active" key="active" />,
+];
+
+export const ItemList = () => (
+  <List filters={itemFilters}>
+    <Datagrid rowClick="edit">
+      <BooleanField source="active" />
+      <DateField source="created" showTime />
+      <NumberField source="id" />
+      <TextField source="name" />
+      <TextField source="status" />
+    </Datagrid>
+  </List>
+);
+
+export const ItemEdit = () => (
+  <Edit>
+    <SimpleForm>
+      <BooleanInput source="active" />
+      <TextInput source="name" />
+    </SimpleForm>
+  </Edit>
+);
+
+export const ItemCreate = () => (
+  <Create>
+    <SimpleForm>
+      <BooleanInput source="active" />
+      <TextInput source="name" />
+    </SimpleForm>
+  </Create>
+);
+
+/** Children of `<Admin>` */
+export const resources = [
+  <Resource key="item" name="item" list={ItemList} edit={ItemEdit} create={ItemCreate} />,
+];
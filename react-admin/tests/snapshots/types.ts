@@ -0,0 +1,24 @@
+// Generated from OpenAPI documentation of the backend. Do not edit, regenerate instead.
+
+/**
+ * Item of the list
+ */
+export interface Item {
+  active?: boolean;
+  created?: string;
+  id: ItemId;
+  /**
+   * Name of item
+   */
+  name: string;
+  status?: "new" | "done";
+  tags?: Array<string> | null;
+}
+
+export interface ItemCreate {
+  active?: boolean;
+  name: string;
+}
+
+export type ItemId = number;
+
@@ -1,5 +1,5 @@
 use utoipa::{
-    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
     ToSchema,
 };
 
@@ -14,7 +14,12 @@ pub fn new() -> utoipa::openapi::OpenApi {
     );
     components.add_security_scheme(
         "authorization_header",
-        SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("Authorization"))),
+        SecurityScheme::Http(
+            HttpBuilder::new()
+                .scheme(HttpAuthScheme::Bearer)
+                .description(Some("Token in `Authorization: Bearer <token>` header"))
+                .build(),
+        ),
     );
 
     utoipa::openapi::OpenApiBuilder::new()
@@ -0,0 +1,124 @@
+//! `Authorization` header with `Bearer` scheme (RFC 6750) and challenges of rejected requests.
+//!
+//! Scheme name is case-insensitive, the token must be `b64token`. Requests without credentials or with a token which
+//! is not accepted are rejected with 401 and `WWW-Authenticate: Bearer` challenge, malformed header is rejected with
+//! 400.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+
+/// Scheme of `Authorization` header documented in OpenAPI
+pub const BEARER: &str = "Bearer";
+
+/// Error code of `WWW-Authenticate` challenge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BearerError {
+    /// Header is malformed, response is 400
+    InvalidRequest,
+    /// Token is unknown, expired or revoked
+    InvalidToken,
+}
+
+impl BearerError {
+    fn code(self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::InvalidToken => "invalid_token",
+        }
+    }
+}
+
+/// Rejection of request which is not authenticated
+#[derive(Debug)]
+pub struct Unauthorized {
+    error: Option<BearerError>,
+    description: &'static str,
+}
+
+impl Unauthorized {
+    /// No credentials are given, challenge has no error code as RFC 6750 requires
+    pub fn missing() -> Self {
+        Self {
+            error: None,
+            description: "Not authorized",
+        }
+    }
+
+    pub fn invalid_request(description: &'static str) -> Self {
+        Self {
+            error: Some(BearerError::InvalidRequest),
+            description,
+        }
+    }
+
+    pub fn invalid_token(description: &'static str) -> Self {
+        Self {
+            error: Some(BearerError::InvalidToken),
+            description,
+        }
+    }
+
+    /// Value of `WWW-Authenticate` header
+    pub fn challenge(&self) -> String {
+        match self.error {
+            None => BEARER.to_owned(),
+            Some(error) => format!(
+                r#"{BEARER} error="{}", error_description="{}""#,
+                error.code(),
+                self.description
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for Unauthorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.description)
+    }
+}
+
+impl std::error::Error for Unauthorized {}
+
+impl ResponseError for Unauthorized {
+    fn status_code(&self) -> StatusCode {
+        match self.error {
+            Some(BearerError::InvalidRequest) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .insert_header((header::WWW_AUTHENTICATE, self.challenge()))
+            .insert_header(header::ContentType::plaintext())
+            .body(self.description)
+    }
+}
+
+fn is_b64token(token: &str) -> bool {
+    let token = token.trim_end_matches('=');
+    !token.is_empty()
+        && token
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"-._~+/".contains(&c))
+}
+
+/// Token of `Authorization: Bearer <token>` header. `Ok(None)` if there is no header or it has other scheme
+pub fn bearer_token(req: &HttpRequest) -> Result<Option<&str>, Unauthorized> {
+    let value = match req.headers().get(header::AUTHORIZATION) {
+        None => return Ok(None),
+        Some(v) => v
+            .to_str()
+            .map_err(|_| Unauthorized::invalid_request("Malformed Authorization header"))?,
+    };
+    let (scheme, token) = value.split_once(' ').unwrap_or((value, ""));
+    if !scheme.eq_ignore_ascii_case(BEARER) {
+        return Ok(None);
+    }
+    let token = token.trim_start_matches(' ');
+    if is_b64token(token) {
+        Ok(Some(token))
+    } else {
+        Err(Unauthorized::invalid_request("Malformed bearer token"))
+    }
+}
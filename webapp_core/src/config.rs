@@ -24,6 +24,24 @@ pub struct CORS {
     pub origins: Vec<webapp_yaml_config::url::Url>,
 }
 
+#[derive(Serialize, Deserialize, StructDoc, Clone)]
+#[serde(default)]
+pub struct CSRF {
+    /// Reject unsafe requests authenticated with session cookie if they come from untrusted origin
+    pub enabled: bool,
+    /// Origins trusted besides the application itself and CORS origins
+    pub trusted_origins: Vec<webapp_yaml_config::url::Url>,
+}
+
+impl Default for CSRF {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trusted_origins: Vec::new(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, StructDoc, Clone)]
 pub struct Config {
     /// Bind web application to specified address. For example, "127.0.0.1"
@@ -42,6 +60,12 @@ pub struct Config {
     pub openapi: Option<OpenAPI>,
     /// CORS configuration
     pub cors: Option<CORS>,
+    /// Compress responses with gzip, brotli or zstd if client accepts it
+    #[serde(default)]
+    pub compress: bool,
+    /// CSRF protection of requests authenticated with session cookie, enabled by default
+    #[serde(default)]
+    pub csrf: CSRF,
 }
 
 impl Default for Config {
@@ -54,6 +78,8 @@ fn default() -> Self {
             logger: Default::default(),
             openapi: Some(OpenAPI::default()),
             cors: None,
+            compress: false,
+            csrf: CSRF::default(),
         }
     }
 }
@@ -0,0 +1,154 @@
+//! CSRF protection of requests authenticated with session cookie.
+//!
+//! Requests with unsafe methods which carry the session cookie must come from the application itself or from a trusted
+//! origin: CORS origins and `csrf.trusted_origins` of core config. Origin is taken from `Origin` header, or from
+//! `Referer` if there is no `Origin`, requests without both are rejected. Requests with `Authorization: Bearer` token
+//! are not checked, they are authenticated by the token and browsers never add it to cross-site requests on their own.
+//! Other schemes do not skip the check, since the session cookie is used for such requests.
+
+use actix_web::dev::ServiceRequest;
+use actix_web::http::{header, Method};
+use std::collections::HashSet;
+
+#[derive(Clone)]
+pub struct Csrf {
+    enabled: bool,
+    /// Trusted origins as `scheme://host[:port]`
+    trusted: HashSet<String>,
+}
+
+impl Csrf {
+    pub fn new(config: &crate::config::Config) -> Self {
+        let cors = config.cors.iter().flat_map(|v| v.origins.iter());
+        Self {
+            enabled: config.csrf.enabled,
+            trusted: config
+                .csrf
+                .trusted_origins
+                .iter()
+                .chain(cors)
+                .map(|v| v.origin().ascii_serialization())
+                .collect(),
+        }
+    }
+
+    /// Returns 403 if request may be forged
+    pub fn check(&self, req: &ServiceRequest) -> actix_web::Result<()> {
+        if !self.enabled
+            || matches!(
+                *req.method(),
+                Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+            )
+            || matches!(crate::auth::bearer_token(req.request()), Ok(Some(_)))
+            || req.cookie(crate::SESSION_COOKIE_NAME).is_none()
+        {
+            return Ok(());
+        }
+
+        let source = [header::ORIGIN, header::REFERER]
+            .iter()
+            .find_map(|v| req.headers().get(v))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| url::Url::parse(v).ok());
+        let source = match source {
+            Some(v) => v,
+            None => {
+                return Err(actix_web::error::ErrorForbidden(
+                    "CSRF check failed: no Origin or Referer header",
+                ))
+            }
+        };
+        if self
+            .trusted
+            .contains(&source.origin().ascii_serialization())
+        {
+            return Ok(());
+        }
+        // the application itself, scheme is not compared since TLS may be terminated by proxy
+        let host = match (source.host_str(), source.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => String::new(),
+        };
+        if !host.is_empty() && host.eq_ignore_ascii_case(req.connection_info().host()) {
+            return Ok(());
+        }
+        tracing::warn!("CSRF check failed for request from {source}");
+        Err(actix_web::error::ErrorForbidden(
+            "CSRF check failed: request from untrusted origin",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::test::TestRequest;
+
+    fn csrf() -> Csrf {
+        Csrf {
+            enabled: true,
+            trusted: HashSet::new(),
+        }
+    }
+
+    fn cross_site_post() -> TestRequest {
+        TestRequest::post()
+            .uri("http://app.example/api/v1/user")
+            .insert_header((header::HOST, "app.example"))
+            .insert_header((header::ORIGIN, "https://evil.example"))
+    }
+
+    #[test]
+    fn cookie_with_other_authorization_scheme_is_checked() {
+        let req = cross_site_post()
+            .cookie(Cookie::new(crate::SESSION_COOKIE_NAME, "session"))
+            .insert_header((header::AUTHORIZATION, "Basic eDp5"))
+            .to_srv_request();
+        assert!(csrf().check(&req).is_err());
+    }
+
+    #[test]
+    fn cookie_with_malformed_bearer_is_checked() {
+        let req = cross_site_post()
+            .cookie(Cookie::new(crate::SESSION_COOKIE_NAME, "session"))
+            .insert_header((header::AUTHORIZATION, "Bearer"))
+            .to_srv_request();
+        assert!(csrf().check(&req).is_err());
+    }
+
+    #[test]
+    fn cookie_without_token_is_checked() {
+        let req = cross_site_post()
+            .cookie(Cookie::new(crate::SESSION_COOKIE_NAME, "session"))
+            .to_srv_request();
+        assert!(csrf().check(&req).is_err());
+    }
+
+    #[test]
+    fn cookie_from_the_application_itself_passes() {
+        let req = TestRequest::post()
+            .uri("http://app.example/api/v1/user")
+            .insert_header((header::HOST, "app.example"))
+            .insert_header((header::ORIGIN, "https://app.example"))
+            .cookie(Cookie::new(crate::SESSION_COOKIE_NAME, "session"))
+            .to_srv_request();
+        assert!(csrf().check(&req).is_ok());
+    }
+
+    #[test]
+    fn bearer_token_is_not_checked() {
+        let req = cross_site_post()
+            .cookie(Cookie::new(crate::SESSION_COOKIE_NAME, "session"))
+            .insert_header((header::AUTHORIZATION, "Bearer dG9rZW4="))
+            .to_srv_request();
+        assert!(csrf().check(&req).is_ok());
+    }
+
+    #[test]
+    fn request_without_cookie_is_not_checked() {
+        let req = cross_site_post().to_srv_request();
+        assert!(csrf().check(&req).is_ok());
+    }
+}
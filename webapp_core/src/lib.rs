@@ -1,5 +1,7 @@
 mod apidoc;
+pub mod auth;
 pub mod config;
+pub mod csrf;
 pub mod logging;
 pub mod plugin;
 pub mod secstr;
@@ -13,6 +15,26 @@
 
 pub const SESSION_COOKIE_NAME: &str = "session";
 
+/// Request ID assigned by tracing middleware, the same as in `x-request-id` response header
+pub fn request_id<M: actix_web::HttpMessage>(req: &M) -> Option<String> {
+    req.extensions()
+        .get::<tracing_actix_web::RequestId>()
+        .map(|v| v.to_string())
+}
+
+/// OpenAPI documentation of all endpoints of plugins, the same as served by the app
+pub fn openapi(plugins: &[Arc<Mutex<Box<dyn crate::plugin::Plugin>>>]) -> utoipa::openapi::OpenApi {
+    let mut apidoc = crate::apidoc::new();
+    let mut app = App::new();
+    for plugin in plugins {
+        let guarded_plugin = plugin.lock().unwrap();
+        app = app.configure(|service_config| {
+            apidoc.merge(guarded_plugin.webapp_initializer(service_config))
+        })
+    }
+    apidoc
+}
+
 pub struct WebappCore {
     pub config: webapp_yaml_config::yaml::Config<crate::config::Config>,
 }
@@ -47,21 +69,23 @@ pub async fn run(self, plugins: Vec<Arc<Mutex<Box<dyn crate::plugin::Plugin>>>>)
         let app_config = config.clone();
         HttpServer::new(move || {
             let cors = Self::get_cors(&app_config.config);
+            let csrf = crate::csrf::Csrf::new(&app_config.config);
             let mut apidoc = crate::apidoc::new();
             let mut app = App::new()
+                .wrap_fn(move |req, srv| {
+                    use actix_web::dev::Service;
+                    use futures_util::future::{ready, Either};
+                    match csrf.check(&req) {
+                        Ok(()) => Either::Left(srv.call(req)),
+                        Err(err) => Either::Right(ready(Err(err))),
+                    }
+                })
                 .wrap_fn(|req, srv| {
                     use actix_web::{
                         dev::Service,
                         http::header::{HeaderName, HeaderValue},
-                        HttpMessage,
                     };
-                    use tracing_actix_web::RequestId;
-                    let request_id = req
-                        .extensions()
-                        .get::<RequestId>()
-                        .copied()
-                        .map(|v| v.to_string())
-                        .unwrap_or_default();
+                    let request_id = crate::request_id(&req).unwrap_or_default();
                     let res = srv.call(req);
                     async move {
                         tracing::debug!("New request");
@@ -74,6 +98,10 @@ pub async fn run(self, plugins: Vec<Arc<Mutex<Box<dyn crate::plugin::Plugin>>>>)
                         Ok(res)
                     }
                 })
+                .wrap(actix_web::middleware::Condition::new(
+                    app_config.config.compress,
+                    actix_web::middleware::Compress::default(),
+                ))
                 .wrap(TracingLogger::default())
                 .wrap(actix_web_opentelemetry::RequestTracing::new())
                 .wrap(cors);
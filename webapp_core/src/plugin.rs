@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use utoipa::OpenApi;
 
@@ -16,7 +16,7 @@ fn webapp_initializer(
 }
 
 #[async_trait]
-pub trait PluginMetadata {
+pub trait PluginMetadata: Sync {
     fn plugin_name(&self) -> &'static str;
 
     fn config_dump(&self) -> Result<Option<String>>;
@@ -29,7 +29,19 @@ fn new(configs_path: &std::path::Path) -> Result<Self>
 
     async fn init_plugin(&self) -> Result<Box<dyn Plugin>>;
 
+    /// Initializes plugin only to describe its API, e.g. for TypeScript generation. Plugins which have side effects
+    /// on initialization, like migrations or background jobs, skip them here
+    async fn init_documentation(&self) -> Result<Box<dyn Plugin>> {
+        self.init_plugin().await
+    }
+
     fn is_core(&self) -> bool {
         false
     }
+
+    /// Runs command of the plugin, `<app> plugin <NAME> ARGS...`. The first of `args` is the name of the command
+    /// itself for help messages, like in `std::env::args`
+    async fn run_command(&self, _args: &[String]) -> Result<()> {
+        Err(anyhow!("Plugin {:?} has no commands", self.plugin_name()))
+    }
 }